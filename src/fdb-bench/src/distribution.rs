@@ -0,0 +1,128 @@
+use clap::ValueEnum;
+use rand::Rng;
+
+/// How the keys an operation works on are picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Distribution {
+    /// Every key is equally likely.
+    Uniform,
+    /// A few keys, spread over the key space, are picked far more often than the rest.
+    Zipfian,
+    /// The most recently inserted keys are picked most often.
+    Latest,
+}
+
+/// Skew of the zipfian distribution, the one used by YCSB.
+const THETA: f64 = 0.99;
+
+/// Zipfian distribution over `0..items`, with 0 the most likely, following "Quickly Generating
+/// Billion-Record Synthetic Databases" by Gray et al. like YCSB's `ZipfianGenerator`.
+#[derive(Debug, Clone)]
+struct Zipfian {
+    items: u64,
+    zetan: f64,
+    alpha: f64,
+    eta: f64,
+}
+
+fn zeta(n: u64, theta: f64) -> f64 {
+    (1..=n).map(|i| 1.0 / (i as f64).powf(theta)).sum()
+}
+
+impl Zipfian {
+    fn new(items: u64) -> Self {
+        let items = items.max(1);
+        let zetan = zeta(items, THETA);
+        let eta = (1.0 - (2.0 / items as f64).powf(1.0 - THETA)) / (1.0 - zeta(2, THETA) / zetan);
+        Zipfian { items, zetan, alpha: 1.0 / (1.0 - THETA), eta }
+    }
+
+    fn next<R: Rng>(&self, rng: &mut R) -> u64 {
+        let u: f64 = rng.gen();
+        let uz = u * self.zetan;
+        if uz < 1.0 {
+            return 0;
+        }
+        if uz < 1.0 + 0.5f64.powf(THETA) {
+            return 1;
+        }
+        let item = (self.items as f64 * (self.eta * u - self.eta + 1.0).powf(self.alpha)) as u64;
+        item.min(self.items - 1)
+    }
+}
+
+/// FNV-1a, scattering the popular items of the zipfian distribution over the key space.
+fn fnv(item: u64) -> u64 {
+    item.to_be_bytes().iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Picks key indexes according to a `Distribution`.
+#[derive(Debug, Clone)]
+pub struct KeyChooser {
+    distribution: Distribution,
+    zipfian: Zipfian,
+}
+
+impl KeyChooser {
+    /// A chooser for key spaces of about `items` keys. The zipfian constants are computed once,
+    /// in time linear to `items`.
+    pub fn new(distribution: Distribution, items: u64) -> Self {
+        let zipfian = match distribution {
+            Distribution::Uniform => Zipfian::new(1),
+            _ => Zipfian::new(items),
+        };
+        KeyChooser { distribution, zipfian }
+    }
+
+    /// An index below `max`, the number of keys existing so far.
+    pub fn next<R: Rng>(&self, rng: &mut R, max: u64) -> u64 {
+        let max = max.max(1);
+        match self.distribution {
+            Distribution::Uniform => rng.gen_range(0..max),
+            Distribution::Zipfian => fnv(self.zipfian.next(rng)) % max,
+            Distribution::Latest => max - 1 - self.zipfian.next(rng).min(max - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn histogram(distribution: Distribution, max: u64) -> Vec<u64> {
+        let chooser = KeyChooser::new(distribution, max);
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = vec![0; max as usize];
+        for _ in 0..100_000 {
+            counts[chooser.next(&mut rng, max) as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn zipfian_is_skewed() {
+        let zipfian = Zipfian::new(1_000);
+        let mut rng = StdRng::seed_from_u64(7);
+        let picks: Vec<_> = (0..100_000).map(|_| zipfian.next(&mut rng)).collect();
+        assert!(picks.iter().all(|p| *p < 1_000));
+
+        // With theta 0.99 the first item is picked about 13% of the time
+        let first = picks.iter().filter(|p| **p == 0).count();
+        assert!((10_000..16_000).contains(&first), "{first}");
+    }
+
+    #[test]
+    fn distributions_stay_in_bounds() {
+        let uniform = histogram(Distribution::Uniform, 100);
+        assert!(uniform.iter().all(|c| (700..1_300).contains(c)), "{uniform:?}");
+
+        let latest = histogram(Distribution::Latest, 100);
+        assert!(latest[99] > latest[0] * 10, "{latest:?}");
+
+        let zipfian = histogram(Distribution::Zipfian, 100);
+        assert!(*zipfian.iter().max().unwrap() > 10_000, "{zipfian:?}");
+    }
+}
@@ -0,0 +1,351 @@
+//! Workloads driving a `Database` to measure the throughput and latency of the bindings.
+//!
+//! The YCSB core workloads A to F are modelled after their definitions in YCSB, along with a range
+//! scan and an atomic counter workload. Every operation runs in a transaction of its own, with up
+//! to `Config::concurrency` transactions in flight on a single task, so that the numbers reflect
+//! the bindings and the cluster rather than a thread pool.
+
+use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use futures::stream::FuturesUnordered;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use fdb::{BulkLoad, Database, Error, Key, KeySelector, LoadProgress, ReadOnlyTransaction, Subspace, Tenant, Transaction, Value};
+
+pub use distribution::{Distribution, KeyChooser};
+pub use stats::{Report, Summary};
+
+mod distribution;
+mod stats;
+
+/// The mix of operations a benchmark runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Workload {
+    /// Update heavy: 50% reads, 50% updates.
+    A,
+    /// Read mostly: 95% reads, 5% updates.
+    B,
+    /// Read only.
+    C,
+    /// Read latest: 95% reads, 5% inserts, reading the latest records most.
+    D,
+    /// Short ranges: 95% scans, 5% inserts.
+    E,
+    /// Read-modify-write: 50% reads, 50% read-modify-writes.
+    F,
+    /// Range scans only.
+    Scan,
+    /// Atomic increments of a few counters only.
+    Counter,
+}
+
+impl Workload {
+    /// The operation for a uniformly distributed `r` in `0..1`.
+    pub fn pick(self, r: f64) -> Operation {
+        match self {
+            Workload::A if r < 0.5 => Operation::Read,
+            Workload::A => Operation::Update,
+            Workload::B if r < 0.95 => Operation::Read,
+            Workload::B => Operation::Update,
+            Workload::C => Operation::Read,
+            Workload::D if r < 0.95 => Operation::Read,
+            Workload::D => Operation::Insert,
+            Workload::E if r < 0.95 => Operation::Scan,
+            Workload::E => Operation::Insert,
+            Workload::F if r < 0.5 => Operation::Read,
+            Workload::F => Operation::ReadModifyWrite,
+            Workload::Scan => Operation::Scan,
+            Workload::Counter => Operation::Increment,
+        }
+    }
+
+    /// The distribution YCSB uses for the workload.
+    pub fn default_distribution(self) -> Distribution {
+        match self {
+            Workload::D => Distribution::Latest,
+            Workload::E | Workload::Scan => Distribution::Uniform,
+            _ => Distribution::Zipfian,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Operation {
+    Read,
+    Update,
+    Insert,
+    Scan,
+    ReadModifyWrite,
+    Increment,
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Read => "read",
+            Operation::Update => "update",
+            Operation::Insert => "insert",
+            Operation::Scan => "scan",
+            Operation::ReadModifyWrite => "read-modify-write",
+            Operation::Increment => "increment",
+        })
+    }
+}
+
+/// What the workloads run against: a `Database`, or a `Tenant` of it.
+pub trait Target {
+    fn run<T, F>(&self, f: F) -> impl Future<Output=Result<T, Error>>
+    where
+        F: AsyncFnMut(&mut Transaction) -> Result<T, Error>;
+
+    fn read<T, F>(&self, f: F) -> impl Future<Output=Result<T, Error>>
+    where
+        F: AsyncFnMut(&ReadOnlyTransaction) -> Result<T, Error>;
+
+    fn bulk_load<S, K, V, P>(&self, kvs: S, options: BulkLoad, on_progress: P) -> impl Future<Output=Result<LoadProgress, Error>>
+    where
+        S: Stream<Item=(K, V)>,
+        K: Into<Key>,
+        V: Into<Value>,
+        P: FnMut(&LoadProgress);
+}
+
+impl Target for Database {
+    fn run<T, F>(&self, f: F) -> impl Future<Output=Result<T, Error>>
+    where
+        F: AsyncFnMut(&mut Transaction) -> Result<T, Error>,
+    {
+        Database::run(self, f)
+    }
+
+    fn read<T, F>(&self, f: F) -> impl Future<Output=Result<T, Error>>
+    where
+        F: AsyncFnMut(&ReadOnlyTransaction) -> Result<T, Error>,
+    {
+        Database::read(self, f)
+    }
+
+    fn bulk_load<S, K, V, P>(&self, kvs: S, options: BulkLoad, on_progress: P) -> impl Future<Output=Result<LoadProgress, Error>>
+    where
+        S: Stream<Item=(K, V)>,
+        K: Into<Key>,
+        V: Into<Value>,
+        P: FnMut(&LoadProgress),
+    {
+        Database::bulk_load(self, kvs, options, on_progress)
+    }
+}
+
+impl Target for Tenant {
+    fn run<T, F>(&self, f: F) -> impl Future<Output=Result<T, Error>>
+    where
+        F: AsyncFnMut(&mut Transaction) -> Result<T, Error>,
+    {
+        Tenant::run(self, f)
+    }
+
+    fn read<T, F>(&self, f: F) -> impl Future<Output=Result<T, Error>>
+    where
+        F: AsyncFnMut(&ReadOnlyTransaction) -> Result<T, Error>,
+    {
+        Tenant::read(self, f)
+    }
+
+    fn bulk_load<S, K, V, P>(&self, kvs: S, options: BulkLoad, on_progress: P) -> impl Future<Output=Result<LoadProgress, Error>>
+    where
+        S: Stream<Item=(K, V)>,
+        K: Into<Key>,
+        V: Into<Value>,
+        P: FnMut(&LoadProgress),
+    {
+        Tenant::bulk_load(self, kvs, options, on_progress)
+    }
+}
+
+/// What `load` writes and `run` runs.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub workload: Workload,
+    pub distribution: Distribution,
+    /// Records written by `load`, which the operations pick from.
+    pub records: u64,
+    pub operations: u64,
+    /// Transactions in flight at the same time.
+    pub concurrency: usize,
+    pub value_size: usize,
+    /// Most records read per scan.
+    pub scan_length: i32,
+    /// Keys incremented by the counter workload.
+    pub counters: u64,
+    /// Subspace of the records and counters.
+    pub subspace: Subspace,
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            workload: Workload::A,
+            distribution: Distribution::Zipfian,
+            records: 100_000,
+            operations: 100_000,
+            concurrency: 64,
+            value_size: 100,
+            scan_length: 100,
+            counters: 10,
+            subspace: Subspace::new("bench/"),
+            seed: 0,
+        }
+    }
+}
+
+impl Config {
+    fn record(&self, index: u64) -> Key {
+        self.subspace.subspace("r").key(index.to_be_bytes())
+    }
+
+    fn counter(&self, index: u64) -> Key {
+        self.subspace.subspace("c").key(index.to_be_bytes())
+    }
+
+    fn value(&self) -> Vec<u8> {
+        vec![b'v'; self.value_size]
+    }
+}
+
+/// Writes the records `run` reads, replacing earlier ones.
+pub async fn load<D: Target>(db: &D, config: &Config) -> Result<LoadProgress, Error> {
+    let value = config.value();
+    let records = stream::iter(0..config.records).map(|i| (config.record(i), value.clone()));
+    db.bulk_load(records, BulkLoad::default(), |_| {}).await
+}
+
+/// Clears the records and counters.
+pub async fn clean<D: Target>(db: &D, config: &Config) -> Result<(), Error> {
+    let range = config.subspace.range();
+    db.run(async |tx: &mut Transaction| {
+        tx.clear_range(range.clone()).await;
+        Ok(())
+    }).await
+}
+
+fn found<T>(result: Result<T, Error>) -> Result<(), Error> {
+    match result {
+        Ok(_) | Err(Error::KeyNotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+async fn execute<D: Target>(db: &D, config: &Config, operation: Operation, index: u64) -> Result<(), Error> {
+    match operation {
+        Operation::Read => {
+            let key = config.record(index);
+            db.read(async |tx: &ReadOnlyTransaction| found(tx.get(key.clone()).await)).await
+        }
+        Operation::Update | Operation::Insert => {
+            let (key, value) = (config.record(index), config.value());
+            db.run(async |tx: &mut Transaction| {
+                tx.set(key.clone(), value.clone()).await;
+                Ok(())
+            }).await
+        }
+        Operation::Scan => {
+            let (start, end) = (config.record(index), config.subspace.subspace("r").range().end);
+            db.read(async |tx: &ReadOnlyTransaction| {
+                tx.get_range(
+                    KeySelector::first_greater_or_equal(start.clone()),
+                    KeySelector::first_greater_or_equal(end.clone()),
+                    Some(config.scan_length),
+                    None,
+                    false,
+                ).try_for_each(|_| async { Ok(()) }).await
+            }).await
+        }
+        Operation::ReadModifyWrite => {
+            let (key, value) = (config.record(index), config.value());
+            db.run(async |tx: &mut Transaction| {
+                found(tx.get(key.clone()).await)?;
+                tx.set(key.clone(), value.clone()).await;
+                Ok(())
+            }).await
+        }
+        Operation::Increment => {
+            let key = config.counter(index);
+            db.run(async |tx: &mut Transaction| {
+                tx.atomic_add_i64(key.clone(), 1).await;
+                Ok(())
+            }).await
+        }
+    }
+}
+
+/// Runs `Config::operations` operations of the workload against records written by `load`.
+pub async fn run<D: Target>(db: &D, config: &Config) -> Result<Report, Error> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let records = KeyChooser::new(config.distribution, config.records);
+    let counters = KeyChooser::new(config.distribution, config.counters);
+    let inserted = Cell::new(config.records);
+
+    let mut stats = stats::Stats::default();
+    let mut in_flight = FuturesUnordered::new();
+    let mut next = 0;
+    let started = Instant::now();
+
+    while next < config.operations || !in_flight.is_empty() {
+        while next < config.operations && in_flight.len() < config.concurrency.max(1) {
+            let operation = config.workload.pick(rng.gen());
+            let index = match operation {
+                Operation::Insert => inserted.replace(inserted.get() + 1),
+                Operation::Increment => counters.next(&mut rng, config.counters),
+                _ => records.next(&mut rng, inserted.get()),
+            };
+            in_flight.push(async move {
+                let start = Instant::now();
+                execute(db, config, operation, index).await.map(|()| (operation, start.elapsed()))
+            });
+            next += 1;
+        }
+        if let Some(done) = in_flight.next().await {
+            let (operation, latency): (Operation, Duration) = done?;
+            stats.record(operation, latency);
+        }
+    }
+    Ok(stats.report(started.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mix(workload: Workload) -> Vec<(Operation, usize)> {
+        let mut counts = std::collections::BTreeMap::new();
+        for i in 0..100 {
+            *counts.entry(workload.pick(i as f64 / 100.0)).or_insert(0) += 1;
+        }
+        counts.into_iter().collect()
+    }
+
+    #[test]
+    fn workload_mixes() {
+        assert_eq!(mix(Workload::A), vec![(Operation::Read, 50), (Operation::Update, 50)]);
+        assert_eq!(mix(Workload::B), vec![(Operation::Read, 95), (Operation::Update, 5)]);
+        assert_eq!(mix(Workload::C), vec![(Operation::Read, 100)]);
+        assert_eq!(mix(Workload::D), vec![(Operation::Read, 95), (Operation::Insert, 5)]);
+        assert_eq!(mix(Workload::E), vec![(Operation::Insert, 5), (Operation::Scan, 95)]);
+        assert_eq!(mix(Workload::F), vec![(Operation::Read, 50), (Operation::ReadModifyWrite, 50)]);
+        assert_eq!(mix(Workload::Counter), vec![(Operation::Increment, 100)]);
+    }
+
+    #[test]
+    fn keys_stay_in_their_subspaces() {
+        let config = Config::default();
+        assert!(config.subspace.subspace("r").range().contains(config.record(u64::MAX)));
+        assert!(!config.subspace.subspace("r").range().contains(config.counter(0)));
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+
+use fdb::{Client, Subspace};
+use fdb_bench::{Config, Distribution, Workload};
+
+/// Runs a benchmark workload against a FoundationDB cluster.
+#[derive(Debug, Parser)]
+#[command(name = "fdb-bench")]
+struct Args {
+    #[arg(long, value_enum, default_value = "a")]
+    workload: Workload,
+    /// Defaults to the distribution YCSB uses for the workload.
+    #[arg(long, value_enum)]
+    distribution: Option<Distribution>,
+    #[arg(long, default_value_t = 100_000)]
+    records: u64,
+    #[arg(long, default_value_t = 100_000)]
+    operations: u64,
+    /// Transactions in flight at the same time.
+    #[arg(long, default_value_t = 64)]
+    concurrency: usize,
+    #[arg(long, default_value_t = 100)]
+    value_size: usize,
+    /// Most records read per scan.
+    #[arg(long, default_value_t = 100)]
+    scan_length: i32,
+    /// Keys incremented by the counter workload.
+    #[arg(long, default_value_t = 10)]
+    counters: u64,
+    /// Prefix of the keys written.
+    #[arg(long, default_value = "bench/")]
+    prefix: String,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// API version to select, to compare the bindings across versions.
+    #[arg(long)]
+    api_version: Option<i32>,
+    #[arg(long)]
+    cluster_file: Option<PathBuf>,
+    /// Runs against the records of an earlier run instead of loading them.
+    #[arg(long)]
+    skip_load: bool,
+    /// Keeps the records after the run.
+    #[arg(long)]
+    keep: bool,
+}
+
+async fn bench(args: Args) -> Result<(), fdb::Error> {
+    let builder = Client::builder();
+    let client = match args.api_version {
+        Some(version) => builder.api_version(version).build()?,
+        None => builder.build()?,
+    };
+    let db = match &args.cluster_file {
+        Some(path) => client.database_from(path)?,
+        None => client.database()?,
+    };
+
+    let config = Config {
+        workload: args.workload,
+        distribution: args.distribution.unwrap_or(args.workload.default_distribution()),
+        records: args.records,
+        operations: args.operations,
+        concurrency: args.concurrency,
+        value_size: args.value_size,
+        scan_length: args.scan_length,
+        counters: args.counters,
+        subspace: Subspace::new(args.prefix),
+        seed: args.seed,
+    };
+
+    if !args.skip_load {
+        let loaded = fdb_bench::load(&db, &config).await?;
+        eprintln!("Loaded {} records in {} transactions", loaded.keys, loaded.transactions);
+    }
+    let report = fdb_bench::run(&db, &config).await;
+    if !args.keep {
+        fdb_bench::clean(&db, &config).await?;
+    }
+
+    println!("workload {:?}, {:?} distribution", config.workload, config.distribution);
+    print!("{}", report?);
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    match bench(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("fdb-bench: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
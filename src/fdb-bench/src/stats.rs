@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+use crate::Operation;
+
+/// Latency percentiles of one kind of operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub count: u64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Summary {
+    fn new(mut latencies: Vec<Duration>) -> Self {
+        latencies.sort();
+        let percentile = |p: f64| match latencies.len() {
+            0 => Duration::ZERO,
+            n => latencies[((n - 1) as f64 * p).round() as usize],
+        };
+        Summary {
+            count: latencies.len() as u64,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: percentile(1.0),
+        }
+    }
+}
+
+/// Latencies recorded while running a workload.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    latencies: BTreeMap<Operation, Vec<Duration>>,
+}
+
+impl Stats {
+    pub(crate) fn record(&mut self, operation: Operation, latency: Duration) {
+        self.latencies.entry(operation).or_default().push(latency);
+    }
+
+    pub(crate) fn report(self, elapsed: Duration) -> Report {
+        let operations = self.latencies.into_iter().map(|(op, latencies)| (op, Summary::new(latencies))).collect();
+        Report { elapsed, operations }
+    }
+}
+
+/// Outcome of `run`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub elapsed: Duration,
+    pub operations: BTreeMap<Operation, Summary>,
+}
+
+impl Report {
+    pub fn count(&self) -> u64 {
+        self.operations.values().map(|s| s.count).sum()
+    }
+
+    /// Operations per second.
+    pub fn throughput(&self) -> f64 {
+        self.count() as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} operations in {:.2?}, {:.0}/s", self.count(), self.elapsed, self.throughput())?;
+        writeln!(f, "{:<18} {:>10} {:>10} {:>10} {:>10} {:>10}", "operation", "count", "p50", "p95", "p99", "max")?;
+        for (op, s) in &self.operations {
+            writeln!(f, "{:<18} {:>10} {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?}", op.to_string(), s.count, s.p50, s.p95, s.p99, s.max)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summaries() {
+        let mut stats = Stats::default();
+        for ms in (1..=100).rev() {
+            stats.record(Operation::Read, Duration::from_millis(ms));
+        }
+        stats.record(Operation::Update, Duration::from_millis(5));
+
+        let report = stats.report(Duration::from_secs(2));
+        assert_eq!(report.count(), 101);
+        assert_eq!(report.throughput(), 50.5);
+
+        let reads = report.operations[&Operation::Read];
+        assert_eq!((reads.p50, reads.p95, reads.p99, reads.max), (
+            Duration::from_millis(51),
+            Duration::from_millis(95),
+            Duration::from_millis(99),
+            Duration::from_millis(100),
+        ));
+        assert_eq!(report.operations[&Operation::Update].p99, Duration::from_millis(5));
+    }
+}
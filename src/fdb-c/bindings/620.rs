@@ -0,0 +1,283 @@
+/* Generated from include/620/fdb_c.h with FDB_API_VERSION 620, regenerate with the `bindgen` feature */
+
+pub const FDB_API_VERSION: u32 = 620;
+pub const FDBNetworkOption_FDB_NET_OPTION_LOCAL_ADDRESS: FDBNetworkOption = 10;
+pub const FDBNetworkOption_FDB_NET_OPTION_CLUSTER_FILE: FDBNetworkOption = 20;
+pub const FDBNetworkOption_FDB_NET_OPTION_TRACE_ENABLE: FDBNetworkOption = 30;
+pub const FDBNetworkOption_FDB_NET_OPTION_TRACE_ROLL_SIZE: FDBNetworkOption = 31;
+pub const FDBNetworkOption_FDB_NET_OPTION_TRACE_MAX_LOGS_SIZE: FDBNetworkOption = 32;
+pub const FDBNetworkOption_FDB_NET_OPTION_TRACE_LOG_GROUP: FDBNetworkOption = 33;
+pub const FDBNetworkOption_FDB_NET_OPTION_TRACE_FORMAT: FDBNetworkOption = 34;
+pub const FDBNetworkOption_FDB_NET_OPTION_KNOB: FDBNetworkOption = 40;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_PLUGIN: FDBNetworkOption = 41;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_CERT_BYTES: FDBNetworkOption = 42;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_CERT_PATH: FDBNetworkOption = 43;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_KEY_BYTES: FDBNetworkOption = 45;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_KEY_PATH: FDBNetworkOption = 46;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_VERIFY_PEERS: FDBNetworkOption = 47;
+pub const FDBNetworkOption_FDB_NET_OPTION_BUGGIFY_ENABLE: FDBNetworkOption = 48;
+pub const FDBNetworkOption_FDB_NET_OPTION_BUGGIFY_DISABLE: FDBNetworkOption = 49;
+pub const FDBNetworkOption_FDB_NET_OPTION_BUGGIFY_SECTION_ACTIVATED_PROBABILITY: FDBNetworkOption = 50;
+pub const FDBNetworkOption_FDB_NET_OPTION_BUGGIFY_SECTION_FIRED_PROBABILITY: FDBNetworkOption = 51;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_CA_BYTES: FDBNetworkOption = 52;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_CA_PATH: FDBNetworkOption = 53;
+pub const FDBNetworkOption_FDB_NET_OPTION_TLS_PASSWORD: FDBNetworkOption = 54;
+pub const FDBNetworkOption_FDB_NET_OPTION_DISABLE_MULTI_VERSION_CLIENT_API: FDBNetworkOption = 60;
+pub const FDBNetworkOption_FDB_NET_OPTION_CALLBACKS_ON_EXTERNAL_THREADS: FDBNetworkOption = 61;
+pub const FDBNetworkOption_FDB_NET_OPTION_EXTERNAL_CLIENT_LIBRARY: FDBNetworkOption = 62;
+pub const FDBNetworkOption_FDB_NET_OPTION_EXTERNAL_CLIENT_DIRECTORY: FDBNetworkOption = 63;
+pub const FDBNetworkOption_FDB_NET_OPTION_DISABLE_LOCAL_CLIENT: FDBNetworkOption = 64;
+pub const FDBNetworkOption_FDB_NET_OPTION_DISABLE_CLIENT_STATISTICS_LOGGING: FDBNetworkOption = 70;
+pub const FDBNetworkOption_FDB_NET_OPTION_ENABLE_SLOW_TASK_PROFILING: FDBNetworkOption = 71;
+pub const FDBNetworkOption_FDB_NET_OPTION_CLIENT_BUGGIFY_ENABLE: FDBNetworkOption = 80;
+pub const FDBNetworkOption_FDB_NET_OPTION_CLIENT_BUGGIFY_DISABLE: FDBNetworkOption = 81;
+pub const FDBNetworkOption_FDB_NET_OPTION_CLIENT_BUGGIFY_SECTION_ACTIVATED_PROBABILITY: FDBNetworkOption = 82;
+pub const FDBNetworkOption_FDB_NET_OPTION_CLIENT_BUGGIFY_SECTION_FIRED_PROBABILITY: FDBNetworkOption = 83;
+pub type FDBNetworkOption = ::std::os::raw::c_uint;
+pub const FDBDatabaseOption_FDB_DB_OPTION_LOCATION_CACHE_SIZE: FDBDatabaseOption = 10;
+pub const FDBDatabaseOption_FDB_DB_OPTION_MAX_WATCHES: FDBDatabaseOption = 20;
+pub const FDBDatabaseOption_FDB_DB_OPTION_MACHINE_ID: FDBDatabaseOption = 21;
+pub const FDBDatabaseOption_FDB_DB_OPTION_DATACENTER_ID: FDBDatabaseOption = 22;
+pub const FDBDatabaseOption_FDB_DB_OPTION_SNAPSHOT_RYW_ENABLE: FDBDatabaseOption = 26;
+pub const FDBDatabaseOption_FDB_DB_OPTION_SNAPSHOT_RYW_DISABLE: FDBDatabaseOption = 27;
+pub const FDBDatabaseOption_FDB_DB_OPTION_TRANSACTION_LOGGING_MAX_FIELD_LENGTH: FDBDatabaseOption = 405;
+pub const FDBDatabaseOption_FDB_DB_OPTION_TRANSACTION_TIMEOUT: FDBDatabaseOption = 500;
+pub const FDBDatabaseOption_FDB_DB_OPTION_TRANSACTION_RETRY_LIMIT: FDBDatabaseOption = 501;
+pub const FDBDatabaseOption_FDB_DB_OPTION_TRANSACTION_MAX_RETRY_DELAY: FDBDatabaseOption = 502;
+pub const FDBDatabaseOption_FDB_DB_OPTION_TRANSACTION_SIZE_LIMIT: FDBDatabaseOption = 503;
+pub const FDBDatabaseOption_FDB_DB_OPTION_TRANSACTION_CAUSAL_READ_RISKY: FDBDatabaseOption = 504;
+pub const FDBDatabaseOption_FDB_DB_OPTION_TRANSACTION_INCLUDE_PORT_IN_ADDRESS: FDBDatabaseOption = 505;
+pub type FDBDatabaseOption = ::std::os::raw::c_uint;
+pub const FDBTransactionOption_FDB_TR_OPTION_CAUSAL_WRITE_RISKY: FDBTransactionOption = 10;
+pub const FDBTransactionOption_FDB_TR_OPTION_CAUSAL_READ_RISKY: FDBTransactionOption = 20;
+pub const FDBTransactionOption_FDB_TR_OPTION_CAUSAL_READ_DISABLE: FDBTransactionOption = 21;
+pub const FDBTransactionOption_FDB_TR_OPTION_INCLUDE_PORT_IN_ADDRESS: FDBTransactionOption = 23;
+pub const FDBTransactionOption_FDB_TR_OPTION_NEXT_WRITE_NO_WRITE_CONFLICT_RANGE: FDBTransactionOption = 30;
+pub const FDBTransactionOption_FDB_TR_OPTION_READ_YOUR_WRITES_DISABLE: FDBTransactionOption = 51;
+pub const FDBTransactionOption_FDB_TR_OPTION_READ_AHEAD_DISABLE: FDBTransactionOption = 52;
+pub const FDBTransactionOption_FDB_TR_OPTION_DURABILITY_DATACENTER: FDBTransactionOption = 110;
+pub const FDBTransactionOption_FDB_TR_OPTION_DURABILITY_RISKY: FDBTransactionOption = 120;
+pub const FDBTransactionOption_FDB_TR_OPTION_DURABILITY_DEV_NULL_IS_WEB_SCALE: FDBTransactionOption = 130;
+pub const FDBTransactionOption_FDB_TR_OPTION_PRIORITY_SYSTEM_IMMEDIATE: FDBTransactionOption = 200;
+pub const FDBTransactionOption_FDB_TR_OPTION_PRIORITY_BATCH: FDBTransactionOption = 201;
+pub const FDBTransactionOption_FDB_TR_OPTION_INITIALIZE_NEW_DATABASE: FDBTransactionOption = 300;
+pub const FDBTransactionOption_FDB_TR_OPTION_ACCESS_SYSTEM_KEYS: FDBTransactionOption = 301;
+pub const FDBTransactionOption_FDB_TR_OPTION_READ_SYSTEM_KEYS: FDBTransactionOption = 302;
+pub const FDBTransactionOption_FDB_TR_OPTION_DEBUG_RETRY_LOGGING: FDBTransactionOption = 401;
+pub const FDBTransactionOption_FDB_TR_OPTION_TRANSACTION_LOGGING_ENABLE: FDBTransactionOption = 402;
+pub const FDBTransactionOption_FDB_TR_OPTION_DEBUG_TRANSACTION_IDENTIFIER: FDBTransactionOption = 403;
+pub const FDBTransactionOption_FDB_TR_OPTION_LOG_TRANSACTION: FDBTransactionOption = 404;
+pub const FDBTransactionOption_FDB_TR_OPTION_TRANSACTION_LOGGING_MAX_FIELD_LENGTH: FDBTransactionOption = 405;
+pub const FDBTransactionOption_FDB_TR_OPTION_TIMEOUT: FDBTransactionOption = 500;
+pub const FDBTransactionOption_FDB_TR_OPTION_RETRY_LIMIT: FDBTransactionOption = 501;
+pub const FDBTransactionOption_FDB_TR_OPTION_MAX_RETRY_DELAY: FDBTransactionOption = 502;
+pub const FDBTransactionOption_FDB_TR_OPTION_SIZE_LIMIT: FDBTransactionOption = 503;
+pub const FDBTransactionOption_FDB_TR_OPTION_SNAPSHOT_RYW_ENABLE: FDBTransactionOption = 600;
+pub const FDBTransactionOption_FDB_TR_OPTION_SNAPSHOT_RYW_DISABLE: FDBTransactionOption = 601;
+pub const FDBTransactionOption_FDB_TR_OPTION_LOCK_AWARE: FDBTransactionOption = 700;
+pub const FDBTransactionOption_FDB_TR_OPTION_USED_DURING_COMMIT_PROTECTION_DISABLE: FDBTransactionOption = 701;
+pub const FDBTransactionOption_FDB_TR_OPTION_READ_LOCK_AWARE: FDBTransactionOption = 702;
+pub const FDBTransactionOption_FDB_TR_OPTION_USE_PROVISIONAL_PROXIES: FDBTransactionOption = 711;
+pub type FDBTransactionOption = ::std::os::raw::c_uint;
+pub const FDBStreamingMode_FDB_STREAMING_MODE_WANT_ALL: FDBStreamingMode = -2;
+pub const FDBStreamingMode_FDB_STREAMING_MODE_ITERATOR: FDBStreamingMode = -1;
+pub const FDBStreamingMode_FDB_STREAMING_MODE_EXACT: FDBStreamingMode = 0;
+pub const FDBStreamingMode_FDB_STREAMING_MODE_SMALL: FDBStreamingMode = 1;
+pub const FDBStreamingMode_FDB_STREAMING_MODE_MEDIUM: FDBStreamingMode = 2;
+pub const FDBStreamingMode_FDB_STREAMING_MODE_LARGE: FDBStreamingMode = 3;
+pub const FDBStreamingMode_FDB_STREAMING_MODE_SERIAL: FDBStreamingMode = 4;
+pub type FDBStreamingMode = ::std::os::raw::c_int;
+pub const FDBMutationType_FDB_MUTATION_TYPE_ADD: FDBMutationType = 2;
+pub const FDBMutationType_FDB_MUTATION_TYPE_AND: FDBMutationType = 6;
+pub const FDBMutationType_FDB_MUTATION_TYPE_BIT_AND: FDBMutationType = 6;
+pub const FDBMutationType_FDB_MUTATION_TYPE_OR: FDBMutationType = 7;
+pub const FDBMutationType_FDB_MUTATION_TYPE_BIT_OR: FDBMutationType = 7;
+pub const FDBMutationType_FDB_MUTATION_TYPE_XOR: FDBMutationType = 8;
+pub const FDBMutationType_FDB_MUTATION_TYPE_BIT_XOR: FDBMutationType = 8;
+pub const FDBMutationType_FDB_MUTATION_TYPE_APPEND_IF_FITS: FDBMutationType = 9;
+pub const FDBMutationType_FDB_MUTATION_TYPE_MAX: FDBMutationType = 12;
+pub const FDBMutationType_FDB_MUTATION_TYPE_MIN: FDBMutationType = 13;
+pub const FDBMutationType_FDB_MUTATION_TYPE_SET_VERSIONSTAMPED_KEY: FDBMutationType = 14;
+pub const FDBMutationType_FDB_MUTATION_TYPE_SET_VERSIONSTAMPED_VALUE: FDBMutationType = 15;
+pub const FDBMutationType_FDB_MUTATION_TYPE_BYTE_MIN: FDBMutationType = 16;
+pub const FDBMutationType_FDB_MUTATION_TYPE_BYTE_MAX: FDBMutationType = 17;
+pub const FDBMutationType_FDB_MUTATION_TYPE_COMPARE_AND_CLEAR: FDBMutationType = 20;
+pub type FDBMutationType = ::std::os::raw::c_uint;
+pub const FDBConflictRangeType_FDB_CONFLICT_RANGE_TYPE_READ: FDBConflictRangeType = 0;
+pub const FDBConflictRangeType_FDB_CONFLICT_RANGE_TYPE_WRITE: FDBConflictRangeType = 1;
+pub type FDBConflictRangeType = ::std::os::raw::c_uint;
+pub const FDBErrorPredicate_FDB_ERROR_PREDICATE_RETRYABLE: FDBErrorPredicate = 50000;
+pub const FDBErrorPredicate_FDB_ERROR_PREDICATE_MAYBE_COMMITTED: FDBErrorPredicate = 50001;
+pub const FDBErrorPredicate_FDB_ERROR_PREDICATE_RETRYABLE_NOT_COMMITTED: FDBErrorPredicate = 50002;
+pub type FDBErrorPredicate = ::std::os::raw::c_uint;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FDB_future {
+    _unused: [u8; 0],
+}
+pub type FDBFuture = FDB_future;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FDB_database {
+    _unused: [u8; 0],
+}
+pub type FDBDatabase = FDB_database;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FDB_transaction {
+    _unused: [u8; 0],
+}
+pub type FDBTransaction = FDB_transaction;
+pub type fdb_error_t = ::std::os::raw::c_int;
+pub type fdb_bool_t = ::std::os::raw::c_int;
+extern "C" {
+    pub fn fdb_get_error(code: fdb_error_t) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn fdb_error_predicate(predicate_test: ::std::os::raw::c_int, code: fdb_error_t) -> fdb_bool_t;
+}
+extern "C" {
+    pub fn fdb_network_set_option(option: FDBNetworkOption, value: *const u8, value_length: ::std::os::raw::c_int) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_setup_network() -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_run_network() -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_stop_network() -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_add_network_thread_completion_hook(hook: ::std::option::Option<unsafe extern "C" fn(arg1: *mut ::std::os::raw::c_void)>, hook_parameter: *mut ::std::os::raw::c_void) -> fdb_error_t;
+}
+#[repr(C, packed(4))]
+#[derive(Debug, Copy, Clone)]
+pub struct keyvalue {
+    pub key: *const ::std::os::raw::c_void,
+    pub key_length: ::std::os::raw::c_int,
+    pub value: *const ::std::os::raw::c_void,
+    pub value_length: ::std::os::raw::c_int,
+}
+pub type FDBKeyValue = keyvalue;
+extern "C" {
+    pub fn fdb_future_cancel(f: *mut FDBFuture);
+}
+extern "C" {
+    pub fn fdb_future_release_memory(f: *mut FDBFuture);
+}
+extern "C" {
+    pub fn fdb_future_destroy(f: *mut FDBFuture);
+}
+extern "C" {
+    pub fn fdb_future_block_until_ready(f: *mut FDBFuture) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_future_is_ready(f: *mut FDBFuture) -> fdb_bool_t;
+}
+pub type FDBCallback = ::std::option::Option<unsafe extern "C" fn(future: *mut FDBFuture, callback_parameter: *mut ::std::os::raw::c_void)>;
+extern "C" {
+    pub fn fdb_future_set_callback(f: *mut FDBFuture, callback: FDBCallback, callback_parameter: *mut ::std::os::raw::c_void) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_future_get_error(f: *mut FDBFuture) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_future_get_int64(f: *mut FDBFuture, out: *mut i64) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_future_get_key(f: *mut FDBFuture, out_key: *mut *const u8, out_key_length: *mut ::std::os::raw::c_int) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_future_get_value(f: *mut FDBFuture, out_present: *mut fdb_bool_t, out_value: *mut *const u8, out_value_length: *mut ::std::os::raw::c_int) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_future_get_keyvalue_array(f: *mut FDBFuture, out_kv: *mut *const FDBKeyValue, out_count: *mut ::std::os::raw::c_int, out_more: *mut fdb_bool_t) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_future_get_string_array(f: *mut FDBFuture, out_strings: *mut *mut *const ::std::os::raw::c_char, out_count: *mut ::std::os::raw::c_int) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_create_database(cluster_file_path: *const ::std::os::raw::c_char, out_database: *mut *mut FDBDatabase) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_database_destroy(d: *mut FDBDatabase);
+}
+extern "C" {
+    pub fn fdb_database_set_option(d: *mut FDBDatabase, option: FDBDatabaseOption, value: *const u8, value_length: ::std::os::raw::c_int) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_database_create_transaction(d: *mut FDBDatabase, out_transaction: *mut *mut FDBTransaction) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_transaction_destroy(tr: *mut FDBTransaction);
+}
+extern "C" {
+    pub fn fdb_transaction_cancel(tr: *mut FDBTransaction);
+}
+extern "C" {
+    pub fn fdb_transaction_set_option(tr: *mut FDBTransaction, option: FDBTransactionOption, value: *const u8, value_length: ::std::os::raw::c_int) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_transaction_set_read_version(tr: *mut FDBTransaction, version: i64);
+}
+extern "C" {
+    pub fn fdb_transaction_get_read_version(tr: *mut FDBTransaction) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_get(tr: *mut FDBTransaction, key_name: *const u8, key_name_length: ::std::os::raw::c_int, snapshot: fdb_bool_t) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_get_key(tr: *mut FDBTransaction, key_name: *const u8, key_name_length: ::std::os::raw::c_int, or_equal: fdb_bool_t, offset: ::std::os::raw::c_int, snapshot: fdb_bool_t) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_get_addresses_for_key(tr: *mut FDBTransaction, key_name: *const u8, key_name_length: ::std::os::raw::c_int) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_get_range(tr: *mut FDBTransaction, begin_key_name: *const u8, begin_key_name_length: ::std::os::raw::c_int, begin_or_equal: fdb_bool_t, begin_offset: ::std::os::raw::c_int, end_key_name: *const u8, end_key_name_length: ::std::os::raw::c_int, end_or_equal: fdb_bool_t, end_offset: ::std::os::raw::c_int, limit: ::std::os::raw::c_int, target_bytes: ::std::os::raw::c_int, mode: FDBStreamingMode, iteration: ::std::os::raw::c_int, snapshot: fdb_bool_t, reverse: fdb_bool_t) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_set(tr: *mut FDBTransaction, key_name: *const u8, key_name_length: ::std::os::raw::c_int, value: *const u8, value_length: ::std::os::raw::c_int);
+}
+extern "C" {
+    pub fn fdb_transaction_atomic_op(tr: *mut FDBTransaction, key_name: *const u8, key_name_length: ::std::os::raw::c_int, param: *const u8, param_length: ::std::os::raw::c_int, operation_type: FDBMutationType);
+}
+extern "C" {
+    pub fn fdb_transaction_clear(tr: *mut FDBTransaction, key_name: *const u8, key_name_length: ::std::os::raw::c_int);
+}
+extern "C" {
+    pub fn fdb_transaction_clear_range(tr: *mut FDBTransaction, begin_key_name: *const u8, begin_key_name_length: ::std::os::raw::c_int, end_key_name: *const u8, end_key_name_length: ::std::os::raw::c_int);
+}
+extern "C" {
+    pub fn fdb_transaction_watch(tr: *mut FDBTransaction, key_name: *const u8, key_name_length: ::std::os::raw::c_int) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_commit(tr: *mut FDBTransaction) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_get_committed_version(tr: *mut FDBTransaction, out_version: *mut i64) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_transaction_get_approximate_size(tr: *mut FDBTransaction) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_get_versionstamp(tr: *mut FDBTransaction) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_on_error(tr: *mut FDBTransaction, error: fdb_error_t) -> *mut FDBFuture;
+}
+extern "C" {
+    pub fn fdb_transaction_reset(tr: *mut FDBTransaction);
+}
+extern "C" {
+    pub fn fdb_transaction_add_conflict_range(tr: *mut FDBTransaction, begin_key_name: *const u8, begin_key_name_length: ::std::os::raw::c_int, end_key_name: *const u8, end_key_name_length: ::std::os::raw::c_int, type_: FDBConflictRangeType) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_select_api_version_impl(runtime_version: ::std::os::raw::c_int, header_version: ::std::os::raw::c_int) -> fdb_error_t;
+}
+extern "C" {
+    pub fn fdb_get_max_api_version() -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn fdb_get_client_version() -> *const ::std::os::raw::c_char;
+}
@@ -1,60 +1,206 @@
-use std::path::PathBuf;
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 
-cfg_if::cfg_if! {
-    if #[cfg(feature = "730")] {
-const FDB_VERSION : i32 = 730;
-    } else if #[cfg(feature = "710")] {
-const FDB_VERSION : i32 = 710;
-    } else if #[cfg(feature = "700")] {
-const FDB_VERSION : i32 = 700;
-    } else if #[cfg(feature = "630")] {
-const FDB_VERSION : i32 = 630;
-    } else if #[cfg(feature = "620")] {
-const FDB_VERSION : i32 = 620;
-    } else if #[cfg(feature = "610")] {
-const FDB_VERSION : i32 = 610;
-    } else if #[cfg(feature = "600")] {
-const FDB_VERSION : i32 = 600;
-    } else if #[cfg(feature = "520")] {
-const FDB_VERSION : i32 = 520;
-    } else if #[cfg(feature = "510")] {
-const FDB_VERSION : i32 = 510;
-    } else {
-        // No version selected, fails
-        const FDB_VERSION : i32 = 0;
-    }
-}
-
+/// Versions of the headers in `include/`, each selected by the feature of the same name.
+const VERSIONS: [i32; 9] = [510, 520, 600, 610, 620, 630, 700, 710, 730];
 
 fn main() {
+    let version = select_version();
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
 
-    if FDB_VERSION == 0 {
-        panic!("You must select a version feature for foundation_db")
-    }
+    #[cfg(not(feature = "dlopen"))]
+    link();
 
-    println!("cargo:rustc-link-search=native={}", "/nix/store/i9nfm95bakqnlcgxk1d0bvd3sz751539-foundationdb-7.1.32-lib/lib");
+    let bindings = bindings(&manifest_dir, version);
 
-    println!("cargo:rustc-link-lib=fdb_c");
+    #[cfg(feature = "dlopen")]
+    let bindings = dynamic(&bindings);
 
+    fs::write(out_path.join("bindings.rs"), bindings).expect("Could not write bindings!");
+}
 
-    let include_path = format!("-I./include/{}", FDB_VERSION);
+/// The single API version feature enabled, or the newest header when loading libfdb_c at runtime.
+/// Fails the build with an explanation otherwise.
+fn select_version() -> i32 {
+    let selected: Vec<i32> = VERSIONS.into_iter()
+        .filter(|v| env::var_os(format!("CARGO_FEATURE_{v}")).is_some())
+        .collect();
+
+    match selected[..] {
+        [version] => version,
+        // The loaded library decides what is actually available
+        [] if cfg!(feature = "dlopen") => 730,
+        [] => fail(
+            "no API version selected.\n\
+             Enable exactly one of the features 510, 520, 600, 610, 620, 630, 700, 710 or 730, matching \
+             the version of the libfdb_c you link against (e.g. `features = [\"710\"]` for FoundationDB 7.1), \
+             or `dlopen` to load libfdb_c at runtime and choose the API version there.",
+        ),
+        _ => fail(&format!(
+            "the API version features {selected:?} are enabled, but exactly one may be selected.\n\
+             Cargo merges the features every dependent enables, run `cargo tree -e features -i fdb-c` \
+             to find out where they come from. `fdb` selects 710 by default, depend on it with \
+             `default-features = false` to select another version. To support several FoundationDB versions with one build, \
+             use the `dlopen` feature instead.",
+        )),
+    }
+}
 
-    let mut wrapper = String::new();
-    wrapper.push_str(&format!("#define FDB_API_VERSION {}\n", FDB_VERSION));
-    wrapper.push_str("#include <fdb_c.h>\n");
+fn fail(message: &str) -> ! {
+    eprintln!("error: fdb-c: {message}");
+    process::exit(1);
+}
 
-    fs::write("./wrapper.h", &wrapper).expect("Could not write wrapper file");
+/// Tells cargo where to find libfdb_c: the directory in `FDB_LIB_DIR`, the `fdb_c` pkg-config
+/// package, or the directories the FoundationDB client packages install to, in this order.
+/// Otherwise the linker's default search path is left to find it.
+#[cfg(not(feature = "dlopen"))]
+fn link() {
+    println!("cargo:rerun-if-env-changed=FDB_LIB_DIR");
+
+    if let Some(dir) = env::var_os("FDB_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", Path::new(&dir).display());
+    } else if pkg_config::Config::new().probe("fdb_c").is_ok() {
+        // pkg-config emitted the search path and library already
+        return;
+    } else {
+        let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+        let (file, dirs): (&str, &[&str]) = match target_os.as_str() {
+            "windows" => ("fdb_c.lib", &["C:\\Program Files\\foundationdb\\lib\\foundationdb"]),
+            "macos" => ("libfdb_c.dylib", &["/usr/local/lib", "/opt/homebrew/lib"]),
+            _ => ("libfdb_c.so", &["/usr/lib", "/usr/lib64", "/usr/local/lib"]),
+        };
+
+        match dirs.iter().map(Path::new).find(|dir| dir.join(file).exists()) {
+            Some(dir) => println!("cargo:rustc-link-search=native={}", dir.display()),
+            None => println!("cargo:warning=Could not find {file}, set FDB_LIB_DIR to the directory containing it"),
+        }
+    }
 
+    println!("cargo:rustc-link-lib=fdb_c");
+}
 
+/// Runs bindgen on the header of `version`.
+///
+/// With `FDB_C_UPDATE_BINDINGS` set, the result also replaces the pre-generated bindings.
+#[cfg(feature = "bindgen")]
+fn bindings(manifest_dir: &Path, version: i32) -> String {
+    println!("cargo:rerun-if-env-changed=FDB_C_UPDATE_BINDINGS");
 
     let bindings = bindgen::Builder::default()
+        .clang_arg(format!("-I{}", manifest_dir.join("include").join(version.to_string()).display()))
+        .header_contents("wrapper.h", &format!("#define FDB_API_VERSION {version}\n#include <fdb_c.h>\n"))
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
+        .generate()
+        .expect("Unable to generate bindings")
+        .to_string();
+
+    if env::var_os("FDB_C_UPDATE_BINDINGS").is_some() {
+        let header = format!(
+            "/* Generated from include/{version}/fdb_c.h with FDB_API_VERSION {version}, regenerate with the `bindgen` feature */\n\n",
+        );
+        fs::write(manifest_dir.join("bindings").join(format!("{version}.rs")), header + &bindings)
+            .expect("Could not update the pre-generated bindings");
+    }
 
-        .clang_arg(include_path).header("wrapper.h").parse_callbacks(Box::new(bindgen::CargoCallbacks::new())).generate().expect("Unable to generate bindings");
+    bindings
+}
 
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+/// The pre-generated bindings of `version`.
+#[cfg(not(feature = "bindgen"))]
+fn bindings(manifest_dir: &Path, version: i32) -> String {
+    let path = manifest_dir.join("bindings").join(format!("{version}.rs"));
+    println!("cargo:rerun-if-changed={}", path.display());
 
-    bindings.write_to_file(out_path.join("bindings.rs")).expect("Could not write bindings!")
+    fs::read_to_string(&path).expect("Could not read the pre-generated bindings")
+}
 
-}
\ No newline at end of file
+/// Replaces the `extern "C"` declarations of the bindings by `FdbCApi`, holding the functions of
+/// the library loaded at runtime, and free functions with the same signatures calling through it.
+/// Users of this crate thus do not depend on how libfdb_c is loaded.
+/// Also generates `is_available`, telling whether the loaded library exports a function, and
+/// `symbol`, looking up any other.
+#[cfg(feature = "dlopen")]
+fn dynamic(bindings: &str) -> String {
+    use quote::{format_ident, quote};
+
+    let file = syn::parse_file(bindings).expect("Could not parse the bindings");
+    let mut items = Vec::new();
+    let mut functions = Vec::new();
+
+    for item in file.items {
+        match item {
+            syn::Item::ForeignMod(block) => functions.extend(block.items.into_iter().filter_map(|item| match item {
+                syn::ForeignItem::Fn(function) => Some(function.sig),
+                _ => None,
+            })),
+            item => items.push(item),
+        }
+    }
+
+    let names: Vec<_> = functions.iter().map(|sig| sig.ident.clone()).collect();
+    let strings: Vec<_> = names.iter().map(|name| name.to_string()).collect();
+    let symbols: Vec<_> = strings.iter()
+        .map(|name| syn::LitByteStr::new(format!("{name}\0").as_bytes(), proc_macro2::Span::call_site()))
+        .collect();
+    let missing: Vec<_> = strings.iter()
+        .map(|name| format!("{name} is not exported by the loaded libfdb_c"))
+        .collect();
+    let inputs: Vec<_> = functions.iter().map(|sig| &sig.inputs).collect();
+    let outputs: Vec<_> = functions.iter().map(|sig| &sig.output).collect();
+    let arguments: Vec<Vec<_>> = functions.iter()
+        .map(|sig| sig.inputs.iter()
+            .enumerate()
+            .map(|(i, arg)| match arg {
+                syn::FnArg::Typed(arg) => match &*arg.pat {
+                    syn::Pat::Ident(pat) => pat.ident.clone(),
+                    _ => format_ident!("arg{}", i + 1),
+                },
+                syn::FnArg::Receiver(_) => unreachable!("foreign functions have no receiver"),
+            })
+            .collect())
+        .collect();
+
+    quote! {
+        #(#items)*
+
+        pub struct FdbCApi {
+            __library: ::libloading::Library,
+            #(pub #names: Result<unsafe extern "C" fn(#inputs) #outputs, ::libloading::Error>,)*
+        }
+
+        impl FdbCApi {
+            /// Loads the library, keeping missing functions as errors.
+            pub unsafe fn new<P: AsRef<::std::ffi::OsStr>>(path: P) -> Result<Self, ::libloading::Error> {
+                let library = ::libloading::Library::new(path)?;
+                Ok(FdbCApi {
+                    #(#names: library.get(#symbols).map(|sym| *sym),)*
+                    __library: library,
+                })
+            }
+        }
+
+        #(
+            pub unsafe fn #names(#inputs) #outputs {
+                (library().#names.as_ref().expect(#missing))(#(#arguments),*)
+            }
+        )*
+
+        /// Whether the loaded library exports the function `symbol`.
+        pub fn is_available(symbol: &str) -> bool {
+            match symbol {
+                #(#strings => library().#names.is_ok(),)*
+                _ => false,
+            }
+        }
+
+        /// Looks up `symbol`, a nul terminated name, in the loaded library. For functions newer
+        /// than the header the bindings were generated from, which `FdbCApi` does not hold.
+        pub unsafe fn symbol<T: Copy>(symbol: &[u8]) -> Result<T, ::libloading::Error> {
+            library().__library.get::<T>(symbol).map(|sym| *sym)
+        }
+    }.to_string()
+}
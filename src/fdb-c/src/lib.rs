@@ -4,7 +4,35 @@
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
-#[cfg(test)]
+#[cfg(feature = "dlopen")]
+static LIBRARY: std::sync::OnceLock<FdbCApi> = std::sync::OnceLock::new();
+
+/// Loads libfdb_c from `path`, or by its platform specific name (`libfdb_c.so`, `libfdb_c.dylib`,
+/// `fdb_c.dll`) from the system's library search path. Must be called before any other function
+/// of this crate.
+///
+/// Only the first successful call loads a library, later calls do nothing.
+#[cfg(feature = "dlopen")]
+pub fn load(path: Option<&std::path::Path>) -> Result<(), libloading::Error> {
+    if LIBRARY.get().is_some() {
+        return Ok(());
+    }
+
+    let api = match path {
+        Some(path) => unsafe { FdbCApi::new(path)? },
+        None => unsafe { FdbCApi::new(libloading::library_filename("fdb_c"))? },
+    };
+    // Loses against a concurrent call, which loaded a library just as well
+    let _ = LIBRARY.set(api);
+    Ok(())
+}
+
+#[cfg(feature = "dlopen")]
+fn library() -> &'static FdbCApi {
+    LIBRARY.get().expect("libfdb_c has not been loaded, call fdb_c::load first")
+}
+
+#[cfg(all(test, not(feature = "dlopen")))]
 mod tests {
     use super::*;
     use std::mem;
@@ -1,2 +0,0 @@
-#define FDB_API_VERSION 710
-#include <fdb_c.h>
@@ -0,0 +1,59 @@
+use clap::Args;
+
+use fdb::Subspace;
+use fdb_bench::{Config, Workload};
+
+use crate::{Result, Target};
+
+/// Runs a workload of `fdb-bench`, see there for the full set of options.
+#[derive(Debug, Args)]
+pub struct Bench {
+    #[arg(long, value_enum, default_value = "a")]
+    workload: Workload,
+    /// Records written before the workload runs.
+    #[arg(long, default_value_t = 10_000)]
+    records: u64,
+    /// Operations run, each in a transaction of its own.
+    #[arg(long, default_value_t = 10_000)]
+    operations: u64,
+    /// Transactions running at the same time.
+    #[arg(long, default_value_t = 16)]
+    concurrency: usize,
+    /// Bytes per value.
+    #[arg(long, default_value_t = 100)]
+    value_size: usize,
+    /// Prefix of the keys, which are cleared afterwards.
+    #[arg(long, default_value = "fdb-bench/")]
+    prefix: String,
+}
+
+async fn bench<D: fdb_bench::Target>(db: &D, config: &Config) -> Result<()> {
+    let loaded = fdb_bench::load(db, config).await?;
+    eprintln!("Loaded {} records in {} transactions", loaded.keys, loaded.transactions);
+
+    // Cleans up after a failed run too
+    let report = fdb_bench::run(db, config).await;
+    fdb_bench::clean(db, config).await?;
+
+    println!("workload {:?}, {:?} distribution", config.workload, config.distribution);
+    print!("{}", report?);
+    Ok(())
+}
+
+pub async fn run(target: &Target, options: Bench) -> Result<()> {
+    let config = Config {
+        workload: options.workload,
+        distribution: options.workload.default_distribution(),
+        records: options.records,
+        operations: options.operations,
+        concurrency: options.concurrency,
+        value_size: options.value_size,
+        subspace: Subspace::new(options.prefix),
+        ..Config::default()
+    };
+
+    match target {
+        Target::Database(db) => bench(db, &config).await,
+        Target::Tenant(_, tenant) => bench(tenant, &config).await,
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use futures::TryStreamExt;
+
+use fdb::{printable, CreateTransaction, Database, KeyRange, KeySelector, ReadOnlyTransaction, Tenant, Transaction, TransactionOption, Value};
+use fdb::backup::{self, Restore};
+use fdb::layers::DirectoryLayer;
+
+use crate::{Result, Target};
+use crate::tuple;
+
+const STATUS: &[u8] = b"\xff\xff/status/json";
+
+fn found(result: std::result::Result<Value, fdb::Error>) -> std::result::Result<Option<Value>, fdb::Error> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(fdb::Error::KeyNotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn get(target: &Target, key: Vec<u8>) -> Result<()> {
+    let value = target.read(async |tx: &ReadOnlyTransaction| found(tx.get(key.clone()).await)).await?;
+    match value {
+        Some(value) => println!("{}", printable(&value)),
+        None => return Err(format!("{} is not set", printable(&key)).into()),
+    }
+    Ok(())
+}
+
+pub async fn set(target: &Target, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+    target.run(async |tx: &mut Transaction| {
+        tx.set(key.clone(), value.clone()).await;
+        Ok(())
+    }).await?;
+    Ok(())
+}
+
+pub async fn clear(target: &Target, key: Vec<u8>, end: Option<Vec<u8>>) -> Result<()> {
+    target.run(async |tx: &mut Transaction| {
+        match &end {
+            Some(end) => tx.clear_range(KeyRange::new(key.clone(), end.clone())).await,
+            None => tx.clear(key.clone()).await,
+        }
+        Ok(())
+    }).await?;
+    Ok(())
+}
+
+pub async fn range(target: &Target, begin: Vec<u8>, end: Vec<u8>, limit: i32, reverse: bool, tuples: bool) -> Result<()> {
+    let kvs: Vec<_> = target.read(async |tx: &ReadOnlyTransaction| {
+        tx.get_range(
+            KeySelector::first_greater_or_equal(begin.clone()),
+            KeySelector::first_greater_or_equal(end.clone()),
+            Some(limit),
+            None,
+            reverse,
+        ).try_collect().await
+    }).await?;
+
+    for (key, value) in &kvs {
+        let key = match tuples.then(|| tuple::decode(key)).flatten() {
+            Some(tuple) => tuple.to_string(),
+            None => printable(key),
+        };
+        println!("{key} => {}", printable(value));
+    }
+    if kvs.len() == limit as usize {
+        eprintln!("Stopped after --limit {limit} keys");
+    }
+    Ok(())
+}
+
+pub async fn tenants(db: &Database) -> Result<()> {
+    let prefix = Tenant::map_prefix();
+    let end = [prefix, b"\xff"].concat();
+    let tenants: Vec<_> = db.read(async |tx: &ReadOnlyTransaction| {
+        tx.get_range(
+            KeySelector::first_greater_or_equal(prefix),
+            KeySelector::first_greater_or_equal(end.clone()),
+            None,
+            None,
+            false,
+        ).try_collect().await
+    }).await?;
+
+    for (key, _) in tenants {
+        println!("{}", printable(&key[prefix.len()..]));
+    }
+    Ok(())
+}
+
+/// Sets or clears the special key of tenant `name`, returning whether it existed before.
+async fn update_tenant(db: &Database, name: &str, create: bool) -> Result<bool> {
+    let key = [Tenant::map_prefix(), name.as_bytes()].concat();
+    let existed = db.run(async |tx: &mut Transaction| {
+        tx.set_option(TransactionOption::SpecialKeySpaceEnableWrites)?;
+        let existed = found(tx.get(key.clone()).await)?.is_some();
+        match (create, existed) {
+            (true, false) => tx.set(key.clone(), Value::from("")).await,
+            (false, true) => tx.clear(key.clone()).await,
+            _ => {}
+        }
+        Ok(existed)
+    }).await?;
+    Ok(existed)
+}
+
+pub async fn create_tenant(db: &Database, name: &str) -> Result<()> {
+    match update_tenant(db, name, true).await? {
+        true => Err(format!("tenant {name:?} already exists").into()),
+        false => Ok(()),
+    }
+}
+
+pub async fn delete_tenant(db: &Database, name: &str) -> Result<()> {
+    match update_tenant(db, name, false).await? {
+        true => Ok(()),
+        false => Err(format!("tenant {name:?} does not exist").into()),
+    }
+}
+
+/// The names of a directory path like `/app/users`, empty for the root.
+fn directory_path(path: &str) -> Vec<&str> {
+    path.split('/').filter(|name| !name.is_empty()).collect()
+}
+
+pub async fn list_directory(target: &Target, path: &str) -> Result<()> {
+    let path = directory_path(path);
+    let names = target.run(async |tx: &mut Transaction| DirectoryLayer::default().list(tx, &path).await).await?;
+    for name in names {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+pub async fn create_directory(target: &Target, path: &str) -> Result<()> {
+    let path = directory_path(path);
+    let contents = target.run(async |tx: &mut Transaction| DirectoryLayer::default().create(tx, &path).await).await?;
+    println!("{}", printable(contents.prefix()));
+    Ok(())
+}
+
+pub async fn remove_directory(target: &Target, path: &str) -> Result<()> {
+    let names = directory_path(path);
+    match target.run(async |tx: &mut Transaction| DirectoryLayer::default().remove(tx, &names).await).await? {
+        true => Ok(()),
+        false => Err(format!("directory {path:?} does not exist").into()),
+    }
+}
+
+pub async fn status(db: &Database) -> Result<()> {
+    let status = db.read(async |tx: &ReadOnlyTransaction| tx.get(STATUS).await).await?;
+    println!("{}", String::from_utf8_lossy(&status));
+    Ok(())
+}
+
+pub async fn watch(target: &Target, key: Vec<u8>) -> Result<()> {
+    let mut last = None;
+    loop {
+        let (value, changed) = target.run(async |tx: &mut Transaction| {
+            let value = found(tx.get(key.clone()).await)?;
+            Ok((value, tx.watch(key.clone())))
+        }).await?;
+
+        // A watch may fire without a change of the value, e.g. when it was set to the same value
+        if last.as_ref() != Some(&value) {
+            match &value {
+                Some(value) => println!("{}", printable(value)),
+                None => println!("(not set)"),
+            }
+            last = Some(value);
+        }
+        changed.await?;
+    }
+}
+
+pub async fn export<C: CreateTransaction>(db: &C, path: &Path, begin: Vec<u8>, end: Vec<u8>) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let (file, report) = backup::dump(db, &[KeyRange::new(begin, end)], file).await?;
+    file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+    eprintln!("Exported {} keys, {} bytes, in {} transactions", report.keys, report.bytes, report.transactions);
+    if !report.is_consistent() {
+        eprintln!("The export took longer than the MVCC window and is not a consistent snapshot:");
+        for (key, version) in &report.read_versions {
+            eprintln!("  from {} at read version {version}", printable(key));
+        }
+    }
+    Ok(())
+}
+
+pub async fn import<C: CreateTransaction>(db: &C, path: &Path, remaps: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+    // Checking the whole file first keeps a corrupted one from being partially imported
+    let (header, keys) = backup::verify(BufReader::new(File::open(path)?))?;
+    match &header.tenant {
+        Some(tenant) => eprintln!("Importing {keys} keys backed up from tenant {tenant} at read version {}", header.read_version),
+        None => eprintln!("Importing {keys} keys backed up at read version {}", header.read_version),
+    }
+
+    let options = remaps.into_iter().fold(Restore::default(), |options, (from, to)| options.remap(from, to));
+    let progress = backup::restore(db, BufReader::new(File::open(path)?), options).await?;
+    eprintln!("Imported {} keys, {} bytes, in {} transactions", progress.keys, progress.bytes, progress.transactions);
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use clap::{Parser, Subcommand};
+
+use fdb::{Client, Database, Tenant};
+
+use crate::tuple::unprintable;
+
+mod bench;
+mod commands;
+mod tuple;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Reads and writes keys of a FoundationDB cluster.
+///
+/// Keys and values are written escaped like FoundationDB's `printable()`: `\xNN` is the byte `NN`
+/// and `\\` a backslash.
+#[derive(Debug, Parser)]
+#[command(name = "fdb")]
+struct Cli {
+    /// Cluster file of the cluster to connect to, instead of `FDB_CLUSTER_FILE` or the default one.
+    #[arg(long, global = true)]
+    cluster_file: Option<PathBuf>,
+    /// Runs the command in this tenant.
+    #[arg(long, global = true)]
+    tenant: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Prints the value of a key.
+    Get {
+        key: Bytes,
+    },
+    /// Sets the value of a key.
+    Set {
+        key: Bytes,
+        value: Bytes,
+    },
+    /// Clears a key, or the keys from `key` up to `end`.
+    Clear {
+        key: Bytes,
+        end: Option<Bytes>,
+    },
+    /// Prints the keys and values from `begin` up to `end`, or up to the system keys.
+    Range {
+        begin: Bytes,
+        end: Option<Bytes>,
+        /// Most keys printed.
+        #[arg(long, default_value_t = 100)]
+        limit: i32,
+        /// Prints the last keys of the range first.
+        #[arg(long)]
+        reverse: bool,
+        /// Prints keys encoded with the tuple layer as tuples.
+        #[arg(long)]
+        tuple: bool,
+    },
+    /// Lists, creates and removes directories of the directory layer the other bindings share.
+    Dir {
+        #[command(subcommand)]
+        command: DirCommand,
+    },
+    /// Lists, creates and deletes tenants.
+    Tenant {
+        #[command(subcommand)]
+        command: TenantCommand,
+    },
+    /// Prints the status of the cluster as JSON.
+    Status,
+    /// Prints the value of a key whenever it changes, until interrupted.
+    Watch {
+        key: Bytes,
+    },
+    /// Writes a backup of the keys from `begin` up to `end` to a file.
+    Export {
+        file: PathBuf,
+        #[arg(default_value = "")]
+        begin: Bytes,
+        #[arg(default_value = "\\xff")]
+        end: Bytes,
+    },
+    /// Loads a backup file written by `export`.
+    Import {
+        file: PathBuf,
+        /// Restores keys starting with FROM under TO instead, given as `FROM=TO`.
+        #[arg(long, value_parser = remap)]
+        remap: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    /// Measures the throughput and latency of a workload, like `fdb-bench`.
+    Bench(bench::Bench),
+}
+
+/// Directories are given as paths like `/app/users`.
+#[derive(Debug, Subcommand)]
+enum DirCommand {
+    /// Lists the subdirectories of a directory.
+    Ls {
+        #[arg(default_value = "/")]
+        path: String,
+    },
+    /// Creates a directory and its missing parents, printing the prefix of its contents.
+    Mkdir { path: String },
+    /// Removes a directory with its subdirectories and all their contents.
+    Rm { path: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum TenantCommand {
+    List,
+    Create { name: String },
+    Delete { name: String },
+}
+
+/// A key or value given on the command line, escaped like `printable()`.
+#[derive(Debug, Clone)]
+pub struct Bytes(pub Vec<u8>);
+
+impl FromStr for Bytes {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        unprintable(s).map(Bytes)
+    }
+}
+
+fn remap(s: &str) -> std::result::Result<(Vec<u8>, Vec<u8>), String> {
+    let (from, to) = s.split_once('=').ok_or_else(|| format!("expected FROM=TO, got {s:?}"))?;
+    Ok((unprintable(from)?, unprintable(to)?))
+}
+
+/// What the command runs against: the database, or a tenant of it.
+pub enum Target {
+    Database(Database),
+    Tenant(Database, Tenant),
+}
+
+impl Target {
+    fn database(&self) -> &Database {
+        match self {
+            Target::Database(db) | Target::Tenant(db, _) => db,
+        }
+    }
+
+    async fn run<T, F>(&self, f: F) -> std::result::Result<T, fdb::Error>
+    where
+        F: AsyncFnMut(&mut fdb::Transaction) -> std::result::Result<T, fdb::Error>,
+    {
+        match self {
+            Target::Database(db) => db.run(f).await,
+            Target::Tenant(_, tenant) => tenant.run(f).await,
+        }
+    }
+
+    async fn read<T, F>(&self, f: F) -> std::result::Result<T, fdb::Error>
+    where
+        F: AsyncFnMut(&fdb::ReadOnlyTransaction) -> std::result::Result<T, fdb::Error>,
+    {
+        match self {
+            Target::Database(db) => db.read(f).await,
+            Target::Tenant(_, tenant) => tenant.read(f).await,
+        }
+    }
+}
+
+async fn execute(cli: Cli, target: Target) -> Result<()> {
+    match cli.command {
+        Command::Get { key } => commands::get(&target, key.0).await,
+        Command::Set { key, value } => commands::set(&target, key.0, value.0).await,
+        Command::Clear { key, end } => commands::clear(&target, key.0, end.map(|end| end.0)).await,
+        Command::Range { begin, end, limit, reverse, tuple } => {
+            let end = end.map_or(b"\xff".to_vec(), |end| end.0);
+            commands::range(&target, begin.0, end, limit, reverse, tuple).await
+        }
+        Command::Dir { command } => match command {
+            DirCommand::Ls { path } => commands::list_directory(&target, &path).await,
+            DirCommand::Mkdir { path } => commands::create_directory(&target, &path).await,
+            DirCommand::Rm { path } => commands::remove_directory(&target, &path).await,
+        },
+        Command::Tenant { command } => match command {
+            TenantCommand::List => commands::tenants(target.database()).await,
+            TenantCommand::Create { name } => commands::create_tenant(target.database(), &name).await,
+            TenantCommand::Delete { name } => commands::delete_tenant(target.database(), &name).await,
+        },
+        Command::Status => commands::status(target.database()).await,
+        Command::Watch { key } => commands::watch(&target, key.0).await,
+        Command::Export { file, begin, end } => match &target {
+            Target::Database(db) => commands::export(db, &file, begin.0, end.0).await,
+            Target::Tenant(_, tenant) => commands::export(tenant, &file, begin.0, end.0).await,
+        },
+        Command::Import { file, remap } => match &target {
+            Target::Database(db) => commands::import(db, &file, remap).await,
+            Target::Tenant(_, tenant) => commands::import(tenant, &file, remap).await,
+        },
+        Command::Bench(options) => bench::run(&target, options).await,
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let client = match Client::new() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("fdb: could not start the client: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let target = match &cli.cluster_file {
+        Some(path) => client.database_from(path),
+        None => client.database(),
+    }.and_then(|db| match &cli.tenant {
+        Some(name) => db.tenant(name).map(|tenant| Target::Tenant(db, tenant)),
+        None => Ok(Target::Database(db)),
+    });
+
+    let result = match target {
+        Ok(target) => execute(cli, target).await,
+        Err(e) => Err(e.into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("fdb: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt::{self, Display, Formatter};
+
+use fdb::printable;
+
+/// An element of a key encoded with the tuple layer, as far as it can be displayed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Null,
+    Bytes(Vec<u8>),
+    String(String),
+    Nested(Vec<Element>),
+    Int(i128),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    Uuid([u8; 16]),
+    Versionstamp([u8; 12]),
+}
+
+/// A decoded tuple, displayed like `("users", 42, b"\x00")`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuple(pub Vec<Element>);
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn write_elements(f: &mut Formatter<'_>, elements: &[Element]) -> fmt::Result {
+    write!(f, "(")?;
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{element}")?;
+    }
+    if elements.len() == 1 {
+        write!(f, ",")?;
+    }
+    write!(f, ")")
+}
+
+impl Display for Element {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Element::Null => write!(f, "null"),
+            Element::Bytes(bytes) => write!(f, "b\"{}\"", printable(bytes)),
+            Element::String(s) => write!(f, "{s:?}"),
+            Element::Nested(elements) => write_elements(f, elements),
+            Element::Int(i) => write!(f, "{i}"),
+            Element::Float(x) => write!(f, "{x:?}f"),
+            Element::Double(x) => write!(f, "{x:?}"),
+            Element::Bool(b) => write!(f, "{b}"),
+            Element::Uuid(u) => {
+                let h = hex(u);
+                write!(f, "{}-{}-{}-{}-{}", &h[..8], &h[8..12], &h[12..16], &h[16..20], &h[20..])
+            }
+            Element::Versionstamp(v) => write!(f, "Versionstamp({})", hex(v)),
+        }
+    }
+}
+
+impl Display for Tuple {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_elements(f, &self.0)
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes: &'a [u8] = self.bytes;
+        let taken = bytes.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(taken)
+    }
+
+    /// Bytes up to the next unescaped `0x00`, with `0x00 0xFF` standing for `0x00`.
+    fn escaped(&mut self) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        loop {
+            match self.take(1)?[0] {
+                0x00 if self.bytes.get(self.pos) == Some(&0xff) => {
+                    self.pos += 1;
+                    out.push(0x00);
+                }
+                0x00 => return Some(out),
+                b => out.push(b),
+            }
+        }
+    }
+
+    fn element(&mut self, nested: bool) -> Option<Element> {
+        let code = self.take(1)?[0];
+        Some(match code {
+            // Within a nested tuple null is escaped, as 0x00 alone ends the tuple
+            0x00 if nested => {
+                self.take(1).filter(|b| b == &[0xff])?;
+                Element::Null
+            }
+            0x00 => Element::Null,
+            0x01 => Element::Bytes(self.escaped()?),
+            0x02 => Element::String(String::from_utf8(self.escaped()?).ok()?),
+            0x05 => {
+                let mut elements = Vec::new();
+                while self.bytes.get(self.pos) != Some(&0x00) || self.bytes.get(self.pos + 1) == Some(&0xff) {
+                    elements.push(self.element(true)?);
+                }
+                self.pos += 1;
+                Element::Nested(elements)
+            }
+            0x0c..=0x1c => {
+                let n = (code as i32 - 0x14).unsigned_abs() as usize;
+                let magnitude = self.take(n)?.iter().fold(0i128, |acc, b| acc << 8 | *b as i128);
+                match code >= 0x14 {
+                    true => Element::Int(magnitude),
+                    false => Element::Int(magnitude - ((1i128 << (8 * n)) - 1)),
+                }
+            }
+            0x20 => {
+                let mut b: [u8; 4] = self.take(4)?.try_into().ok()?;
+                flip(&mut b);
+                Element::Float(f32::from_be_bytes(b))
+            }
+            0x21 => {
+                let mut b: [u8; 8] = self.take(8)?.try_into().ok()?;
+                flip(&mut b);
+                Element::Double(f64::from_be_bytes(b))
+            }
+            0x26 => Element::Bool(false),
+            0x27 => Element::Bool(true),
+            0x30 => Element::Uuid(self.take(16)?.try_into().ok()?),
+            0x33 => Element::Versionstamp(self.take(12)?.try_into().ok()?),
+            _ => return None,
+        })
+    }
+}
+
+/// Reverses the encoding of floats: positive numbers have their sign bit flipped, negative numbers
+/// all of their bits.
+fn flip(bytes: &mut [u8]) {
+    if bytes[0] & 0x80 != 0 {
+        bytes[0] ^= 0x80;
+    } else {
+        bytes.iter_mut().for_each(|b| *b = !*b);
+    }
+}
+
+/// Decodes `bytes` as a tuple, or `None` if they are not a valid encoding of one.
+pub fn decode(bytes: &[u8]) -> Option<Tuple> {
+    let mut decoder = Decoder { bytes, pos: 0 };
+    let mut elements = Vec::new();
+    while decoder.pos < bytes.len() {
+        elements.push(decoder.element(false)?);
+    }
+    Some(Tuple(elements))
+}
+
+/// Parses the escaping of `printable`: `\\` is a backslash and `\xNN` the byte `NN`.
+pub fn unprintable(s: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'\\') => out.push(b'\\'),
+            Some(b'x') => {
+                let digits = [bytes.next(), bytes.next()];
+                let hex = digits.iter().flatten().map(|d| *d as char).collect::<String>();
+                let byte = u8::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 2);
+                out.push(byte.ok_or_else(|| format!("invalid escape \\x{hex} in {s:?}"))?);
+            }
+            _ => return Err(format!("invalid escape in {s:?}, expected \\\\ or \\xNN")),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_tuples() {
+        let key = b"\x02users\x00\x15\x2a\x13\xfe\x01a\x00\xffb\x00\x05\x00\xff\x14\x00\x27";
+        let tuple = decode(key).unwrap();
+        assert_eq!(tuple.0, vec![
+            Element::String("users".into()),
+            Element::Int(42),
+            Element::Int(-1),
+            Element::Bytes(b"a\x00b".to_vec()),
+            Element::Nested(vec![Element::Null, Element::Int(0)]),
+            Element::Bool(true),
+        ]);
+        assert_eq!(tuple.to_string(), r#"("users", 42, -1, b"a\x00b", (null, 0), true)"#);
+
+        // 1.5 and -1.5 as doubles
+        assert_eq!(decode(b"\x21\xbf\xf8\x00\x00\x00\x00\x00\x00").unwrap().0, vec![Element::Double(1.5)]);
+        assert_eq!(decode(b"\x21\x40\x07\xff\xff\xff\xff\xff\xff").unwrap().0, vec![Element::Double(-1.5)]);
+        assert_eq!(decode(b"\x14").unwrap().to_string(), "(0,)");
+    }
+
+    #[test]
+    fn rejects_other_keys() {
+        assert_eq!(decode(b"plain"), None);
+        assert_eq!(decode(b"\x02unterminated"), None);
+        assert_eq!(decode(b"\x16\x01"), None);
+    }
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(unprintable(r"a\x00\\b").unwrap(), b"a\x00\\b");
+        assert_eq!(unprintable(&printable(b"\xff\x02k\\")).unwrap(), b"\xff\x02k\\");
+        assert!(unprintable(r"\x0").is_err());
+        assert!(unprintable(r"\n").is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Logical backups of key ranges into local files, and restoring them.
+//!
+//! A backup file starts with the magic bytes `FDBBKUP` and a format version, followed by a gzip
+//! stream of records: a header with the read version, the ranges backed up and the tenant they
+//! were read from, if any (since format version 2), one record per
+//! key-value pair, and an end record with the number of pairs. The CRC32 checksum of the gzip
+//! trailer and the end record detect corrupted and truncated files.
+//!
+//! Files are read and written synchronously; pass a `BufReader` or `BufWriter` for local files.
+
+use std::io::{self, Read, Write};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use futures::{pin_mut, stream, StreamExt};
+
+use crate::{BulkLoad, Error, LoadProgress};
+use crate::scan::key_after;
+use crate::transaction::CreateTransaction;
+use crate::types::{Key, KeyRange, KeySelector, Value};
+
+const MAGIC: &[u8; 7] = b"FDBBKUP";
+const FORMAT_VERSION: u16 = 2;
+
+const ENTRY: u8 = 1;
+const END: u8 = 0;
+
+fn io<T>(result: io::Result<T>) -> Result<T, Error> {
+    result.map_err(|e| Error::Backup(e.to_string()))
+}
+
+fn corrupt<T>(reason: &str) -> Result<T, Error> {
+    Err(Error::Backup(reason.to_string()))
+}
+
+/// What a backup file contains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupHeader {
+    /// Read version the backup started at.
+    pub read_version: i64,
+    pub ranges: Vec<KeyRange>,
+    /// Tenant the ranges were read from, `None` for the whole database.
+    pub tenant: Option<String>,
+}
+
+/// How a backup went.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupReport {
+    pub keys: u64,
+    /// Bytes of the keys and values, before compression.
+    pub bytes: u64,
+    /// Number of transactions the backup took.
+    pub transactions: usize,
+    /// The read versions the backup was taken at, each with the first key read at it. More than
+    /// one means the backup took longer than the MVCC window, and is not a consistent snapshot.
+    pub read_versions: Vec<(Key, i64)>,
+}
+
+impl BackupReport {
+    /// Whether all keys were read at the same read version.
+    pub fn is_consistent(&self) -> bool {
+        self.read_versions.len() <= 1
+    }
+}
+
+struct Encoder<W: Write> {
+    out: GzEncoder<W>,
+}
+
+impl<W: Write> Encoder<W> {
+    fn new(mut writer: W, header: &BackupHeader) -> Result<Self, Error> {
+        io(writer.write_all(MAGIC))?;
+        io(writer.write_all(&FORMAT_VERSION.to_be_bytes()))?;
+
+        let mut encoder = Encoder { out: GzEncoder::new(writer, Compression::default()) };
+        encoder.write(&header.read_version.to_be_bytes())?;
+        encoder.write(&(header.ranges.len() as u32).to_be_bytes())?;
+        for range in &header.ranges {
+            encoder.bytes(&range.start)?;
+            encoder.bytes(&range.end)?;
+        }
+        match &header.tenant {
+            Some(tenant) => {
+                encoder.write(&[1])?;
+                encoder.bytes(tenant.as_bytes())?;
+            }
+            None => encoder.write(&[0])?,
+        }
+        Ok(encoder)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        io(self.out.write_all(bytes))
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.write(&(bytes.len() as u32).to_be_bytes())?;
+        self.write(bytes)
+    }
+
+    fn entry(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.write(&[ENTRY])?;
+        self.bytes(key)?;
+        self.bytes(value)
+    }
+
+    fn finish(mut self, keys: u64) -> Result<W, Error> {
+        self.write(&[END])?;
+        self.write(&keys.to_be_bytes())?;
+        io(self.out.finish())
+    }
+}
+
+/// The key-value pairs of a backup file, read with `open`.
+pub struct Entries<R: Read> {
+    input: GzDecoder<R>,
+    keys: u64,
+    done: bool,
+}
+
+impl<R: Read> Entries<R> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut bytes = [0; N];
+        match self.input.read_exact(&mut bytes) {
+            Ok(()) => Ok(bytes),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => corrupt("the file is truncated"),
+            Err(e) => io(Err(e)),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let len = u32::from_be_bytes(self.array()?) as usize;
+        let mut bytes = vec![0; len];
+        match self.input.read_exact(&mut bytes) {
+            Ok(()) => Ok(bytes),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => corrupt("the file is truncated"),
+            Err(e) => io(Err(e)),
+        }
+    }
+
+    fn next_entry(&mut self) -> Result<Option<(Key, Value)>, Error> {
+        match self.array::<1>()? {
+            [ENTRY] => {
+                let key = self.bytes()?;
+                let value = self.bytes()?;
+                self.keys += 1;
+                Ok(Some((key.into(), value.into())))
+            }
+            [END] => {
+                if u64::from_be_bytes(self.array()?) != self.keys {
+                    return corrupt("the number of entries does not match the end record");
+                }
+                // Reading to the end verifies the checksum of the gzip trailer
+                let mut rest = Vec::new();
+                io(self.input.read_to_end(&mut rest))?;
+                if !rest.is_empty() {
+                    return corrupt("unexpected data after the end record");
+                }
+                Ok(None)
+            }
+            _ => corrupt("unknown record"),
+        }
+    }
+}
+
+impl<R: Read> Iterator for Entries<R> {
+    type Item = Result<(Key, Value), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_entry().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+/// Reads the header of a backup file, returning it with the key-value pairs that follow.
+pub fn open<R: Read>(mut reader: R) -> Result<(BackupHeader, Entries<R>), Error> {
+    let mut magic = [0; 9];
+    io(reader.read_exact(&mut magic))?;
+    if &magic[..7] != MAGIC {
+        return corrupt("not a backup file");
+    }
+    let version = u16::from_be_bytes([magic[7], magic[8]]);
+    if !(1..=FORMAT_VERSION).contains(&version) {
+        return Err(Error::Backup(format!("unsupported format version {version}")));
+    }
+
+    let mut entries = Entries { input: GzDecoder::new(reader), keys: 0, done: false };
+    let read_version = i64::from_be_bytes(entries.array()?);
+    let count = u32::from_be_bytes(entries.array()?);
+    let mut ranges = Vec::new();
+    for _ in 0..count {
+        ranges.push(KeyRange::new(entries.bytes()?, entries.bytes()?));
+    }
+    let tenant = match version {
+        1 => None,
+        _ => match entries.array()? {
+            [0] => None,
+            [1] => Some(String::from_utf8(entries.bytes()?).or_else(|_| corrupt("the tenant name is not UTF-8"))?),
+            _ => return corrupt("unknown tenant record"),
+        },
+    };
+    Ok((BackupHeader { read_version, ranges, tenant }, entries))
+}
+
+/// Reads a whole backup file, failing if it is corrupted or truncated.
+pub fn verify<R: Read>(reader: R) -> Result<(BackupHeader, u64), Error> {
+    let (header, mut entries) = open(reader)?;
+    for entry in entries.by_ref() {
+        entry?;
+    }
+    Ok((header, entries.keys))
+}
+
+/// Writes the keys of `ranges` to `writer`, from a tenant if `db` is a `Tenant`.
+///
+/// All ranges are read at the read version of the first transaction for as long as the cluster
+/// keeps it, about 5 seconds. Backups taking longer continue at the latest read version, and the
+/// report lists where the read version changed.
+pub async fn dump<C: CreateTransaction, W: Write>(db: &C, ranges: &[KeyRange], writer: W) -> Result<(W, BackupReport), Error> {
+    let mut tx = db.create_transaction()?;
+    let mut version = tx.get_read_version().await?;
+    let mut report = BackupReport {
+        transactions: 1,
+        read_versions: vec![(ranges.first().map(|r| r.start.clone()).unwrap_or_default(), version)],
+        ..BackupReport::default()
+    };
+
+    let header = BackupHeader { read_version: version, ranges: ranges.to_vec(), tenant: db.tenant_name().map(str::to_owned) };
+    let mut encoder = Encoder::new(writer, &header)?;
+
+    for range in ranges {
+        let mut begin = range.start.clone();
+        loop {
+            let mut failure = None;
+            {
+                let kvs = tx.snapshot().get_range(
+                    KeySelector::first_greater_or_equal(begin.clone()),
+                    KeySelector::first_greater_or_equal(range.end.clone()),
+                    None,
+                    None,
+                    false,
+                );
+                pin_mut!(kvs);
+
+                while let Some(kv) = kvs.next().await {
+                    match kv {
+                        Ok((key, value)) => {
+                            encoder.entry(&key, &value)?;
+                            report.keys += 1;
+                            report.bytes += (key.len() + value.len()) as u64;
+                            begin = key_after(&key);
+                        }
+                        Err(e) => {
+                            failure = Some(e);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            let Some(e) = failure else {
+                break;
+            };
+            let too_old = e == Error::TransactionTooOld;
+            tx.on_error(e).await?;
+            report.transactions += 1;
+            if too_old {
+                version = tx.get_read_version().await?;
+                report.read_versions.push((begin.clone(), version));
+            } else {
+                tx.set_read_version(version);
+            }
+        }
+    }
+
+    Ok((encoder.finish(report.keys)?, report))
+}
+
+/// How `restore` writes the pairs of a backup.
+#[derive(Debug, Clone, Default)]
+pub struct Restore {
+    remaps: Vec<(Key, Key)>,
+    load: BulkLoad,
+}
+
+impl Restore {
+    /// Restores keys starting with `from` with `to` instead. The first matching remap applies.
+    pub fn remap<F: Into<Key>, T: Into<Key>>(mut self, from: F, to: T) -> Self {
+        self.remaps.push((from.into(), to.into()));
+        self
+    }
+
+    /// Options of the bulk load writing the pairs.
+    pub fn bulk_load(mut self, load: BulkLoad) -> Self {
+        self.load = load;
+        self
+    }
+
+    fn apply(&self, key: Key) -> Key {
+        for (from, to) in &self.remaps {
+            if let Some(rest) = key.strip_prefix(from.as_slice()) {
+                return [to.as_slice(), rest].concat().into();
+            }
+        }
+        key
+    }
+}
+
+/// Writes the pairs of a backup file with `Database::bulk_load`, into a tenant if `db` is a
+/// `Tenant`. Existing keys are overwritten, other keys in the backed up ranges are kept.
+///
+/// The pairs are written wherever `db` points to, regardless of the tenant recorded in the header.
+///
+/// A corrupted file may only be noticed after part of it was restored; `verify` it first to rule
+/// that out.
+pub async fn restore<C: CreateTransaction, R: Read>(db: &C, reader: R, options: Restore) -> Result<LoadProgress, Error> {
+    let (_, entries) = open(reader)?;
+
+    let mut failure = None;
+    let kvs = entries.map_while(|entry| match entry {
+        Ok((key, value)) => Some((options.apply(key), value)),
+        Err(e) => {
+            failure = Some(e);
+            None
+        }
+    });
+    let progress = crate::bulk::bulk_load(db, stream::iter(kvs), options.load.clone(), |_| {}).await?;
+
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(progress),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup(entries: &[(&str, &str)]) -> Vec<u8> {
+        let header = BackupHeader { read_version: 42, ranges: vec![KeyRange::new("a", "z")], tenant: Some("t".into()) };
+        let mut encoder = Encoder::new(Vec::new(), &header).unwrap();
+        for (key, value) in entries {
+            encoder.entry(key.as_bytes(), value.as_bytes()).unwrap();
+        }
+        encoder.finish(entries.len() as u64).unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let file = backup(&[("a", "1"), ("b", "")]);
+        let (header, entries) = open(file.as_slice()).unwrap();
+        assert_eq!(header, BackupHeader { read_version: 42, ranges: vec![KeyRange::new("a", "z")], tenant: Some("t".into()) });
+
+        let entries: Result<Vec<_>, _> = entries.collect();
+        assert_eq!(entries.unwrap(), vec![(Key::from("a"), Value::from("1")), (Key::from("b"), Value::from(""))]);
+    }
+
+    #[test]
+    fn corruption_is_detected() {
+        let file = backup(&[("a", "1"), ("b", "2")]);
+        assert_eq!(verify(file.as_slice()).unwrap().1, 2);
+
+        let truncated = &file[..file.len() - 12];
+        assert!(verify(truncated).is_err());
+
+        // The last 8 bytes are the CRC32 and length of the gzip trailer
+        let mut flipped = file.clone();
+        let crc = flipped.len() - 8;
+        flipped[crc] ^= 0xff;
+        assert!(verify(flipped.as_slice()).is_err());
+
+        assert_eq!(open(b"FDBBKUP\x00\x03".as_slice()).err(), Some(Error::Backup("unsupported format version 3".to_string())));
+    }
+
+    #[test]
+    fn version_1_has_no_tenant() {
+        let mut file = [MAGIC.as_slice(), &1u16.to_be_bytes()].concat();
+        let mut out = GzEncoder::new(Vec::new(), Compression::default());
+        out.write_all(&[42i64.to_be_bytes().as_slice(), &0u32.to_be_bytes(), &[END], &0u64.to_be_bytes()].concat()).unwrap();
+        file.extend(out.finish().unwrap());
+
+        let (header, keys) = verify(file.as_slice()).unwrap();
+        assert_eq!((header, keys), (BackupHeader { read_version: 42, ranges: vec![], tenant: None }, 0));
+    }
+
+    #[test]
+    fn remapping() {
+        let restore = Restore::default().remap("tenant1/", "tenant2/").remap("t", "x");
+        assert_eq!(restore.apply(Key::from("tenant1/k")), Key::from("tenant2/k"));
+        assert_eq!(restore.apply(Key::from("tk")), Key::from("xk"));
+        assert_eq!(restore.apply(Key::from("other")), Key::from("other"));
+    }
+}
@@ -0,0 +1,259 @@
+use std::future::Future;
+
+use futures::{pin_mut, stream, Stream, StreamExt};
+
+use crate::{Error, FdbErrorCode};
+use crate::database::Database;
+use crate::options::TransactionOption;
+use crate::transaction::{CreateTransaction, Transaction};
+use crate::types::{Key, Value};
+
+/// Most bytes a transaction may write, beyond which commits fail with `transaction_too_large`.
+const TRANSACTION_SIZE_LIMIT: usize = 10_000_000;
+
+/// Error code of commits exceeding `TRANSACTION_SIZE_LIMIT`.
+const TRANSACTION_TOO_LARGE: i32 = 2101;
+
+/// How `Database::bulk_load` splits and commits its writes.
+#[derive(Debug, Clone)]
+pub struct BulkLoad {
+    batch_bytes: usize,
+    concurrency: usize,
+    priority_batch: bool,
+    read_your_writes_disable: bool,
+}
+
+impl Default for BulkLoad {
+    /// Batches of 1 MB, the size FoundationDB performs best with, committed 4 at a time.
+    fn default() -> Self {
+        BulkLoad { batch_bytes: 1_000_000, concurrency: 4, priority_batch: false, read_your_writes_disable: false }
+    }
+}
+
+impl BulkLoad {
+    /// Bytes of keys and values written per transaction, at most the 10 MB transaction limit.
+    pub fn batch_bytes(mut self, bytes: usize) -> Self {
+        self.batch_bytes = bytes.clamp(1, TRANSACTION_SIZE_LIMIT);
+        self
+    }
+
+    /// Number of transactions committed at the same time.
+    pub fn concurrency(mut self, transactions: usize) -> Self {
+        self.concurrency = transactions.max(1);
+        self
+    }
+
+    /// Runs the transactions with `TransactionOption::PriorityBatch`, yielding to other traffic.
+    pub fn priority_batch(mut self, enabled: bool) -> Self {
+        self.priority_batch = enabled;
+        self
+    }
+
+    /// Runs the transactions with `TransactionOption::ReadYourWritesDisable`, which saves the
+    /// client the bookkeeping of the writes.
+    pub fn read_your_writes_disable(mut self, enabled: bool) -> Self {
+        self.read_your_writes_disable = enabled;
+        self
+    }
+}
+
+/// What `Database::bulk_load` committed so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub keys: u64,
+    /// Bytes of the keys and values.
+    pub bytes: u64,
+    pub transactions: u64,
+    /// Attempts retried after a retryable error.
+    pub retries: u64,
+}
+
+impl LoadProgress {
+    fn add(&mut self, other: LoadProgress) {
+        self.keys += other.keys;
+        self.bytes += other.bytes;
+        self.transactions += other.transactions;
+        self.retries += other.retries;
+    }
+}
+
+fn batch_size(batch: &[(Key, Value)]) -> usize {
+    batch.iter().map(|(k, v)| k.len() + v.len()).sum()
+}
+
+/// Takes key-value pairs from `kvs` until they add up to `bytes`.
+async fn next_batch<S: Stream<Item=(Key, Value)> + Unpin>(kvs: &mut S, bytes: usize) -> Vec<(Key, Value)> {
+    let mut batch = Vec::new();
+    let mut size = 0;
+    while size < bytes {
+        let Some((key, value)) = kvs.next().await else {
+            break;
+        };
+        size += key.len() + value.len();
+        batch.push((key, value));
+    }
+    batch
+}
+
+/// Loads the batches of `kvs` with up to `BulkLoad::concurrency` loads in flight, reading the
+/// next batches while they commit.
+async fn load_batches<S, L, F, P>(kvs: S, options: &BulkLoad, load: L, mut on_progress: P) -> Result<LoadProgress, Error>
+where
+    S: Stream<Item=(Key, Value)> + Unpin,
+    L: FnMut(Vec<(Key, Value)>) -> F,
+    F: Future<Output=Result<LoadProgress, Error>>,
+    P: FnMut(&LoadProgress),
+{
+    let bytes = options.batch_bytes;
+    let batches = stream::unfold(kvs, |mut kvs| async move {
+        let batch = next_batch(&mut kvs, bytes).await;
+        (!batch.is_empty()).then_some((batch, kvs))
+    });
+    let loads = batches.map(load).buffer_unordered(options.concurrency);
+    pin_mut!(loads);
+
+    let mut progress = LoadProgress::default();
+    while let Some(loaded) = loads.next().await {
+        progress.add(loaded?);
+        on_progress(&progress);
+    }
+    Ok(progress)
+}
+
+impl Database {
+    /// Writes all key-value pairs of `kvs`, in as many transactions as needed.
+    ///
+    /// The pairs are split into batches of `BulkLoad::batch_bytes`, each committed in a transaction
+    /// of its own and retried on its own after retryable errors. A batch whose transaction turns
+    /// out larger than the 10 MB limit according to `Transaction::get_approximate_size` is split
+    /// in halves. `on_progress` is called after every committed batch.
+    ///
+    /// The load as a whole is not atomic: when it fails, the batches committed until then stay
+    /// written, and as batches are committed concurrently they are not necessarily the first ones.
+    /// Loading the same pairs again is harmless.
+    pub async fn bulk_load<S, K, V, P>(&self, kvs: S, options: BulkLoad, on_progress: P) -> Result<LoadProgress, Error>
+    where
+        S: Stream<Item=(K, V)>,
+        K: Into<Key>,
+        V: Into<Value>,
+        P: FnMut(&LoadProgress),
+    {
+        bulk_load(self, kvs, options, on_progress).await
+    }
+}
+
+/// `Database::bulk_load` for any transaction source.
+pub(crate) async fn bulk_load<C, S, K, V, P>(db: &C, kvs: S, options: BulkLoad, on_progress: P) -> Result<LoadProgress, Error>
+where
+    C: CreateTransaction,
+    S: Stream<Item=(K, V)>,
+    K: Into<Key>,
+    V: Into<Value>,
+    P: FnMut(&LoadProgress),
+{
+    let kvs = kvs.map(|(k, v)| (k.into(), v.into()));
+    pin_mut!(kvs);
+    load_batches(kvs, &options, |batch| load_batch(db, batch, &options), on_progress).await
+}
+
+/// Commits `batch`, split into as many transactions as the size limit requires.
+async fn load_batch<C: CreateTransaction>(db: &C, batch: Vec<(Key, Value)>, options: &BulkLoad) -> Result<LoadProgress, Error> {
+    let mut loaded = LoadProgress::default();
+    let mut pending = vec![batch];
+
+    while let Some(mut batch) = pending.pop() {
+        let mut tx = db.create_transaction()?;
+        loop {
+            match write_batch(&mut tx, &batch, options).await {
+                Ok(true) => {
+                    loaded.keys += batch.len() as u64;
+                    loaded.bytes += batch_size(&batch) as u64;
+                    loaded.transactions += 1;
+                    break;
+                }
+                Ok(false) => {
+                    let second = batch.split_off(batch.len() / 2);
+                    pending.extend([second, batch]);
+                    break;
+                }
+                Err(e) if FdbErrorCode::from(&e).0 == TRANSACTION_TOO_LARGE && batch.len() > 1 => {
+                    let second = batch.split_off(batch.len() / 2);
+                    pending.extend([second, batch]);
+                    break;
+                }
+                Err(e) => {
+                    tx.on_error(e).await?;
+                    loaded.retries += 1;
+                }
+            }
+        }
+    }
+    Ok(loaded)
+}
+
+/// Writes and commits `batch`, unless the transaction would exceed the size limit and can be
+/// split, in which case `false` is returned without committing.
+async fn write_batch(tx: &mut Transaction, batch: &[(Key, Value)], options: &BulkLoad) -> Result<bool, Error> {
+    // Options are reset with the transaction after an error
+    if options.priority_batch {
+        tx.set_option(TransactionOption::PriorityBatch)?;
+    }
+    if options.read_your_writes_disable {
+        tx.set_option(TransactionOption::ReadYourWritesDisable)?;
+    }
+
+    for (key, value) in batch {
+        tx.set(key.clone(), value.clone()).await;
+    }
+    if batch.len() > 1 && tx.get_approximate_size().await?.0 as usize > TRANSACTION_SIZE_LIMIT {
+        return Ok(false);
+    }
+
+    tx._commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn batches_fill_up_to_budget() {
+        let kvs = (0..10u8).map(|i| (Key::from(vec![i]), Value::from(vec![0u8; 9])));
+        let mut kvs = stream::iter(kvs);
+
+        let batch = block_on(next_batch(&mut kvs, 25));
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch_size(&batch), 30);
+
+        assert_eq!(block_on(next_batch(&mut kvs, 1_000)).len(), 7);
+        assert!(block_on(next_batch(&mut kvs, 1_000)).is_empty());
+    }
+
+    #[test]
+    fn options_are_bounded() {
+        let options = BulkLoad::default().batch_bytes(100_000_000).concurrency(0);
+        assert_eq!(options.batch_bytes, TRANSACTION_SIZE_LIMIT);
+        assert_eq!(options.concurrency, 1);
+    }
+
+    #[test]
+    fn batches_are_read_while_loading() {
+        // The input only continues once the batch before is loaded
+        let (sender, receiver) = mpsc::unbounded();
+        sender.unbounded_send((Key::from("a"), Value::from("1"))).unwrap();
+        let options = BulkLoad::default().batch_bytes(1);
+
+        let load = |batch: Vec<(Key, Value)>| async move {
+            Ok(LoadProgress { keys: batch.len() as u64, transactions: 1, ..Default::default() })
+        };
+        let progress = block_on(load_batches(receiver, &options, load, |progress| match progress.keys {
+            1 => sender.unbounded_send((Key::from("b"), Value::from("2"))).unwrap(),
+            _ => sender.close_channel(),
+        })).unwrap();
+        assert_eq!((progress.keys, progress.transactions), (2, 2));
+    }
+}
@@ -0,0 +1,341 @@
+//! An in-process cache of hot keys, invalidated by watches.
+//!
+//! `CachedDatabase::get` reads a key once and keeps its value together with a watch on the key.
+//! Later reads are answered from the cache until the watch fires, which the cluster does shortly
+//! after the key changes, or until the entry is older than `Cache::max_staleness`. Values may
+//! therefore lag behind the database by the watch latency, at most by the maximum staleness, which
+//! suits keys read far more often than written, like configuration.
+//!
+//! Watches are not polled by a background task: each is registered with a waker flagging the
+//! entry as changed, so the cache works on any executor. The cluster limits the watches of a
+//! client to 10,000 by default, which bounds `Cache::capacity`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::task::{Context, Wake, Waker};
+use std::time::{Duration, Instant};
+
+use crate::{Database, Error};
+use crate::layers::get_optional;
+use crate::transaction::Transaction;
+use crate::types::{Key, Value};
+
+/// How many keys `CachedDatabase` keeps, and for how long.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    capacity: usize,
+    max_staleness: Duration,
+}
+
+impl Default for Cache {
+    /// 1000 keys, each re-read at least every second.
+    fn default() -> Self {
+        Cache { capacity: 1_000, max_staleness: Duration::from_secs(1) }
+    }
+}
+
+impl Cache {
+    /// Keys kept at most, evicting the least recently read ones beyond.
+    pub fn capacity(mut self, keys: usize) -> Self {
+        self.capacity = keys.max(1);
+        self
+    }
+
+    /// Age after which an entry is read again even though its watch did not fire, covering
+    /// watches that fire late or not at all.
+    pub fn max_staleness(mut self, max_staleness: Duration) -> Self {
+        self.max_staleness = max_staleness;
+        self
+    }
+}
+
+/// Counters of a `CachedDatabase` since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped because their watch fired or they were too old.
+    pub invalidations: u64,
+    /// Entries dropped to stay within the capacity.
+    pub evictions: u64,
+    pub entries: usize,
+}
+
+/// Waker flagging that the watch of an entry resolved, because the key changed or the watch failed.
+#[derive(Default)]
+struct Changed(AtomicBool);
+
+impl Wake for Changed {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+type Watch = Pin<Box<dyn Future<Output=Result<(), Error>>>>;
+
+struct Entry {
+    value: Option<Value>,
+    read_at: Instant,
+    last_used: u64,
+    changed: Arc<Changed>,
+    /// Kept until the entry is dropped, as dropping the future cancels the watch.
+    _watch: Watch,
+}
+
+impl Entry {
+    fn is_valid(&self, max_staleness: Duration) -> bool {
+        !self.changed.0.load(Ordering::Acquire) && self.read_at.elapsed() < max_staleness
+    }
+}
+
+/// Entries with their recency, the least recently used first in `by_use`.
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<Key, Entry>,
+    by_use: BTreeMap<u64, Key>,
+    uses: u64,
+}
+
+impl Entries {
+    fn touch(&mut self, key: &Key) {
+        self.uses += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.by_use.remove(&entry.last_used);
+            entry.last_used = self.uses;
+            self.by_use.insert(self.uses, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &Key) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.by_use.remove(&entry.last_used);
+        Some(entry)
+    }
+
+    fn insert(&mut self, key: Key, entry: Entry) {
+        self.remove(&key);
+        self.entries.insert(key.clone(), entry);
+        self.touch(&key);
+    }
+
+    /// Removes the least recently used entry.
+    fn pop_oldest(&mut self) -> Option<Key> {
+        let (_, key) = self.by_use.pop_first()?;
+        self.entries.remove(&key);
+        Some(key)
+    }
+}
+
+/// A `Database` caching the values of the keys read through it, see the module docs.
+pub struct CachedDatabase<'a> {
+    db: &'a Database,
+    options: Cache,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<'a> CachedDatabase<'a> {
+    pub fn new(db: &'a Database, options: Cache) -> Self {
+        CachedDatabase {
+            db,
+            options,
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// The database reads are forwarded to, e.g. to write keys.
+    pub fn database(&self) -> &'a Database {
+        self.db
+    }
+
+    /// Cached value of `key`, `None` if it is not set. Reads the key on a miss.
+    ///
+    /// Like a snapshot read, the value is not checked for conflicts and may be older than the
+    /// latest committed one, by at most `Cache::max_staleness`.
+    pub async fn get<K: Into<Key>>(&self, key: K) -> Result<Option<Value>, Error> {
+        let key = key.into();
+        if let Some(value) = self.lookup(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            metrics::counter!("fdb_cache_hits_total").increment(1);
+            return Ok(value);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("fdb_cache_misses_total").increment(1);
+
+        let (value, watch) = self.db.run(async |tx: &mut Transaction| {
+            let value = get_optional(tx, key.clone()).await?;
+            Ok((value, tx.watch(key.clone())))
+        }).await?;
+
+        // Registers the watch with a waker flagging the entry, and leaves the future alone after
+        let changed = Arc::new(Changed::default());
+        let mut watch: Watch = Box::pin(watch);
+        let waker = Waker::from(changed.clone());
+        if watch.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+            // Changed already, or watches are unavailable, e.g. too many of them
+            return Ok(value);
+        }
+
+        let entry = Entry { value: value.clone(), read_at: Instant::now(), last_used: 0, changed, _watch: watch };
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, entry);
+        while entries.entries.len() > self.options.capacity {
+            entries.pop_oldest();
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            metrics::counter!("fdb_cache_evictions_total").increment(1);
+        }
+        Ok(value)
+    }
+
+    /// The value of a valid entry, dropping an invalid one.
+    fn lookup(&self, key: &Key) -> Option<Option<Value>> {
+        let mut entries = self.entries.lock().unwrap();
+        let valid = entries.entries.get(key)?.is_valid(self.options.max_staleness);
+        if !valid {
+            entries.remove(key);
+            self.invalidations.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            metrics::counter!("fdb_cache_invalidations_total").increment(1);
+            return None;
+        }
+        entries.touch(key);
+        Some(entries.entries[key].value.clone())
+    }
+
+    /// Drops the entry of `key`, e.g. right after writing it, so that the next read does not wait
+    /// for the watch.
+    pub fn invalidate<K: Into<Key>>(&self, key: K) {
+        self.entries.lock().unwrap().remove(&key.into());
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().entries.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+
+    use super::*;
+
+    fn entry(value: &str, changed: &Arc<Changed>) -> Entry {
+        Entry {
+            value: Some(Value::from(value)),
+            read_at: Instant::now(),
+            last_used: 0,
+            changed: changed.clone(),
+            _watch: Box::pin(future::pending()),
+        }
+    }
+
+    /// A watch that never fires and flags being dropped, i.e. cancelled.
+    struct Cancelled(Arc<AtomicBool>);
+
+    impl Future for Cancelled {
+        type Output = Result<(), Error>;
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> std::task::Poll<Self::Output> {
+            std::task::Poll::Pending
+        }
+    }
+
+    impl Drop for Cancelled {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn least_recently_used_goes_first() {
+        let changed = Arc::new(Changed::default());
+        let mut entries = Entries::default();
+        for key in ["a", "b", "c"] {
+            entries.insert(Key::from(key), entry(key, &changed));
+        }
+        entries.touch(&Key::from("a"));
+        entries.insert(Key::from("b"), entry("b2", &changed));
+
+        assert_eq!(entries.pop_oldest(), Some(Key::from("c")));
+        assert_eq!(entries.pop_oldest(), Some(Key::from("a")));
+        assert_eq!(entries.pop_oldest(), Some(Key::from("b")));
+        assert_eq!(entries.pop_oldest(), None);
+        assert!(entries.entries.is_empty());
+    }
+
+    #[test]
+    fn waking_invalidates() {
+        let changed = Arc::new(Changed::default());
+        let entry = entry("a", &changed);
+        assert!(entry.is_valid(Duration::from_secs(1)));
+        assert!(!entry.is_valid(Duration::ZERO));
+
+        Waker::from(changed.clone()).wake();
+        assert!(!entry.is_valid(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn evicted_and_refreshed_entries_cancel_their_watch() {
+        let changed = Arc::new(Changed::default());
+        let cancelled: Vec<_> = (0..3).map(|_| Arc::new(AtomicBool::new(false))).collect();
+        let watched = |i: usize| Entry { _watch: Box::pin(Cancelled(cancelled[i].clone())), ..entry("v", &changed) };
+        let is_cancelled = |i: usize| cancelled[i].load(Ordering::Acquire);
+
+        let mut entries = Entries::default();
+        entries.insert(Key::from("a"), watched(0));
+        entries.insert(Key::from("b"), watched(1));
+        assert!(!is_cancelled(0) && !is_cancelled(1));
+
+        // Refreshed
+        entries.insert(Key::from("a"), watched(2));
+        assert!(is_cancelled(0) && !is_cancelled(2));
+
+        // Evicted
+        assert_eq!(entries.pop_oldest(), Some(Key::from("b")));
+        assert!(is_cancelled(1));
+
+        // Invalidated
+        entries.remove(&Key::from("a"));
+        assert!(is_cancelled(2));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn evictions_and_refreshes_release_watches() {
+        let cluster = crate::testing::TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let subspace = cluster.subspace();
+
+        // Each read registers a watch, so together more than the 10,000 allowed by default, which
+        // are only cached if the dropped ones left the cluster
+        let cache = CachedDatabase::new(&db, Cache::default().capacity(1));
+        for i in 0..6_000 {
+            cache.get(subspace.key(i.to_string())).await.unwrap();
+        }
+        assert_eq!((cache.stats().evictions, cache.stats().entries), (5_999, 1));
+
+        let cache = CachedDatabase::new(&db, Cache::default().max_staleness(Duration::ZERO));
+        for _ in 0..6_000 {
+            cache.get(subspace.key("refreshed")).await.unwrap();
+        }
+        assert_eq!((cache.stats().invalidations, cache.stats().entries), (5_999, 1));
+    }
+}
@@ -0,0 +1,399 @@
+//! Fault injection for testing that transactional code survives retries.
+//!
+//! `ChaosDatabase` runs transactions like `Database::run`, but randomly fails their commits with
+//! the errors a real cluster produces, before or after actually committing, and delays commits and
+//! reads. The errors are handled by `fdb_transaction_on_error` like any other, so the code under
+//! test sees the same retries it would in production, only far more often. Faults are drawn from
+//! a seeded random number generator, so that a failing run can be repeated.
+//!
+//! Reads are delayed when made through the `ChaosTransaction`, directly or by code generic over
+//! `ReadTransaction`. Reads through the wrapped `Transaction` it dereferences to are not.
+
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_stream::try_stream;
+use futures::{pin_mut, Stream, StreamExt};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::{Database, Error, FdbErrorCode};
+use crate::transaction::{CreateTransaction, ReadTransaction, Transaction};
+use crate::types::{Key, KeySelector, Value};
+
+/// An error `ChaosDatabase` injects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// `not_committed`: the transaction conflicted with another one.
+    NotCommitted,
+    /// `commit_unknown_result`: the transaction may or may not have committed.
+    CommitUnknownResult,
+    /// `transaction_too_old`: the read version is older than the MVCC window.
+    TransactionTooOld,
+    /// `transaction_timed_out`: the `Timeout` option elapsed. Not retried by `on_error`.
+    TimedOut,
+}
+
+impl Fault {
+    pub const ALL: [Fault; 4] = [Fault::NotCommitted, Fault::CommitUnknownResult, Fault::TransactionTooOld, Fault::TimedOut];
+
+    pub fn code(self) -> i32 {
+        match self {
+            Fault::NotCommitted => 1020,
+            Fault::CommitUnknownResult => 1021,
+            Fault::TransactionTooOld => 1007,
+            Fault::TimedOut => 1031,
+        }
+    }
+
+    fn error(self) -> Error {
+        FdbErrorCode(self.code()).into()
+    }
+
+    /// Whether a real commit can fail with this error although the transaction committed.
+    /// `not_committed` and `transaction_too_old` are only returned for transactions that did not.
+    fn after_commit(self) -> bool {
+        matches!(self, Fault::CommitUnknownResult | Fault::TimedOut)
+    }
+}
+
+/// When a fault is injected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// The commit fails without committing.
+    BeforeCommit,
+    /// The commit fails although the transaction committed, like a lost reply. Retrying must not
+    /// apply the transaction's effects twice. Only `CommitUnknownResult` and `TimedOut` are
+    /// injected at this timing, the other faults never follow a successful commit.
+    AfterCommit,
+}
+
+/// Which faults `ChaosDatabase` injects, how often and with which delays.
+#[derive(Debug, Clone)]
+pub struct Chaos {
+    seed: u64,
+    probability: f64,
+    faults: Vec<Fault>,
+    timings: Vec<Timing>,
+    max_delay: Duration,
+}
+
+impl Chaos {
+    /// Fails a quarter of the commits with any of the faults at any timing they can occur at,
+    /// without delays.
+    pub fn new(seed: u64) -> Self {
+        Chaos {
+            seed,
+            probability: 0.25,
+            faults: Fault::ALL.to_vec(),
+            timings: vec![Timing::BeforeCommit, Timing::AfterCommit],
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    /// Fails each commit with this probability.
+    pub fn probability(mut self, probability: f64) -> Self {
+        self.probability = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Injects only these faults.
+    pub fn faults(mut self, faults: &[Fault]) -> Self {
+        self.faults = faults.to_vec();
+        self
+    }
+
+    /// Injects faults only at these timings.
+    pub fn timings(mut self, timings: &[Timing]) -> Self {
+        self.timings = timings.to_vec();
+        self
+    }
+
+    /// Delays every commit and read by up to `max_delay`, so that transactions overlap more and
+    /// conflict.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+}
+
+/// A commit attempt made through a `ChaosDatabase`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    /// Number of the `ChaosTransaction`, counting from 0 in the order they were created.
+    pub transaction: u64,
+    /// Number of the attempt within the transaction, counting from 1.
+    pub attempt: u32,
+    pub fault: Option<(Fault, Timing)>,
+    pub delay: Duration,
+    /// Whether the transaction actually committed.
+    pub committed: bool,
+    /// Error code the commit failed with.
+    pub error: Option<i32>,
+}
+
+#[derive(Debug)]
+struct State {
+    rng: StdRng,
+    transactions: u64,
+    attempts: Vec<Attempt>,
+}
+
+impl State {
+    /// Decides the fault and delay of the next commit, skipping faults at timings they cannot
+    /// occur at.
+    fn roll(&mut self, chaos: &Chaos) -> (Option<(Fault, Timing)>, Duration) {
+        let possible: Vec<_> = chaos.faults.iter()
+            .flat_map(|&fault| chaos.timings.iter().map(move |&timing| (fault, timing)))
+            .filter(|&(fault, timing)| timing == Timing::BeforeCommit || fault.after_commit())
+            .collect();
+        let fault = match possible.is_empty() {
+            true => None,
+            false => self.rng.gen_bool(chaos.probability).then(|| possible[self.rng.gen_range(0..possible.len())]),
+        };
+        (fault, self.delay(chaos))
+    }
+
+    /// Decides the delay of the next commit or read.
+    fn delay(&mut self, chaos: &Chaos) -> Duration {
+        match chaos.max_delay.is_zero() {
+            true => Duration::ZERO,
+            false => self.rng.gen_range(Duration::ZERO..=chaos.max_delay),
+        }
+    }
+}
+
+/// A `Database` injecting faults into the commits of its transactions, see the module docs.
+pub struct ChaosDatabase<'a> {
+    db: &'a Database,
+    chaos: Chaos,
+    state: Arc<Mutex<State>>,
+}
+
+impl<'a> ChaosDatabase<'a> {
+    pub fn new(db: &'a Database, chaos: Chaos) -> Self {
+        let state = State { rng: StdRng::seed_from_u64(chaos.seed), transactions: 0, attempts: Vec::new() };
+        ChaosDatabase { db, chaos, state: Arc::new(Mutex::new(state)) }
+    }
+
+    /// Creates a transaction for retry loops of its own, using `ChaosTransaction::commit` and
+    /// `ChaosTransaction::on_error`.
+    pub fn create_transaction(&self) -> Result<ChaosTransaction, Error> {
+        let tx = self.db.create_transaction()?;
+        let number = {
+            let mut state = self.state.lock().unwrap();
+            state.transactions += 1;
+            state.transactions - 1
+        };
+        Ok(ChaosTransaction { tx, number, attempts: 0, chaos: self.chaos.clone(), state: self.state.clone() })
+    }
+
+    /// Like `Database::run`, with faults injected into the commits and reads through the
+    /// `ChaosTransaction` delayed.
+    pub async fn run<T, F>(&self, mut f: F) -> Result<T, Error>
+    where
+        F: AsyncFnMut(&mut ChaosTransaction) -> Result<T, Error>,
+    {
+        let mut tx = self.create_transaction()?;
+        loop {
+            let attempt = match f(&mut tx).await {
+                Ok(value) => tx.commit().await.map(|()| value),
+                Err(e) => Err(e),
+            };
+
+            match attempt {
+                Ok(value) => return Ok(value),
+                Err(e) => tx.on_error(e).await?,
+            }
+        }
+    }
+
+    /// Every commit attempt so far, in order.
+    pub fn attempts(&self) -> Vec<Attempt> {
+        self.state.lock().unwrap().attempts.clone()
+    }
+}
+
+/// A transaction whose commits fail at random and whose reads are delayed. Dereferences to the
+/// wrapped `Transaction`.
+pub struct ChaosTransaction {
+    tx: Transaction,
+    number: u64,
+    attempts: u32,
+    chaos: Chaos,
+    state: Arc<Mutex<State>>,
+}
+
+impl ChaosTransaction {
+    /// Commits, unless a fault is injected before, and may fail with a fault after committing.
+    /// Unlike `Transaction::commit` the transaction is kept for `on_error`.
+    pub async fn commit(&mut self) -> Result<(), Error> {
+        self.attempts += 1;
+        let (fault, delay) = self.state.lock().unwrap().roll(&self.chaos);
+        if !delay.is_zero() {
+            crate::runtime::sleep(delay).await;
+        }
+
+        let (committed, result) = match fault {
+            Some((fault, Timing::BeforeCommit)) => (false, Err(fault.error())),
+            Some((fault, Timing::AfterCommit)) => match self.tx._commit().await {
+                Ok(()) => (true, Err(fault.error())),
+                Err(e) => (false, Err(e)),
+            },
+            None => {
+                let result = self.tx._commit().await;
+                (result.is_ok(), result)
+            }
+        };
+
+        self.state.lock().unwrap().attempts.push(Attempt {
+            transaction: self.number,
+            attempt: self.attempts,
+            fault,
+            delay,
+            committed,
+            error: result.as_ref().err().map(|e| FdbErrorCode::from(e).0),
+        });
+        result
+    }
+
+    /// See `Transaction::on_error`.
+    pub async fn on_error(&mut self, error: Error) -> Result<(), Error> {
+        self.tx.on_error(error).await
+    }
+
+    async fn delay(&self) {
+        let delay = self.state.lock().unwrap().delay(&self.chaos);
+        if !delay.is_zero() {
+            crate::runtime::sleep(delay).await;
+        }
+    }
+
+    /// `Transaction::get`, after a delay.
+    pub async fn get<K: Into<Key>>(&self, key: K) -> Result<Value, Error> {
+        self.delay().await;
+        self.tx.get(key).await
+    }
+
+    /// `Transaction::get_key`, after a delay.
+    pub async fn get_key<S: Into<KeySelector>>(&self, selector: S) -> Result<Key, Error> {
+        self.delay().await;
+        self.tx.get_key(selector).await
+    }
+
+    /// `Transaction::get_range`, starting after a delay.
+    pub fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> + '_ {
+        let (start, end) = (start.into(), end.into());
+        try_stream! {
+            self.delay().await;
+            let range = self.tx.get_range(start, end, limit, target_bytes, reverse);
+            pin_mut!(range);
+            while let Some(kv) = range.next().await {
+                yield kv?;
+            }
+        }
+    }
+}
+
+impl ReadTransaction for ChaosTransaction {
+    fn get_read_version(&self) -> impl Future<Output=Result<i64, Error>> {
+        self.tx.get_read_version()
+    }
+
+    fn get<K: Into<Key>>(&self, key: K) -> impl Future<Output=Result<Value, Error>> {
+        ChaosTransaction::get(self, key)
+    }
+
+    fn get_key<S: Into<KeySelector>>(&self, selector: S) -> impl Future<Output=Result<Key, Error>> {
+        ChaosTransaction::get_key(self, selector)
+    }
+
+    fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> {
+        ChaosTransaction::get_range(self, start, end, limit, target_bytes, reverse)
+    }
+}
+
+impl Deref for ChaosTransaction {
+    type Target = Transaction;
+
+    fn deref(&self) -> &Transaction {
+        &self.tx
+    }
+}
+
+impl DerefMut for ChaosTransaction {
+    fn deref_mut(&mut self) -> &mut Transaction {
+        &mut self.tx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(seed: u64) -> State {
+        State { rng: StdRng::seed_from_u64(seed), transactions: 0, attempts: Vec::new() }
+    }
+
+    #[test]
+    fn rolls_are_seeded() {
+        let chaos = Chaos::new(7).probability(0.5).max_delay(Duration::from_millis(20));
+        let (mut a, mut b) = (state(7), state(7));
+        let rolls: Vec<_> = (0..50).map(|_| a.roll(&chaos)).collect();
+        assert_eq!(rolls, (0..50).map(|_| b.roll(&chaos)).collect::<Vec<_>>());
+
+        assert!(rolls.iter().any(|(fault, _)| fault.is_some()));
+        assert!(rolls.iter().any(|(fault, _)| fault.is_none()));
+        assert!(rolls.iter().all(|(_, delay)| *delay <= Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn restricted_faults() {
+        let chaos = Chaos::new(1).probability(1.0).faults(&[Fault::CommitUnknownResult]).timings(&[Timing::AfterCommit]);
+        let mut state = state(1);
+        for _ in 0..10 {
+            assert_eq!(state.roll(&chaos), (Some((Fault::CommitUnknownResult, Timing::AfterCommit)), Duration::ZERO));
+        }
+
+        let never = Chaos::new(1).probability(0.0);
+        assert!((0..10).all(|_| state.roll(&never).0.is_none()));
+        assert!(state.roll(&Chaos::new(1).probability(1.0).faults(&[])).0.is_none());
+    }
+
+    #[test]
+    fn only_unknown_results_and_timeouts_follow_commits() {
+        let mut state = state(3);
+        let chaos = Chaos::new(3).probability(1.0);
+        for _ in 0..200 {
+            let (fault, timing) = state.roll(&chaos).0.unwrap();
+            assert!(timing == Timing::BeforeCommit || matches!(fault, Fault::CommitUnknownResult | Fault::TimedOut), "{fault:?}");
+        }
+
+        let impossible = chaos.faults(&[Fault::NotCommitted, Fault::TransactionTooOld]).timings(&[Timing::AfterCommit]);
+        assert!((0..10).all(|_| state.roll(&impossible).0.is_none()));
+    }
+
+    #[test]
+    fn fault_errors() {
+        assert_eq!(Fault::TransactionTooOld.error(), Error::TransactionTooOld);
+        for fault in Fault::ALL {
+            assert_eq!(FdbErrorCode::from(&fault.error()).0, fault.code());
+        }
+    }
+}
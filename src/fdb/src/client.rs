@@ -1,16 +1,17 @@
-use std::ffi::c_char;
+use std::ffi::{c_char, CString};
+use std::path::{Path, PathBuf};
 use std::ptr;
 use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
-use std::task::Poll;
+use std::sync::{mpsc, Mutex};
+use std::thread;
 
-use futures::poll;
-use log::{error, info, warn};
-use tokio::task;
+use log::{error, info};
 
 use fdb_c::FDB_API_VERSION;
 
 use crate::{Error, FdbErrorCode};
 use crate::database::Database;
+use crate::options::NetworkOption;
 
 /// Singleton Client Instance
 /// TODO: Maybe cheaply clone using ARC? Is this needed?
@@ -20,42 +21,161 @@ pub struct Client;
 static NETWORK_SETUP: AtomicBool = AtomicBool::new(false);
 static NETWORK_STARTED: AtomicBool = AtomicBool::new(false);
 static API_VERSION_SET: AtomicI32 = AtomicI32::new(0);
+/// Handle of the thread driving `fdb_run_network`, joined once the network has been stopped.
+static NETWORK_THREAD: Mutex<Option<thread::JoinHandle<Result<(), Error>>>> = Mutex::new(None);
 
-/// Must be called before any other API functions. version must be less than or equal to FDB_API_VERSION (and should almost always be equal).
+/// Oldest API version that can be selected when libfdb_c is loaded at runtime. The declarations of
+/// the newest header are used for every version, and older versions differ in some signatures.
+#[cfg(feature = "dlopen")]
+const MIN_DLOPEN_API_VERSION: i32 = 630;
+
+/// The API version selected when the client was built, 0 before.
+pub(crate) fn api_version() -> i32 {
+    API_VERSION_SET.load(Ordering::Acquire)
+}
+
+/// Whether `function`, available from API version `since` on, can be used with the selected API
+/// version and, when libfdb_c is loaded at runtime, is exported by the loaded library.
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+pub(crate) fn supports(since: i32, function: &str) -> bool {
+    #[cfg(feature = "dlopen")]
+    if !fdb_c::is_available(function) {
+        return false;
+    }
+    #[cfg(not(feature = "dlopen"))]
+    let _ = function;
+
+    api_version() >= since
+}
+
+/// Fails with `Error::Unsupported` if `function` can not be used, see `supports`.
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+pub(crate) fn require(since: i32, function: &'static str) -> Result<(), Error> {
+    if !supports(since, function) {
+        return Err(Error::Unsupported(function));
+    }
+    Ok(())
+}
+
+/// Configures the client before the network is started. Created with `Client::builder`.
 ///
-/// Passing a version less than FDB_API_VERSION will cause the API to behave as it did in the older version.
+/// The API version, library and network options only take effect for the first client built in a
+/// process, since the network can only be set up once.
+#[derive(Debug, Clone, Default)]
+pub struct ClientBuilder {
+    api_version: Option<i32>,
+    #[cfg(feature = "dlopen")]
+    library_path: Option<PathBuf>,
+    network_options: Vec<NetworkOption>,
+    record_commit_sizes: bool,
+}
 
+impl ClientBuilder {
+    /// Selects the API version, the client then behaves as it did in that version. Defaults to the
+    /// newest version supported by both these bindings and the client library.
+    ///
+    /// With the `dlopen` feature any version from 630 up to `fdb_get_max_api_version()` of the
+    /// loaded library can be selected. Functions introduced after the selected version fail with
+    /// `Error::Unsupported`.
+    pub fn api_version(mut self, version: i32) -> Self {
+        self.api_version = Some(version);
+        self
+    }
 
-struct FDBNetworkOptions;
+    /// Loads libfdb_c from this path instead of searching the system's library path.
+    #[cfg(feature = "dlopen")]
+    pub fn library_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.library_path = Some(path.into());
+        self
+    }
 
+    /// Sets a network option before the network is set up.
+    pub fn network_option(mut self, option: NetworkOption) -> Self {
+        self.network_options.push(option);
+        self
+    }
 
-impl Client {
-    pub async fn new() -> Result<Self, Error> {
-        Self::select_api_version(Self::get_max_api_version()).expect("Invalid API version");
+    /// Adds a client library for the multi-version client, to connect to clusters running at
+    /// another version than the primary library.
+    pub fn external_client_library<P: Into<PathBuf>>(self, path: P) -> Self {
+        self.network_option(NetworkOption::ExternalClientLibrary(path.into()))
+    }
 
-        // Init network
-        Self::setup_network(FDBNetworkOptions)?;
+    /// Adds every client library in the directory for the multi-version client.
+    pub fn external_client_directory<P: Into<PathBuf>>(self, path: P) -> Self {
+        self.network_option(NetworkOption::ExternalClientDirectory(path.into()))
+    }
+
+    /// Records the approximate size of every commit in the transaction span and metrics, see the
+    /// `tracing` and `metrics` features. Costs a call into the client per commit, and applies to
+    /// the whole process.
+    pub fn record_commit_sizes(mut self) -> Self {
+        self.record_commit_sizes = true;
+        self
+    }
 
-        let handle: task::JoinHandle<_> = task::spawn_blocking(Self::run_network);
+    /// Selects the API version, sets up the network and starts it on a dedicated `std::thread`.
+    ///
+    /// No async runtime is required: every `FDBFuture` is woken through `fdb_future_set_callback`
+    /// from the network thread, so the returned futures can be driven by tokio, async-std, smol or
+    /// any other executor.
+    pub fn build(self) -> Result<Client, Error> {
+        #[cfg(feature = "dlopen")]
+        fdb_c::load(self.library_path.as_deref()).map_err(|e| Error::LibraryLoad(e.to_string()))?;
+
+        let version = self.api_version
+            .unwrap_or_else(|| Client::get_max_api_version().min(FDB_API_VERSION as i32));
+        Client::select_api_version(version)?;
+        if self.record_commit_sizes {
+            crate::telemetry::record_commit_sizes();
+        }
 
-        // Poll the network once to check if it errored on initialization
-        let poll = poll!(handle);
-        if let Poll::Ready(join) = poll {
-            join.expect("Could not join network init thread")?;
+        let mut network_thread = NETWORK_THREAD.lock().unwrap();
+        if network_thread.is_none() && !NETWORK_STARTED.load(Ordering::Acquire) {
+            let (sender, setup) = mpsc::channel();
+            let options = self.network_options;
+            let handle = thread::Builder::new()
+                .name("fdb-network".into())
+                .spawn(move || {
+                    // Reported before blocking in `fdb_run_network`, which only returns once stopped
+                    let result = Client::setup_network(&options);
+                    let ready = result.is_ok();
+                    let _ = sender.send(result);
+                    if ready { Client::run_network() } else { Ok(()) }
+                })
+                .expect("Could not spawn the network thread");
+
+            setup.recv().expect("The network thread exited before setting up the network")?;
+            *network_thread = Some(handle);
         }
 
-        Ok(Self)
+        Ok(Client)
+    }
+}
+
+impl Client {
+    /// Builds a client with the default configuration, see `ClientBuilder::build`.
+    pub fn new() -> Result<Self, Error> {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
     }
 
+    /// Must be called before any other API functions. version must be less than or equal to FDB_API_VERSION (and should almost always be equal).
+    ///
+    /// Passing a version less than FDB_API_VERSION will cause the API to behave as it did in the older version.
     fn select_api_version(version: i32) -> Result<(), Error> {
 
-        if version > FDB_API_VERSION as i32 || version > Self::get_max_api_version() || ![510, 520, 600, 610, 620, 630, 700, 710, 730].contains(&version) {
+        if version > FDB_API_VERSION as i32 || version > Self::get_max_api_version() || !Self::is_known_api_version(version) {
             dbg!(version, FDB_API_VERSION);
             return Err(Error::APIVersionNotSupported);
         }
 
+        #[cfg(not(feature = "dlopen"))]
         if version != FDB_API_VERSION as i32 {
-            warn!("Selected API version should almost always be equal to the foundation db version feature")
+            log::warn!("Selected API version should almost always be equal to the foundation db version feature")
         }
 
         let atomic_version_update = API_VERSION_SET
@@ -72,7 +192,7 @@ impl Client {
         }
 
 
-        let result = unsafe { fdb_c::fdb_select_api_version_impl(version, FDB_API_VERSION as i32) };
+        let result = unsafe { fdb_c::fdb_select_api_version_impl(version, Self::header_version(version)) };
 
         if result != 0 {
             error!("{result}");
@@ -81,21 +201,36 @@ impl Client {
         Ok(())
     }
 
+    #[cfg(not(feature = "dlopen"))]
+    fn is_known_api_version(version: i32) -> bool {
+        [510, 520, 600, 610, 620, 630, 700, 710, 730].contains(&version)
+    }
+
+    #[cfg(feature = "dlopen")]
+    fn is_known_api_version(version: i32) -> bool {
+        version >= MIN_DLOPEN_API_VERSION
+    }
+
+    /// Version of the header the bindings were generated from.
+    #[cfg(not(feature = "dlopen"))]
+    fn header_version(_version: i32) -> i32 {
+        FDB_API_VERSION as i32
+    }
+
+    /// The library may be older than the header the bindings were generated from, and refuses
+    /// header versions newer than itself. Only the functions available in the selected version are
+    /// used (see `require`), so the selected version is passed as the header version.
+    #[cfg(feature = "dlopen")]
+    fn header_version(version: i32) -> i32 {
+        version
+    }
+
     fn get_max_api_version() -> i32 {
         unsafe { fdb_c::fdb_get_max_api_version() }
     }
 
-    /// Idempotent singleton network setup
-    fn setup_network(_options: FDBNetworkOptions) -> Result<(), Error> {
-        // TODO: Options
-        // Set options
-        // let options: Vec<FDBNetworkOption> = options.into();
-        //
-        // for (option, value) in options {
-        //     let res = fdb_network_set_option(o, value, mem::length(value));
-        // }
-
-        // Setup network
+    /// Idempotent singleton network setup, setting the options first
+    fn setup_network(options: &[NetworkOption]) -> Result<(), Error> {
         let first_time = NETWORK_SETUP
             .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
             .is_ok();
@@ -104,6 +239,23 @@ impl Client {
             return Ok(());
         }
 
+        for option in options {
+            let value = option.value();
+            let (value_ptr, value_length) = match &value {
+                Some(v) => (v.as_ptr(), v.len() as i32),
+                None => (ptr::null(), 0),
+            };
+
+            let result = unsafe { fdb_c::fdb_network_set_option(option.code(), value_ptr, value_length) };
+
+            if result != 0 {
+                error!("{result}");
+                return Err(FdbErrorCode(result).into());
+            }
+        }
+
+        // Setup network
+
         let result = unsafe { fdb_c::fdb_setup_network() };
 
         if result != 0 {
@@ -163,13 +315,24 @@ impl Client {
     /// Connects to a database on the specified cluster. The caller assumes ownership of the
     /// FDBDatabase object and must destroy it with fdb_database_destroy() (Implemented to automatically happen on Drop).
     ///
-    /// --- TODO: Not implemented yet, always uses the default cluster file ---
+    /// Uses the default cluster file, see `Client::database_from` to select another one.
+    pub fn database(&self) -> Result<Database, Error> {
+        Self::create_database(ptr::null())
+    }
+
+    /// Connects to the database of the cluster described by `cluster_file`.
+    ///
     /// A single client can use this function multiple times to connect to different clusters
     /// simultaneously, with each invocation requiring its own cluster file.
     /// To connect to multiple clusters running at different, incompatible versions, the multi-version client API must be used.
-    pub fn database(&self) -> Result<Database, Error> {
+    pub fn database_from<P: AsRef<Path>>(&self, cluster_file: P) -> Result<Database, Error> {
+        let invalid = || Error::InvalidClusterFile(cluster_file.as_ref().to_path_buf());
+        let path = CString::new(path_bytes(cluster_file.as_ref()).ok_or_else(invalid)?).map_err(|_| invalid())?;
+        Self::create_database(path.as_ptr())
+    }
+
+    fn create_database(cluster_file_path: *const c_char) -> Result<Database, Error> {
         let mut db = ptr::null_mut();
-        let cluster_file_path: *const c_char = ptr::null();
 
         let result = unsafe { fdb_c::fdb_create_database(cluster_file_path, &mut db) };
         if result != 0 {
@@ -181,10 +344,29 @@ impl Client {
     }
 }
 
+/// The path as the C API expects it, as is on unix and `None` if not UTF-8 elsewhere.
+fn path_bytes(path: &Path) -> Option<Vec<u8>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(path.as_os_str().as_bytes().to_vec())
+    }
+    #[cfg(not(unix))]
+    {
+        path.to_str().map(|path| path.as_bytes().to_vec())
+    }
+}
+
 impl Drop for Client {
     fn drop(&mut self) {
         info!("Stopping foundation db network...");
-        Self::stop_network().expect("Stopping the network failed")
+        Self::stop_network().expect("Stopping the network failed");
+
+        if let Some(handle) = NETWORK_THREAD.lock().unwrap().take() {
+            if let Err(e) = handle.join().expect("Could not join the network thread") {
+                error!("Network thread exited with {e}");
+            }
+        }
     }
 }
 
@@ -215,6 +397,16 @@ mod tests {
         assert_eq!(fdb_c::FDB_API_VERSION, 510);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_cluster_file() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/etc/fdb\xff.cluster"));
+        assert_eq!(path_bytes(path), Some(b"/etc/fdb\xff.cluster".to_vec()));
+    }
+
     #[test]
     fn test_select_invalid_api_version() {
         let result = Client::select_api_version(99999);
@@ -223,6 +415,8 @@ mod tests {
         assert_eq!(result, Err(Error::APIVersionNotSupported))
     }
 
+    // Calls into libfdb_c, which is only loaded by `ClientBuilder::build` with `dlopen`
+    #[cfg(not(feature = "dlopen"))]
     #[test]
     fn test_select_api_version() {
         let result = Client::select_api_version(Client::get_max_api_version());
@@ -235,10 +429,11 @@ mod tests {
         assert_eq!(result, Err(Error::APIVersionSingletonViolated));
     }
 
-    #[tokio::test]
-    async fn init_client_idempotent() {
-        let client = Client::new().await;
-        let client2 = Client::new().await;
+    #[cfg(not(feature = "dlopen"))]
+    #[test]
+    fn init_client_idempotent() {
+        let client = Client::new();
+        let client2 = Client::new();
       
         assert!(client.is_ok());
         assert!(client.is_ok());
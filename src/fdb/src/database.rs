@@ -2,10 +2,16 @@ use std::ptr;
 use log::error;
 use fdb_c::FDBDatabase;
 
-#[cfg(any(feature = "730", feature = "710"))]
+#[cfg(any(feature = "730", feature = "710", feature = "dlopen"))]
 use crate::tenant::Tenant;
 
-use crate::transaction::{CreateTransaction, Transaction};
+use crate::transaction::{CreateTransaction, ReadOnlyTransaction, Transaction};
+#[cfg(any(feature = "730", feature = "dlopen"))]
+use crate::future::FDBFuture;
+#[cfg(any(feature = "730", feature = "dlopen"))]
+use crate::status::ClientStatus;
+#[cfg(any(feature = "730", feature = "dlopen"))]
+use crate::types::Key;
 
 pub struct Database(*mut FDBDatabase);
 
@@ -17,12 +23,39 @@ impl From<*mut FDBDatabase> for Database {
 
 
 impl Database {
+    /// Runs `f` in a transaction and commits it, retrying after retryable errors of either as
+    /// decided by `Transaction::on_error`.
+    ///
+    /// `f` may run several times and should therefore not have side effects outside of the
+    /// transaction. Returns the result of the attempt that committed.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, crate::Error>
+    where
+        F: AsyncFnMut(&mut Transaction) -> Result<T, crate::Error>,
+    {
+        run(self, f).await
+    }
+
+    /// Runs `f` in a read-only transaction, retrying after retryable errors as decided by
+    /// `Transaction::on_error`. Nothing is committed.
+    pub async fn read<T, F>(&self, f: F) -> Result<T, crate::Error>
+    where
+        F: AsyncFnMut(&ReadOnlyTransaction) -> Result<T, crate::Error>,
+    {
+        read(self, f).await
+    }
+
     fn set_option() -> Result<(), crate::Error> {
         todo!()
     }
 
-    #[cfg(any(feature = "730", feature = "710"))]
-    fn tenant(&mut self, name: &str) -> Result<Tenant, crate::Error> {
+    /// Opens the tenant `name`, whose transactions only see the tenant's keys. Tenants are created
+    /// and deleted through the special key space, see `TransactionOption::SpecialKeySpaceEnableWrites`.
+    ///
+    /// Opening a tenant that does not exist succeeds, its transactions fail with
+    /// `tenant_not_found` instead.
+    #[cfg(any(feature = "730", feature = "710", feature = "dlopen"))]
+    pub fn tenant(&self, name: &str) -> Result<Tenant, crate::Error> {
+        crate::client::require(710, "fdb_database_open_tenant")?;
         let tenant_name = name.as_bytes();
         let mut tenant = ptr::null_mut();
 
@@ -40,7 +73,7 @@ impl Database {
             return Err(crate::FdbErrorCode(result).into());
         }
 
-        Ok(tenant.into())
+        Ok(Tenant::new(tenant, name))
     }
 
     fn reboot_worker() {
@@ -57,12 +90,71 @@ impl Database {
 
     /// Returns a value where 0 indicates that the client is idle and 1 (or larger) indicates
     /// that the client is saturated. By default, this value is updated every second.
-    #[cfg(any(feature = "730", feature = "710", feature = "700"))]
-    fn get_main_thread_busyness(&mut self) -> f64 {
-        unsafe { fdb_c::fdb_database_get_main_thread_busyness(self.0) }
+    ///
+    /// All transactions of the process are driven by the single network thread, so this is the
+    /// signal to shed load on, see `limiter::LimitedDatabase`.
+    #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+    pub fn get_main_thread_busyness(&self) -> Result<f64, crate::Error> {
+        crate::client::require(700, "fdb_database_get_main_thread_busyness")?;
+        Ok(unsafe { fdb_c::fdb_database_get_main_thread_busyness(self.0) })
+    }
+
+    /// The client's view of the cluster: the coordinators and servers it knows of and the state of
+    /// its connections to them.
+    #[cfg(any(feature = "730", feature = "dlopen"))]
+    pub async fn get_client_status(&self) -> Result<ClientStatus, crate::Error> {
+        crate::client::require(730, "fdb_database_get_client_status")?;
+        let future: FDBFuture<Key> = unsafe { get_client_status(self.0) }.into();
+        ClientStatus::parse(&future.await?)
+    }
+}
+
+#[cfg(all(feature = "730", not(feature = "dlopen")))]
+use fdb_c::fdb_database_get_client_status as get_client_status;
+
+/// `fdb_database_get_client_status`, looked up by name since libfdb_c may be loaded with the
+/// bindings of a header predating it. Only called once `require` found it exported.
+#[cfg(feature = "dlopen")]
+unsafe fn get_client_status(db: *mut FDBDatabase) -> *mut fdb_c::FDBFuture {
+    let function: unsafe extern "C" fn(*mut FDBDatabase) -> *mut fdb_c::FDBFuture =
+        fdb_c::symbol(b"fdb_database_get_client_status\0").expect("fdb_database_get_client_status is not exported");
+    function(db)
+}
+
+/// `Database::run` for any transaction source.
+pub(crate) async fn run<C, T, F>(db: &C, mut f: F) -> Result<T, crate::Error>
+where
+    C: CreateTransaction,
+    F: AsyncFnMut(&mut Transaction) -> Result<T, crate::Error>,
+{
+    let mut tx = db.create_transaction()?;
+    loop {
+        let attempt = match f(&mut tx).await {
+            Ok(value) => tx._commit().await.map(|()| value),
+            Err(e) => Err(e),
+        };
+
+        match attempt {
+            Ok(value) => return Ok(value),
+            Err(e) => tx.on_error(e).await?,
+        }
     }
 }
 
+/// `Database::read` for any transaction source.
+pub(crate) async fn read<C, T, F>(db: &C, mut f: F) -> Result<T, crate::Error>
+where
+    C: CreateTransaction,
+    F: AsyncFnMut(&ReadOnlyTransaction) -> Result<T, crate::Error>,
+{
+    let mut tx = ReadOnlyTransaction::new(db.create_transaction()?);
+    loop {
+        match f(&tx).await {
+            Ok(value) => return Ok(value),
+            Err(e) => tx.on_error(e).await?,
+        }
+    }
+}
 
 impl CreateTransaction for Database {
     fn create_transaction(&self) -> Result<Transaction, crate::Error> {
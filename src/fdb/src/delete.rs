@@ -0,0 +1,191 @@
+use std::time::{Duration, Instant};
+
+use futures::{pin_mut, StreamExt};
+
+use crate::Error;
+use crate::database::Database;
+use crate::scan::key_after;
+use crate::transaction::Transaction;
+use crate::types::{Key, KeyRange, KeySelector, Value};
+
+/// How `Database::delete_range` paces its deletion.
+#[derive(Debug, Clone)]
+pub struct DeleteRange {
+    batch_keys: i32,
+    keys_per_second: Option<u64>,
+    dry_run: bool,
+    resume_from: Option<Key>,
+}
+
+impl Default for DeleteRange {
+    /// Batches of 1000 keys, as fast as the cluster allows.
+    fn default() -> Self {
+        DeleteRange { batch_keys: 1_000, keys_per_second: None, dry_run: false, resume_from: None }
+    }
+}
+
+impl DeleteRange {
+    /// Keys read per transaction.
+    pub fn batch_keys(mut self, keys: i32) -> Self {
+        self.batch_keys = keys.max(1);
+        self
+    }
+
+    /// Reads at most this many keys per second, pausing between batches.
+    pub fn rate_limit(mut self, keys_per_second: u64) -> Self {
+        self.keys_per_second = Some(keys_per_second.max(1));
+        self
+    }
+
+    /// Only counts the keys that would be deleted, with snapshot reads.
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// Continues an earlier deletion at `DeleteProgress::checkpoint`.
+    pub fn resume_from<K: Into<Key>>(mut self, checkpoint: K) -> Self {
+        self.resume_from = Some(checkpoint.into());
+        self
+    }
+}
+
+/// What `Database::delete_range` did so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeleteProgress {
+    pub scanned: u64,
+    /// Keys deleted, or that would have been in a dry run.
+    pub deleted: u64,
+    /// Bytes of the deleted keys and values.
+    pub bytes: u64,
+    /// Where the deletion continues, to be passed to `DeleteRange::resume_from`.
+    pub checkpoint: Key,
+}
+
+/// The ranges covering each run of consecutive matching keys, so that they can be cleared with
+/// one mutation each.
+fn matching_runs(keys: &[(Key, bool)]) -> Vec<KeyRange> {
+    let mut runs = Vec::new();
+    let mut start: Option<&Key> = None;
+    for (i, (key, matches)) in keys.iter().enumerate() {
+        match (*matches, start) {
+            (true, None) => start = Some(key),
+            (false, Some(first)) => {
+                runs.push(KeyRange::new(first.clone(), key_after(&keys[i - 1].0)));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let (Some(first), Some((last, _))) = (start, keys.last()) {
+        runs.push(KeyRange::new(first.clone(), key_after(last)));
+    }
+    runs
+}
+
+/// How long to pause so that `scanned` keys take at least as long as `keys_per_second` allows.
+fn pause(scanned: u64, keys_per_second: u64, elapsed: Duration) -> Duration {
+    Duration::from_secs_f64(scanned as f64 / keys_per_second as f64).saturating_sub(elapsed)
+}
+
+impl Database {
+    /// Deletes the keys of `range` for which `predicate` holds, in batches of
+    /// `DeleteRange::batch_keys`, each read and cleared in a transaction of its own.
+    ///
+    /// Unlike `Transaction::clear_range`, this only deletes some of the keys, and spreads the load
+    /// on the storage servers over time according to `DeleteRange::rate_limit`. Consecutive
+    /// matching keys are cleared with a single range clear. `on_progress` is called after every
+    /// batch, with a checkpoint to resume from after an interruption. The predicate may see a key
+    /// more than once when a batch is retried.
+    pub async fn delete_range<R, P, F>(
+        &self,
+        range: R,
+        mut predicate: P,
+        options: DeleteRange,
+        mut on_progress: F,
+    ) -> Result<DeleteProgress, Error>
+    where
+        R: Into<KeyRange>,
+        P: FnMut(&Key, &Value) -> bool,
+        F: FnMut(&DeleteProgress),
+    {
+        let range = range.into();
+        let mut progress = DeleteProgress {
+            checkpoint: options.resume_from.clone().filter(|c| *c > range.start).unwrap_or(range.start.clone()),
+            ..DeleteProgress::default()
+        };
+        let started = Instant::now();
+
+        while progress.checkpoint < range.end {
+            let (scanned, deleted, bytes, last) = self.run(async |tx: &mut Transaction| {
+                let mut batch = Vec::new();
+                {
+                    let start = KeySelector::first_greater_or_equal(progress.checkpoint.clone());
+                    let end = KeySelector::first_greater_or_equal(range.end.clone());
+                    let kvs = match options.dry_run {
+                        true => tx.snapshot().get_range(start, end, Some(options.batch_keys), None, false).boxed_local(),
+                        // Reading with conflicts makes keys inserted meanwhile abort the batch
+                        // instead of being cleared unseen
+                        false => tx.get_range(start, end, Some(options.batch_keys), None, false).boxed_local(),
+                    };
+                    pin_mut!(kvs);
+
+                    while let Some(kv) = kvs.next().await {
+                        let (key, value) = kv?;
+                        let matches = predicate(&key, &value);
+                        batch.push((key, value.len(), matches));
+                    }
+                }
+
+                let matched = batch.iter().filter(|(_, _, m)| *m);
+                let deleted = matched.clone().count() as u64;
+                let bytes: u64 = matched.map(|(k, len, _)| (k.len() + len) as u64).sum();
+                if !options.dry_run {
+                    let keys: Vec<_> = batch.iter().map(|(k, _, m)| (k.clone(), *m)).collect();
+                    for run in matching_runs(&keys) {
+                        tx.clear_range(run).await;
+                    }
+                }
+                Ok((batch.len() as u64, deleted, bytes, batch.last().map(|(k, _, _)| k.clone())))
+            }).await?;
+
+            progress.scanned += scanned;
+            progress.deleted += deleted;
+            progress.bytes += bytes;
+            progress.checkpoint = match last {
+                Some(last) if scanned == options.batch_keys as u64 => key_after(&last),
+                _ => range.end.clone(),
+            };
+            on_progress(&progress);
+
+            if let Some(keys_per_second) = options.keys_per_second {
+                crate::runtime::sleep(pause(progress.scanned, keys_per_second, started.elapsed())).await;
+            }
+        }
+        Ok(progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[(&str, bool)]) -> Vec<(Key, bool)> {
+        keys.iter().map(|(k, m)| (Key::from(*k), *m)).collect()
+    }
+
+    #[test]
+    fn runs_of_matching_keys() {
+        let runs = matching_runs(&keys(&[("a", true), ("b", true), ("c", false), ("d", true)]));
+        assert_eq!(runs, vec![KeyRange::new("a", key_after(b"b")), KeyRange::new("d", key_after(b"d"))]);
+
+        assert!(matching_runs(&keys(&[("a", false)])).is_empty());
+        assert!(matching_runs(&[]).is_empty());
+    }
+
+    #[test]
+    fn pacing() {
+        assert_eq!(pause(1_000, 500, Duration::from_millis(500)), Duration::from_millis(1_500));
+        assert_eq!(pause(1_000, 500, Duration::from_secs(3)), Duration::ZERO);
+    }
+}
@@ -4,8 +4,10 @@ use std::marker::PhantomData;
 use std::os::raw::c_char;
 use std::pin::Pin;
 use std::ptr;
-use std::task::{Context, Poll, Waker};
+use std::sync::Arc;
+use std::task::{Context, Poll};
 
+use futures::task::AtomicWaker;
 use log::error;
 
 use fdb_c::FDB_future;
@@ -17,22 +19,43 @@ pub trait FDBResult: Sized {
     fn from_future(future: *mut FDB_future) -> Result<Self, Error>;
 }
 
+/// Owns an `FDB_future` until it resolves. Dropping an unresolved future cancels and destroys it,
+/// which for a watch also unregisters the watch from the cluster.
 pub struct FDBFuture<T> {
+    /// Null once the future has resolved and been destroyed
     future: *mut FDB_future,
+    /// Woken by the callback, which is registered on the first pending poll only
+    waker: Option<Arc<AtomicWaker>>,
     target: PhantomData<T>,
 }
 
 unsafe impl<T> Send for FDBFuture<T> {}
 
+impl<T> Unpin for FDBFuture<T> {}
+
 
 impl<T> From<*mut FDB_future> for FDBFuture<T> {
     fn from(value: *mut FDB_future) -> Self {
         FDBFuture {
             future: value,
+            waker: None,
             target: PhantomData,
         }
     }
 }
+
+impl<T> Drop for FDBFuture<T> {
+    fn drop(&mut self) {
+        if self.future.is_null() {
+            return;
+        }
+        // Cancelling fires the callback with `operation_cancelled`, which releases its waker
+        unsafe {
+            fdb_c::fdb_future_cancel(self.future);
+            fdb_c::fdb_future_destroy(self.future);
+        }
+    }
+}
 //
 // impl<T> Deref for FDBFuture<T> {
 //     type Target = *mut FDB_future;
@@ -51,36 +74,49 @@ impl<T> From<*mut FDB_future> for FDBFuture<T> {
 impl<T: FDBResult> Future for FDBFuture<T> {
     type Output = Result<T, Error>;
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let future = self.future;
+        let this = self.get_mut();
+        let future = this.future;
+        assert!(!future.is_null(), "FDBFuture polled after completion");
+
         let ready = unsafe { fdb_c::fdb_future_is_ready(future) };
 
         if ready == 0 {
             unsafe extern "C" fn future_ready_callback(_future: *mut FDB_future, callback_parameter: *mut std::os::raw::c_void,
             ) {
-                let waker: Box<Waker> = Box::from_raw(callback_parameter.cast());
-                waker.wake_by_ref()
+                let waker: Arc<AtomicWaker> = Arc::from_raw(callback_parameter.cast());
+                waker.wake()
             }
 
-            let waker = Box::new(cx.waker().clone());
-            let waker_ptr = Box::into_raw(waker).cast();
-            unsafe { fdb_c::fdb_future_set_callback(future, Some(future_ready_callback), waker_ptr as *mut _) };
+            match &this.waker {
+                Some(waker) => waker.register(cx.waker()),
+                None => {
+                    let waker = Arc::new(AtomicWaker::new());
+                    waker.register(cx.waker());
+                    // The callback owns one reference, so the waker outlives this future if it is
+                    // dropped before the callback fires
+                    let waker_ptr = Arc::into_raw(waker.clone());
+                    this.waker = Some(waker);
+                    unsafe { fdb_c::fdb_future_set_callback(future, Some(future_ready_callback), waker_ptr as *mut _) };
+                }
+            }
             return Poll::Pending;
         }
 
         let error = unsafe { fdb_c::fdb_future_get_error(future) };
 
-        if error != 0 {
+        let result = if error != 0 {
             error!("{error}");
-            return Poll::Ready(Err(FdbErrorCode(error).into()));
-        }
-
-        let result = Poll::Ready(T::from_future(future));
+            Err(FdbErrorCode(error).into())
+        } else {
+            T::from_future(future)
+        };
 
         // The memory referenced by the result is owned by the FDBFuture object and will be valid until fdb_future_destroy(future) is called.
         // All the protocol implementing FDBResult must have ownership of their content at this point.
         unsafe { fdb_c::fdb_future_destroy(future) }
+        this.future = ptr::null_mut();
 
-        result
+        Poll::Ready(result)
     }
 }
 
@@ -182,7 +218,7 @@ impl FDBResult for Value {
     }
 }
 
-#[cfg(any(feature = "730", feature = "710", feature = "700"))]
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
 impl FDBResult for KeyArray {
 
     fn from_future(future: *mut FDB_future) -> Result<Self, Error> {
@@ -261,8 +297,6 @@ impl FDBResult for KeyValueArray {
         assert_ne!(count, i32::MIN);
         assert_ne!(more_remaining, i32::MIN);
 
-        // TODO: Was mit more_remaining anstellen?
-
         let kvs = from_raw_fdb_slice(kvs, count as usize);
         let kvs: Vec<(Key, Value)> = kvs
             .iter()
@@ -274,6 +308,6 @@ impl FDBResult for KeyValueArray {
             })
             .collect();
 
-        Ok(kvs.into())
+        Ok(KeyValueArray(kvs, more_remaining != 0))
     }
 }
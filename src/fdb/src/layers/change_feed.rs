@@ -0,0 +1,362 @@
+use std::time::Duration;
+
+use async_stream::try_stream;
+use futures::{pin_mut, Stream, StreamExt};
+
+use crate::{Database, Error, Transaction};
+use crate::layers::{get_optional, VERSIONS_PER_SECOND};
+use crate::types::{Key, KeyRange, KeySelector, Subspace, Value};
+
+/// Number of log keys a consumer reads per transaction.
+const BATCH_SIZE: i32 = 1_000;
+
+/// Largest part of a change stored under a single log key, the limit of values. The key and
+/// values of a change are split across as many parts as needed.
+const PART_BYTES: usize = 100_000;
+
+/// Position in the log of a `ChangeFeed`: the versionstamp of the transaction that made a change,
+/// followed by the 2 byte number of the change within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cursor([u8; 12]);
+
+impl Cursor {
+    /// Before the first change.
+    pub const BEGINNING: Cursor = Cursor([0; 12]);
+
+    pub fn from_bytes(bytes: [u8; 12]) -> Self {
+        Cursor(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 12] {
+        &self.0
+    }
+
+    /// Commit version of the transaction that made the change.
+    pub fn version(&self) -> i64 {
+        i64::from_be_bytes(self.0[..8].try_into().unwrap())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOp {
+    Set,
+    Clear,
+}
+
+/// A write recorded by a `ChangeFeed`, with the value of the key before and after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub op: ChangeOp,
+    pub key: Key,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl Change {
+    fn encode(&self) -> Vec<u8> {
+        fn put(bytes: &mut Vec<u8>, value: Option<&[u8]>) {
+            bytes.push(value.is_some() as u8);
+            let value = value.unwrap_or_default();
+            bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(value);
+        }
+
+        let mut bytes = vec![self.op as u8];
+        put(&mut bytes, Some(&self.key));
+        put(&mut bytes, self.old.as_deref());
+        put(&mut bytes, self.new.as_deref());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Change> {
+        fn take<'a>(bytes: &mut &'a [u8]) -> Option<Option<&'a [u8]>> {
+            let (&present, rest) = bytes.split_first()?;
+            let len = u32::from_be_bytes(rest.get(..4)?.try_into().unwrap()) as usize;
+            let value = rest.get(4..4 + len)?;
+            *bytes = &rest[4 + len..];
+            Some((present != 0).then_some(value))
+        }
+
+        let (&op, mut rest) = bytes.split_first()?;
+        let op = match op {
+            0 => ChangeOp::Set,
+            1 => ChangeOp::Clear,
+            _ => return None,
+        };
+        let key = take(&mut rest)??;
+        let old = take(&mut rest)?;
+        let new = take(&mut rest)?;
+        Some(Change { op, key: key.into(), old: old.map(Value::from), new: new.map(Value::from) })
+    }
+}
+
+/// Joins the parts of the changes, which are adjacent and share the position. Unless `complete`,
+/// the last change may continue beyond the parts read and is left for the next batch.
+fn assemble(parts: &[(Cursor, Value)], complete: bool) -> Vec<(Cursor, Change)> {
+    let mut records: Vec<(Cursor, Vec<u8>)> = Vec::new();
+    for (position, part) in parts {
+        match records.last_mut() {
+            Some((last, record)) if last == position => record.extend_from_slice(part),
+            _ => records.push((*position, part.to_vec())),
+        }
+    }
+    if !complete && records.len() > 1 {
+        records.pop();
+    }
+    records.into_iter()
+        .filter_map(|(position, record)| Some((position, Change::decode(&record)?)))
+        .collect()
+}
+
+/// A log of the changes made through `ChangeFeed::wrap`, for other processes to follow.
+///
+/// Every change is appended under the versionstamp of its transaction, so writers never conflict
+/// on the log and it is ordered like the transactions committed. Consumers read it from a `Cursor`
+/// as a stream that waits for new changes, and store how far they got with `checkpoint`. Changes
+/// after the checkpoint are delivered again when a consumer restarts, so processing them must be
+/// idempotent.
+///
+/// The log grows until it is trimmed with `trim`, `trim_consumed` or `trim_older_than`.
+#[derive(Debug, Clone)]
+pub struct ChangeFeed {
+    subspace: Subspace,
+}
+
+impl ChangeFeed {
+    pub fn new<S: Into<Subspace>>(subspace: S) -> Self {
+        ChangeFeed { subspace: subspace.into() }
+    }
+
+    fn log(&self) -> Subspace {
+        self.subspace.subspace("log/")
+    }
+
+    fn checkpoints(&self) -> Subspace {
+        self.subspace.subspace("checkpoints/")
+    }
+
+    /// Changed by every appended change, so that consumers at the end of the log can watch it.
+    fn signal_key(&self) -> Key {
+        self.subspace.key("signal")
+    }
+
+    /// Writes through `tx`, recording every change in the log.
+    pub fn wrap<'a>(&'a self, tx: &'a Transaction) -> ChangeTransaction<'a> {
+        ChangeTransaction { feed: self, tx }
+    }
+
+    /// First log key after all parts of the change at `cursor`.
+    fn after(&self, cursor: Cursor) -> Key {
+        self.log().key([cursor.0.as_slice(), &[0xff]].concat())
+    }
+
+    /// The changes after `cursor`, followed by every change appended later.
+    pub fn changes<'a>(&'a self, db: &'a Database, cursor: Cursor) -> impl Stream<Item=Result<(Cursor, Change), Error>> + 'a {
+        try_stream! {
+            let mut cursor = cursor;
+            loop {
+                let (batch, watch) = db.run(async |tx: &mut Transaction| {
+                    let batch = self.read(tx, cursor).await?;
+                    let watch = batch.is_empty().then(|| tx.watch(self.signal_key()));
+                    Ok((batch, watch))
+                }).await?;
+
+                for (position, change) in batch {
+                    cursor = position;
+                    yield (position, change);
+                }
+                if let Some(watch) = watch {
+                    watch.await?;
+                }
+            }
+        }
+    }
+
+    /// Like `changes`, starting after the checkpoint of `consumer`.
+    pub async fn subscribe<'a>(
+        &'a self,
+        db: &'a Database,
+        consumer: &[u8],
+    ) -> Result<impl Stream<Item=Result<(Cursor, Change), Error>> + 'a, Error> {
+        let cursor = db.run(async |tx: &mut Transaction| self.checkpoint_of(tx, consumer).await).await?;
+        Ok(self.changes(db, cursor))
+    }
+
+    /// The next batch of changes after `cursor`.
+    async fn read(&self, tx: &mut Transaction, cursor: Cursor) -> Result<Vec<(Cursor, Change)>, Error> {
+        let log = self.log();
+        let parts = tx.snapshot().get_range(
+            KeySelector::first_greater_or_equal(self.after(cursor)),
+            KeySelector::first_greater_or_equal(log.range().end),
+            Some(BATCH_SIZE),
+            None,
+            false,
+        );
+        pin_mut!(parts);
+
+        let mut batch = Vec::new();
+        while let Some(kv) = parts.next().await {
+            let (key, value) = kv?;
+            if let Some(position) = log.strip(&key).and_then(|p| p.get(..12)?.try_into().ok()).map(Cursor) {
+                batch.push((position, value));
+            }
+        }
+        Ok(assemble(&batch, batch.len() < BATCH_SIZE as usize))
+    }
+
+    /// Stores that `consumer` processed the changes up to `cursor`. Committing it in the
+    /// transaction that applies their effects makes processing exactly once.
+    pub async fn checkpoint(&self, tx: &Transaction, consumer: &[u8], cursor: Cursor) {
+        tx.set(self.checkpoints().key(consumer), cursor.0.as_slice()).await
+    }
+
+    /// How far `consumer` got, `Cursor::BEGINNING` if it never stored a checkpoint.
+    pub async fn checkpoint_of(&self, tx: &Transaction, consumer: &[u8]) -> Result<Cursor, Error> {
+        let checkpoint = get_optional(tx, self.checkpoints().key(consumer)).await?;
+        Ok(checkpoint.and_then(|c| c.as_ref().try_into().ok()).map_or(Cursor::BEGINNING, Cursor))
+    }
+
+    /// Removes the changes up to and including `cursor`.
+    pub async fn trim(&self, tx: &Transaction, cursor: Cursor) {
+        tx.clear_range(KeyRange::new(self.log().range().start, self.after(cursor))).await
+    }
+
+    /// Removes the changes every consumer with a checkpoint has processed.
+    pub async fn trim_consumed(&self, db: &Database) -> Result<(), Error> {
+        db.run(async |tx: &mut Transaction| {
+            let mut oldest: Option<Cursor> = None;
+            {
+                let range = self.checkpoints().range();
+                let checkpoints = tx.get_range(
+                    KeySelector::first_greater_or_equal(range.start),
+                    KeySelector::first_greater_or_equal(range.end),
+                    None,
+                    None,
+                    false,
+                );
+                pin_mut!(checkpoints);
+
+                while let Some(kv) = checkpoints.next().await {
+                    let (_, value) = kv?;
+                    let cursor = value.as_ref().try_into().map_or(Cursor::BEGINNING, Cursor);
+                    oldest = Some(oldest.map_or(cursor, |o| o.min(cursor)));
+                }
+            }
+
+            if let Some(oldest) = oldest {
+                self.trim(tx, oldest).await;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Removes the changes committed more than `age` ago, measured in database versions.
+    pub async fn trim_older_than(&self, db: &Database, age: Duration) -> Result<(), Error> {
+        db.run(async |tx: &mut Transaction| {
+            let age = (age.as_micros() as i64).saturating_mul(VERSIONS_PER_SECOND) / 1_000_000;
+            let before = tx.get_read_version().await?.saturating_sub(age).max(0);
+            tx.clear_range(KeyRange::new(self.log().range().start, self.log().key(before.to_be_bytes()))).await;
+            Ok(())
+        }).await
+    }
+}
+
+/// A transaction whose writes are recorded in a `ChangeFeed`.
+///
+/// Recording the old value reads the key, so every write conflicts with concurrent writes of the
+/// same key.
+pub struct ChangeTransaction<'a> {
+    feed: &'a ChangeFeed,
+    tx: &'a Transaction,
+}
+
+impl ChangeTransaction<'_> {
+    /// The wrapped transaction, for reads and for writes that are not to be recorded.
+    pub fn transaction(&self) -> &Transaction {
+        self.tx
+    }
+
+    pub async fn set<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<(), Error> {
+        let key = key.into();
+        let value = value.into();
+        let old = get_optional(self.tx, key.clone()).await?;
+        self.tx.set(key.clone(), value.clone()).await;
+        self.append(Change { op: ChangeOp::Set, key, old, new: Some(value) }).await
+    }
+
+    /// Clears the key, recording a change only if it had a value.
+    pub async fn clear<K: Into<Key>>(&self, key: K) -> Result<(), Error> {
+        let key = key.into();
+        if let Some(old) = get_optional(self.tx, key.clone()).await? {
+            self.tx.clear(key.clone()).await;
+            self.append(Change { op: ChangeOp::Clear, key, old: Some(old), new: None }).await?;
+        }
+        Ok(())
+    }
+
+    /// Appends the change under the versionstamp of the transaction, split into parts that each
+    /// fit into a value.
+    async fn append(&self, change: Change) -> Result<(), Error> {
+        // Numbered per transaction, so that changes through several wrappers stay distinct
+        let sequence = self.tx.next_sequence()?;
+
+        // The versionstamp is written over the placeholder at the offset in the last 4 bytes
+        let log = self.feed.log();
+        let offset = log.prefix().len() as u32;
+        for (part, bytes) in change.encode().chunks(PART_BYTES).enumerate() {
+            let key = [log.prefix().as_slice(), &[0; 10], &sequence.to_be_bytes(), &[part as u8], &offset.to_le_bytes()].concat();
+            self.tx.atomic_set_versionstamped_key(key, bytes.to_vec().into()).await;
+        }
+        self.tx.atomic_add_i64(self.feed.signal_key(), 1).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn change_roundtrip() {
+        let change = Change { op: ChangeOp::Set, key: Key::from("k"), old: None, new: Some(Value::from("v")) };
+        assert_eq!(Change::decode(&change.encode()), Some(change));
+
+        let change = Change { op: ChangeOp::Clear, key: Key::from("k"), old: Some(Value::from("")), new: None };
+        assert_eq!(Change::decode(&change.encode()), Some(change));
+
+        assert_eq!(Change::decode(&[0, 1, 0, 0, 0, 9, b'k']), None);
+    }
+
+    #[test]
+    fn large_changes_are_split() {
+        let change = Change {
+            op: ChangeOp::Set,
+            key: Key::from("k"),
+            old: Some(Value::from(vec![1; PART_BYTES])),
+            new: Some(Value::from(vec![2; PART_BYTES])),
+        };
+        let small = Change { op: ChangeOp::Clear, key: Key::from("s"), old: Some(Value::from("v")), new: None };
+        let (first, second) = (Cursor([1; 12]), Cursor([2; 12]));
+
+        let mut parts: Vec<_> = change.encode().chunks(PART_BYTES).map(|p| (first, Value::from(p.to_vec()))).collect();
+        assert_eq!(parts.len(), 3);
+        parts.push((second, Value::from(small.encode())));
+
+        assert_eq!(assemble(&parts, true), vec![(first, change.clone()), (second, small)]);
+        assert_eq!(assemble(&parts[..2], false), vec![]);
+        assert_eq!(assemble(&parts[..3], false), vec![(first, change)]);
+    }
+
+    #[test]
+    fn cursors_are_ordered_by_version() {
+        let mut early = [0; 12];
+        early[..8].copy_from_slice(&5i64.to_be_bytes());
+        early[11] = 9;
+        let mut late = [0; 12];
+        late[..8].copy_from_slice(&6i64.to_be_bytes());
+
+        assert!(Cursor::BEGINNING < Cursor(early));
+        assert!(Cursor(early) < Cursor(late));
+        assert_eq!(Cursor(late).version(), 6);
+    }
+}
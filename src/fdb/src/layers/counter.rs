@@ -0,0 +1,166 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use futures::{pin_mut, StreamExt};
+use rand::Rng;
+
+use crate::{Database, Error, ReadTransaction, Transaction};
+use crate::layers::decode_i64;
+use crate::types::{Key, KeySelector, Subspace};
+
+/// Values a `Counter` can count. They are stored as 64-bit little-endian integers, so that shards
+/// can be incremented with atomic adds.
+pub trait CounterValue: Copy {
+    /// The value as an integer number of `1 / scale` units.
+    fn to_fixed(self, scale: i64) -> i64;
+    fn from_fixed(fixed: i64, scale: i64) -> Self;
+}
+
+impl CounterValue for i64 {
+    fn to_fixed(self, _scale: i64) -> i64 {
+        self
+    }
+
+    fn from_fixed(fixed: i64, _scale: i64) -> Self {
+        fixed
+    }
+}
+
+impl CounterValue for f64 {
+    fn to_fixed(self, scale: i64) -> i64 {
+        (self * scale as f64).round() as i64
+    }
+
+    fn from_fixed(fixed: i64, scale: i64) -> Self {
+        fixed as f64 / scale as f64
+    }
+}
+
+/// A counter spreading its increments over several shard keys, so that concurrent increments do
+/// not all write the same key.
+///
+/// `add` atomically adds to a randomly chosen shard and never conflicts. `get` sums the shards
+/// with a snapshot read, which does not conflict with increments either, but reads up to `shards`
+/// keys. `coalesce` folds the shards back into one to keep reads cheap.
+///
+/// `f64` counters are stored as fixed-point integers with a given number of decimals, so that they
+/// can be added atomically as well.
+#[derive(Debug, Clone)]
+pub struct Counter<T = i64> {
+    subspace: Subspace,
+    shards: u16,
+    scale: i64,
+    value: PhantomData<T>,
+}
+
+impl Counter<i64> {
+    pub fn new<S: Into<Subspace>>(subspace: S, shards: u16) -> Self {
+        Counter {
+            subspace: subspace.into(),
+            shards: shards.max(1),
+            scale: 1,
+            value: PhantomData,
+        }
+    }
+}
+
+/// Most decimals of a fixed-point counter, the largest power of ten fitting into an `i64`.
+const MAX_DECIMALS: u32 = 18;
+
+impl Counter<f64> {
+    /// A counter of `f64` values, rounded to `decimals` decimal places on every `add`. At most 18
+    /// decimals are kept.
+    pub fn fixed_point<S: Into<Subspace>>(subspace: S, shards: u16, decimals: u32) -> Self {
+        Counter {
+            subspace: subspace.into(),
+            shards: shards.max(1),
+            scale: 10i64.pow(decimals.min(MAX_DECIMALS)),
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T: CounterValue> Counter<T> {
+    fn shard(&self, index: u16) -> Key {
+        self.subspace.key(index.to_be_bytes())
+    }
+
+    /// Adds `amount` to a random shard.
+    pub async fn add(&self, tx: &Transaction, amount: T) {
+        let index = rand::thread_rng().gen_range(0..self.shards);
+        tx.atomic_add_i64(self.shard(index), amount.to_fixed(self.scale)).await
+    }
+
+    /// The value of the counter, read without conflicting with concurrent increments.
+    pub async fn get(&self, tx: &Transaction) -> Result<T, Error> {
+        let sum = self.sum(&tx.snapshot()).await?;
+        Ok(T::from_fixed(sum, self.scale))
+    }
+
+    async fn sum<R: ReadTransaction>(&self, tx: &R) -> Result<i64, Error> {
+        let range = self.subspace.range();
+        let shards = tx.get_range(
+            KeySelector::first_greater_or_equal(range.start),
+            KeySelector::first_greater_or_equal(range.end),
+            None,
+            None,
+            false,
+        );
+        pin_mut!(shards);
+
+        let mut sum = 0i64;
+        while let Some(kv) = shards.next().await {
+            let (_, value) = kv?;
+            sum = sum.wrapping_add(decode_i64(&value));
+        }
+        Ok(sum)
+    }
+
+    /// Folds all shards into the first one. Conflicts with concurrent increments, in which case it
+    /// is retried.
+    pub async fn coalesce(&self, db: &Database) -> Result<(), Error> {
+        db.run(async |tx: &mut Transaction| {
+            let sum = self.sum(tx).await?;
+            tx.clear_range(self.subspace.range()).await;
+            tx.set(self.shard(0), sum.to_le_bytes().as_slice()).await;
+            Ok(())
+        }).await
+    }
+
+    /// Runs `coalesce` every `interval`, until it fails. Meant to run in the background next to
+    /// the application, e.g. by `select`ing on both.
+    pub async fn coalesce_every(&self, db: &Database, interval: Duration) -> Result<(), Error> {
+        loop {
+            crate::runtime::sleep(interval).await;
+            self.coalesce(db).await?;
+        }
+    }
+
+    /// Resets the counter to zero.
+    pub async fn clear(&self, tx: &Transaction) {
+        tx.clear_range(self.subspace.range()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_point() {
+        let counter = Counter::fixed_point("c/", 4, 3);
+        assert_eq!(1.2345f64.to_fixed(counter.scale), 1235);
+        assert_eq!(f64::from_fixed(-1500, counter.scale), -1.5);
+        assert_eq!(42i64.to_fixed(1), 42);
+        assert_eq!(Counter::fixed_point("c/", 4, 40).scale, 10i64.pow(18));
+    }
+
+    #[test]
+    fn shards_are_in_subspace() {
+        let counter = Counter::new("c/", 300);
+        let range = counter.subspace.range();
+        assert!(range.contains(counter.shard(0)));
+        assert!(range.contains(counter.shard(299)));
+        assert_eq!(Counter::new("c/", 0).shards, 1);
+    }
+}
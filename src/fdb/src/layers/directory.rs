@@ -0,0 +1,389 @@
+use futures::TryStreamExt;
+use rand::Rng;
+
+use crate::{Error, Transaction};
+use crate::layers::{decode_i64, get_optional};
+use crate::types::{Key, KeyRange, KeySelector, Subspace};
+
+/// Prefix of the nodes of the directory tree, the one of the other bindings.
+const NODES: &[u8] = b"\xfe";
+
+/// Version of the directory layer format written on the first change, major, minor and patch.
+const VERSION: [u32; 3] = [1, 0, 0];
+
+/// Layer of directories holding a directory layer of their own.
+const PARTITION: &[u8] = b"partition";
+
+/// Tuple type codes of the elements the directory layer stores.
+const BYTES: u8 = 0x01;
+const STRING: u8 = 0x02;
+const INT_ZERO: u8 = 0x14;
+
+/// A byte string or unicode string tuple element.
+fn pack(code: u8, bytes: &[u8]) -> Vec<u8> {
+    let mut packed = vec![code];
+    for &b in bytes {
+        packed.push(b);
+        if b == 0x00 {
+            packed.push(0xff);
+        }
+    }
+    packed.push(0x00);
+    packed
+}
+
+/// A non-negative integer tuple element.
+fn pack_int(n: u64) -> Vec<u8> {
+    let bytes = n.to_be_bytes();
+    let len = 8 - n.leading_zeros() as usize / 8;
+    [&[INT_ZERO + len as u8], &bytes[8 - len..]].concat()
+}
+
+/// Decodes a leading byte string or unicode string element, `None` if `packed` starts otherwise.
+fn unpack(code: u8, packed: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut rest = packed.strip_prefix(&[code])?.iter();
+    loop {
+        match *rest.next()? {
+            0x00 if rest.as_slice().first() == Some(&0xff) => {
+                rest.next();
+                bytes.push(0x00);
+            }
+            0x00 => return Some(bytes),
+            b => bytes.push(b),
+        }
+    }
+}
+
+fn unpack_int(packed: &[u8]) -> Option<u64> {
+    let len = packed.first()?.checked_sub(INT_ZERO).filter(|len| *len <= 8)? as usize;
+    let mut bytes = [0u8; 8];
+    bytes[8 - len..].copy_from_slice(packed.get(1..1 + len)?);
+    Some(u64::from_be_bytes(bytes))
+}
+
+/// Candidates for new prefixes per window of the allocator, growing with the prefixes in use.
+fn window_size(start: u64) -> u64 {
+    match start {
+        0..=254 => 64,
+        255..=65534 => 1024,
+        _ => 8192,
+    }
+}
+
+fn starting_with(prefix: &[u8]) -> Result<KeyRange, Error> {
+    let prefix = Key::from(prefix);
+    let end = prefix.strinc().ok_or_else(|| Error::InvalidPrefix(prefix.clone()))?;
+    Ok(KeyRange::new(prefix, end))
+}
+
+fn display<S: AsRef<str>>(path: &[S]) -> String {
+    path.iter().map(|name| format!("/{}", name.as_ref())).collect()
+}
+
+/// The directory layer of the other FoundationDB bindings: a tree of named directories, each
+/// assigned a short key prefix for its contents.
+///
+/// Directories are stored in the format of the other bindings, so that they can be shared with
+/// them, with prefixes allocated by the same high contention allocator. Only the operations of
+/// `fdb dir` are implemented: directories with layers other than the default are treated as plain
+/// directories, and moving directories or partitions are not supported.
+#[derive(Debug, Clone)]
+pub struct DirectoryLayer {
+    nodes: Subspace,
+}
+
+impl Default for DirectoryLayer {
+    fn default() -> Self {
+        DirectoryLayer { nodes: Subspace::new(NODES) }
+    }
+}
+
+impl DirectoryLayer {
+    /// Names of the subdirectories of the directory at `path`, the root if empty.
+    pub async fn list<S: AsRef<str>>(&self, tx: &Transaction, path: &[S]) -> Result<Vec<String>, Error> {
+        let Some(node) = self.find(tx, path).await? else {
+            return Err(Error::Directory(format!("{} does not exist", display(path))));
+        };
+        let subdirs = subdirs(&node);
+        let range = subdirs.range();
+        let kvs: Vec<_> = tx.get_range(
+            KeySelector::first_greater_or_equal(range.start),
+            KeySelector::first_greater_or_equal(range.end),
+            None,
+            None,
+            false,
+        ).try_collect().await?;
+        Ok(kvs.iter()
+            .filter_map(|(key, _)| unpack(STRING, subdirs.strip(key)?))
+            .filter_map(|name| String::from_utf8(name).ok())
+            .collect())
+    }
+
+    /// The subspace of the contents of the directory at `path`, `None` if it does not exist.
+    pub async fn open<S: AsRef<str>>(&self, tx: &Transaction, path: &[S]) -> Result<Option<Subspace>, Error> {
+        if path.is_empty() {
+            return Err(Error::Directory("the root directory cannot be opened".to_string()));
+        }
+        let node = self.find(tx, path).await?;
+        Ok(node.map(|node| Subspace::new(self.prefix(&node))))
+    }
+
+    /// Creates the directory at `path` and its missing parents, returning the subspace of its
+    /// contents. Fails if the directory exists already.
+    pub async fn create<S: AsRef<str>>(&self, tx: &Transaction, path: &[S]) -> Result<Subspace, Error> {
+        if path.is_empty() {
+            return Err(Error::Directory("the root directory exists already".to_string()));
+        }
+        self.check_version(tx, true).await?;
+
+        let mut node = self.root();
+        for (i, name) in path.iter().enumerate() {
+            let prefix = match self.child(tx, &node, name.as_ref()).await? {
+                Some(_) if i == path.len() - 1 => {
+                    return Err(Error::Directory(format!("{} exists already", display(path))));
+                }
+                Some(prefix) => prefix.to_vec(),
+                None => {
+                    let prefix = self.allocate(tx).await?;
+                    if !self.is_prefix_free(tx, &prefix).await? {
+                        return Err(Error::Directory(format!("the allocated prefix {prefix:?} is in use already")));
+                    }
+                    tx.set(subdirs(&node).key(pack(STRING, name.as_ref().as_bytes())), prefix.to_vec()).await;
+                    tx.set(self.node(&prefix).key(pack(BYTES, b"layer")), b"".as_slice()).await;
+                    prefix.to_vec()
+                }
+            };
+            node = self.node(&prefix);
+        }
+        Ok(Subspace::new(self.prefix(&node)))
+    }
+
+    /// Removes the directory at `path` with its subdirectories and all their contents, returning
+    /// whether it existed.
+    pub async fn remove<S: AsRef<str>>(&self, tx: &Transaction, path: &[S]) -> Result<bool, Error> {
+        let Some((name, parents)) = path.split_last() else {
+            return Err(Error::Directory("the root directory cannot be removed".to_string()));
+        };
+        self.check_version(tx, true).await?;
+
+        let Some(parent) = self.find(tx, parents).await? else {
+            return Ok(false);
+        };
+        let Some(prefix) = self.child(tx, &parent, name.as_ref()).await? else {
+            return Ok(false);
+        };
+        self.remove_recursive(tx, &prefix).await?;
+        tx.clear(subdirs(&parent).key(pack(STRING, name.as_ref().as_bytes()))).await;
+        Ok(true)
+    }
+
+    fn node(&self, prefix: &[u8]) -> Subspace {
+        self.nodes.subspace(pack(BYTES, prefix))
+    }
+
+    fn root(&self) -> Subspace {
+        self.node(self.nodes.prefix())
+    }
+
+    /// The prefix of the contents of the directory at `node`.
+    fn prefix(&self, node: &Subspace) -> Vec<u8> {
+        self.nodes.strip(node.prefix()).and_then(|packed| unpack(BYTES, packed)).unwrap_or_default()
+    }
+
+    /// The node of the directory at `path`, `None` if it does not exist.
+    async fn find<S: AsRef<str>>(&self, tx: &Transaction, path: &[S]) -> Result<Option<Subspace>, Error> {
+        self.check_version(tx, false).await?;
+        let mut node = self.root();
+        for name in path {
+            match self.child(tx, &node, name.as_ref()).await? {
+                Some(prefix) => node = self.node(&prefix),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(node))
+    }
+
+    /// The prefix of the subdirectory `name` of `node`, failing for partitions.
+    async fn child(&self, tx: &Transaction, node: &Subspace, name: &str) -> Result<Option<Key>, Error> {
+        let Some(prefix) = get_optional(tx, subdirs(node).key(pack(STRING, name.as_bytes()))).await? else {
+            return Ok(None);
+        };
+        let layer = get_optional(tx, self.node(&prefix).key(pack(BYTES, b"layer"))).await?;
+        if layer.is_some_and(|layer| &*layer == PARTITION) {
+            return Err(Error::Directory(format!("{name} is a partition, which is not supported")));
+        }
+        Ok(Some(prefix.to_vec().into()))
+    }
+
+    /// Fails for versions of the format this layer does not know, and writes the version with the
+    /// first change.
+    async fn check_version(&self, tx: &Transaction, write: bool) -> Result<(), Error> {
+        let key = self.root().key(pack(BYTES, b"version"));
+        match get_optional(tx, key.clone()).await? {
+            None if write => {
+                let version: Vec<u8> = VERSION.iter().flat_map(|v| v.to_le_bytes()).collect();
+                tx.set(key, version).await;
+            }
+            None => {}
+            Some(version) => {
+                let [major, minor] = [0, 4].map(|at| {
+                    version.get(at..at + 4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                });
+                if major > VERSION[0] || (write && minor > VERSION[1]) {
+                    return Err(Error::Directory(format!("the directory layer version {major}.{minor} is not supported")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Allocates a prefix like the high contention allocator of the other bindings: a counter per
+    /// window of candidate prefixes, which advances to a larger window once half of it is taken,
+    /// and a random candidate of the current window.
+    async fn allocate(&self, tx: &Transaction) -> Result<Vec<u8>, Error> {
+        let hca = self.root().subspace(pack(BYTES, b"hca"));
+        let (counters, recent) = (hca.subspace(pack_int(0)), hca.subspace(pack_int(1)));
+
+        loop {
+            let mut start = latest_window(tx, &counters).await?;
+            let mut advanced = false;
+            let window = loop {
+                if advanced {
+                    tx.clear_range(KeyRange::new(counters.prefix().clone(), counters.key(pack_int(start)))).await;
+                    tx.clear_range(KeyRange::new(recent.prefix().clone(), recent.key(pack_int(start)))).await;
+                }
+                tx.atomic_add_i64(counters.key(pack_int(start)), 1).await;
+                let count = match tx.snapshot().get(counters.key(pack_int(start))).await {
+                    Ok(count) => decode_i64(&count) as u64,
+                    Err(Error::KeyNotFound) => 0,
+                    Err(e) => return Err(e),
+                };
+
+                let window = window_size(start);
+                if count * 2 < window {
+                    break window;
+                }
+                start += window;
+                advanced = true;
+            };
+
+            loop {
+                let candidate = start + rand::thread_rng().gen_range(0..window);
+                let latest = latest_window(tx, &counters).await?;
+                let taken = get_optional(tx, recent.key(pack_int(candidate))).await?.is_some();
+                tx.set(recent.key(pack_int(candidate)), b"".as_slice()).await;
+
+                // Another transaction advanced the window, start over in the new one
+                if latest > start {
+                    break;
+                }
+                if !taken {
+                    return Ok(pack_int(candidate));
+                }
+            }
+        }
+    }
+
+    /// Whether neither a directory nor other keys use `prefix`, a prefix of it or a key starting
+    /// with it.
+    async fn is_prefix_free(&self, tx: &Transaction, prefix: &[u8]) -> Result<bool, Error> {
+        if prefix.is_empty() || prefix.starts_with(self.nodes.prefix()) {
+            return Ok(false);
+        }
+
+        // The node with the greatest prefix up to `prefix`
+        let before: Vec<_> = tx.snapshot().get_range(
+            KeySelector::first_greater_or_equal(self.nodes.range().start),
+            KeySelector::first_greater_or_equal(self.node(prefix).key([0x00])),
+            Some(1),
+            None,
+            true,
+        ).try_collect().await?;
+        let contained = before.first()
+            .and_then(|(key, _)| unpack(BYTES, self.nodes.strip(key)?))
+            .is_some_and(|existing| prefix.starts_with(&existing));
+        if contained {
+            return Ok(false);
+        }
+
+        for range in [starting_with(self.node(prefix).prefix())?, starting_with(prefix)?] {
+            let after: Vec<_> = tx.snapshot().get_range(
+                KeySelector::first_greater_or_equal(range.start),
+                KeySelector::first_greater_or_equal(range.end),
+                Some(1),
+                None,
+                false,
+            ).try_collect().await?;
+            if !after.is_empty() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn remove_recursive(&self, tx: &Transaction, prefix: &[u8]) -> Result<(), Error> {
+        let node = self.node(prefix);
+        let range = subdirs(&node).range();
+        let children: Vec<_> = tx.get_range(
+            KeySelector::first_greater_or_equal(range.start),
+            KeySelector::first_greater_or_equal(range.end),
+            None,
+            None,
+            false,
+        ).try_collect().await?;
+        for (_, child) in children {
+            Box::pin(self.remove_recursive(tx, &child)).await?;
+        }
+
+        tx.clear_range(starting_with(prefix)?).await;
+        tx.clear_range(starting_with(node.prefix())?).await;
+        Ok(())
+    }
+}
+
+/// The names of the subdirectories of `node`, each mapped to the prefix of the subdirectory.
+fn subdirs(node: &Subspace) -> Subspace {
+    node.subspace(pack_int(0))
+}
+
+/// The start of the latest window of the allocator, 0 before the first allocation.
+async fn latest_window(tx: &Transaction, counters: &Subspace) -> Result<u64, Error> {
+    let range = counters.range();
+    let latest: Vec<_> = tx.snapshot().get_range(
+        KeySelector::first_greater_or_equal(range.start),
+        KeySelector::first_greater_or_equal(range.end),
+        Some(1),
+        None,
+        true,
+    ).try_collect().await?;
+    Ok(latest.first().and_then(|(key, _)| unpack_int(counters.strip(key)?)).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuple_elements() {
+        assert_eq!(pack(BYTES, b"a\x00b"), b"\x01a\x00\xffb\x00");
+        assert_eq!(unpack(BYTES, b"\x01a\x00\xffb\x00\x14"), Some(b"a\x00b".to_vec()));
+        assert_eq!(unpack(STRING, b"\x01a\x00"), None);
+        assert_eq!(unpack(BYTES, b"\x01a"), None);
+
+        assert_eq!(pack_int(0), b"\x14");
+        assert_eq!(pack_int(64), b"\x15\x40");
+        assert_eq!(pack_int(300), b"\x16\x01\x2c");
+        for n in [0, 1, 255, 256, 65535, u64::MAX] {
+            assert_eq!(unpack_int(&pack_int(n)), Some(n));
+        }
+    }
+
+    #[test]
+    fn keys_match_the_other_bindings() {
+        let directories = DirectoryLayer::default();
+        assert_eq!(directories.root().prefix().as_slice(), b"\xfe\x01\xfe\x00");
+        assert_eq!(subdirs(&directories.root()).key(pack(STRING, b"app")).as_slice(), b"\xfe\x01\xfe\x00\x14\x02app\x00");
+        assert_eq!(directories.prefix(&directories.node(b"\x15\x07")), b"\x15\x07");
+    }
+}
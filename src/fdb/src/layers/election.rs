@@ -0,0 +1,403 @@
+use std::time::Duration;
+
+use async_stream::try_stream;
+use futures::{pin_mut, Stream, StreamExt};
+use futures::future::{self, Either};
+
+use crate::{Database, Error, Transaction};
+use crate::layers::{get_optional, VERSIONS_PER_SECOND};
+use crate::transaction::ConflictType;
+use crate::types::{Key, KeyRange, KeySelector, Subspace};
+
+/// Length of a versionstamp: 8 bytes commit version, 2 bytes batch order.
+const VERSIONSTAMP_LEN: usize = 10;
+
+/// The leader of a `LeaderElection`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leader {
+    pub id: Vec<u8>,
+    /// Incremented whenever another candidate becomes leader, usable as a fencing token.
+    pub term: u64,
+}
+
+/// The value of `leader`. Resigning keeps the term with an empty id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LeaderRecord {
+    term: u64,
+    expires_at: i64,
+    id: Vec<u8>,
+}
+
+impl LeaderRecord {
+    fn encode(&self) -> Vec<u8> {
+        [&self.term.to_be_bytes()[..], &self.expires_at.to_be_bytes(), &self.id].concat()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<LeaderRecord> {
+        if bytes.len() < 16 {
+            return None;
+        }
+        Some(LeaderRecord {
+            term: u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            expires_at: i64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+            id: bytes[16..].to_vec(),
+        })
+    }
+
+    fn is_current(&self, now: i64) -> bool {
+        !self.id.is_empty() && self.expires_at > now
+    }
+}
+
+/// The value under `candidates/<id>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CandidateRecord {
+    /// Versionstamp of the registration, ordering candidates by seniority
+    registered: [u8; VERSIONSTAMP_LEN],
+    /// Read version of the last heartbeat
+    heartbeat: i64,
+}
+
+impl CandidateRecord {
+    fn encode(&self) -> Vec<u8> {
+        [&self.registered[..], &self.heartbeat.to_be_bytes()].concat()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<CandidateRecord> {
+        if bytes.len() < VERSIONSTAMP_LEN + 8 {
+            return None;
+        }
+        Some(CandidateRecord {
+            registered: bytes[..VERSIONSTAMP_LEN].try_into().unwrap(),
+            heartbeat: i64::from_be_bytes(bytes[VERSIONSTAMP_LEN..VERSIONSTAMP_LEN + 8].try_into().unwrap()),
+        })
+    }
+}
+
+/// Outcome of an election.
+#[derive(Debug, PartialEq, Eq)]
+struct Decision {
+    /// The leader record to write, if it changed
+    leader: Option<LeaderRecord>,
+    /// Candidates whose last heartbeat is older than the timeout
+    stale: Vec<Vec<u8>>,
+}
+
+/// The election rules, at version `now` and with `heartbeat_from` sending a heartbeat.
+///
+/// A leader stays leader until its lease expires, which its heartbeats extend. Otherwise the live
+/// candidate that registered first becomes leader with the next term.
+fn decide(
+    leader: Option<&LeaderRecord>,
+    candidates: &[(Vec<u8>, CandidateRecord)],
+    heartbeat_from: &[u8],
+    now: i64,
+    timeout: i64,
+) -> Decision {
+    let (stale, live): (Vec<_>, Vec<_>) = candidates.iter()
+        .partition(|(id, c)| id != heartbeat_from && now - c.heartbeat > timeout);
+    let stale = stale.into_iter().map(|(id, _)| id.clone()).collect();
+
+    let leader = match leader {
+        Some(l) if l.id == heartbeat_from => Some(LeaderRecord { expires_at: now + timeout, ..l.clone() }),
+        Some(l) if l.is_current(now) => None,
+        _ => live.iter()
+            .min_by_key(|(_, c)| c.registered)
+            .map(|(id, _)| LeaderRecord {
+                term: leader.map_or(0, |l| l.term) + 1,
+                expires_at: now + timeout,
+                id: id.clone(),
+            }),
+    };
+
+    Decision { leader, stale }
+}
+
+/// Elects one leader among the candidates registered under a subspace.
+///
+/// Candidates `register` once and then call `heartbeat` well within the timeout, or let
+/// `campaign` do so. Every heartbeat also runs the election: the leader keeps its leadership as
+/// long as it sends heartbeats, and when it stops the longest registered live candidate takes
+/// over once the timeout has passed. Candidates that stopped sending heartbeats are evicted.
+///
+/// Time is measured in database versions, so the clocks of the candidates do not matter.
+#[derive(Debug, Clone)]
+pub struct LeaderElection {
+    subspace: Subspace,
+    timeout: Duration,
+}
+
+impl LeaderElection {
+    pub fn new<S: Into<Subspace>>(subspace: S, timeout: Duration) -> Self {
+        LeaderElection { subspace: subspace.into(), timeout }
+    }
+
+    fn leader_key(&self) -> Key {
+        self.subspace.key("leader")
+    }
+
+    fn candidates(&self) -> Subspace {
+        self.subspace.subspace("candidates/")
+    }
+
+    fn timeout_versions(&self) -> i64 {
+        (self.timeout.as_micros() as i64).saturating_mul(VERSIONS_PER_SECOND) / 1_000_000
+    }
+
+    async fn leader_record(&self, tx: &Transaction) -> Result<Option<LeaderRecord>, Error> {
+        Ok(get_optional(tx, self.leader_key()).await?.and_then(|v| LeaderRecord::decode(&v)))
+    }
+
+    /// Registers `id` as a candidate, ranking it behind every candidate registered before.
+    pub async fn register(&self, db: &Database, id: &[u8]) -> Result<(), Error> {
+        db.run(async |tx: &mut Transaction| {
+            let heartbeat = tx.get_read_version().await?;
+            // The versionstamp is written over the placeholder at offset 0
+            let mut value = CandidateRecord { registered: [0; VERSIONSTAMP_LEN], heartbeat }.encode();
+            value.extend_from_slice(&0u32.to_le_bytes());
+            tx.atomic_set_versionstamped_value(self.candidates().key(id), value.into()).await;
+            Ok(())
+        }).await
+    }
+
+    /// Refreshes the registration of `id` and runs the election, returning the leader.
+    ///
+    /// A candidate that was evicted after missing heartbeats registers again, behind all others.
+    pub async fn heartbeat(&self, db: &Database, id: &[u8]) -> Result<Option<Leader>, Error> {
+        let registered = db.run(async |tx: &mut Transaction| {
+            Ok(get_optional(tx, self.candidates().key(id)).await?.and_then(|v| CandidateRecord::decode(&v)))
+        }).await?;
+        if registered.is_none() {
+            self.register(db, id).await?;
+        }
+
+        db.run(async |tx: &mut Transaction| {
+            let now = tx.get_read_version().await?;
+            let leader = self.leader_record(tx).await?;
+
+            let mut candidates = Vec::new();
+            {
+                let range = self.candidates().range();
+                // Snapshot read, so that heartbeats of different candidates do not conflict
+                let records = tx.snapshot().get_range(
+                    KeySelector::first_greater_or_equal(range.start),
+                    KeySelector::first_greater_or_equal(range.end),
+                    None,
+                    None,
+                    false,
+                );
+                pin_mut!(records);
+
+                while let Some(kv) = records.next().await {
+                    let (key, value) = kv?;
+                    if let (Some(candidate), Some(record)) = (self.candidates().strip(&key), CandidateRecord::decode(&value)) {
+                        candidates.push((candidate.to_vec(), record));
+                    }
+                }
+            }
+
+            let own = candidates.iter().find(|(c, _)| c == id).map(|(_, r)| r.registered);
+            let Some(registered) = own else {
+                // Evicted since registering, the next heartbeat registers again
+                return Ok(leader.filter(|l| l.is_current(now)).map(|l| Leader { id: l.id, term: l.term }));
+            };
+            tx.set(self.candidates().key(id), CandidateRecord { registered, heartbeat: now }.encode()).await;
+
+            let decision = decide(leader.as_ref(), &candidates, id, now, self.timeout_versions());
+            for stale in &decision.stale {
+                let key = self.candidates().key(stale);
+                // Conflicts with a heartbeat of the evicted candidate arriving meanwhile
+                tx.add_conflict_range(KeyRange::new(key.clone(), crate::scan::key_after(&key)), ConflictType::Read).await?;
+                tx.clear(key).await;
+            }
+
+            let current = match decision.leader {
+                Some(record) => {
+                    tx.set(self.leader_key(), record.encode()).await;
+                    Some(record)
+                }
+                None => leader.filter(|l| l.is_current(now)),
+            };
+            Ok(current.map(|l| Leader { id: l.id, term: l.term }))
+        }).await
+    }
+
+    /// Sends heartbeats for `id` three times per timeout, until one fails.
+    pub async fn campaign(&self, db: &Database, id: &[u8]) -> Result<(), Error> {
+        loop {
+            self.heartbeat(db, id).await?;
+            crate::runtime::sleep(self.timeout / 3).await;
+        }
+    }
+
+    /// Withdraws the candidate `id`, giving up leadership if it holds it.
+    pub async fn resign(&self, db: &Database, id: &[u8]) -> Result<(), Error> {
+        db.run(async |tx: &mut Transaction| {
+            tx.clear(self.candidates().key(id)).await;
+            if let Some(leader) = self.leader_record(tx).await?.filter(|l| l.id == id) {
+                tx.set(self.leader_key(), LeaderRecord { expires_at: 0, id: Vec::new(), ..leader }.encode()).await;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// The current leader, unless its lease expired.
+    pub async fn leader(&self, tx: &Transaction) -> Result<Option<Leader>, Error> {
+        let now = tx.get_read_version().await?;
+        let leader = self.leader_record(tx).await?;
+        Ok(leader.filter(|l| l.is_current(now)).map(|l| Leader { id: l.id, term: l.term }))
+    }
+
+    /// The leader, followed by every change of leadership. Yields `None` while there is no leader.
+    ///
+    /// Changes are noticed through a watch on the leader key, and expiry of the leader's lease by
+    /// checking again once it is due.
+    pub fn leader_changes<'a>(&'a self, db: &'a Database) -> impl Stream<Item=Result<Option<Leader>, Error>> + 'a {
+        try_stream! {
+            let mut last = None;
+            loop {
+                let (leader, remaining, watch) = db.run(async |tx: &mut Transaction| {
+                    let now = tx.get_read_version().await?;
+                    let record = self.leader_record(tx).await?.filter(|l| l.is_current(now));
+                    let remaining = record.as_ref().map(|l| (l.expires_at - now) * 1_000_000 / VERSIONS_PER_SECOND);
+                    Ok((record.map(|l| Leader { id: l.id, term: l.term }), remaining, tx.watch(self.leader_key())))
+                }).await?;
+
+                if last.as_ref() != Some(&leader) {
+                    last = Some(leader.clone());
+                    yield leader;
+                }
+
+                // Without a leader only a heartbeat electing one changes anything
+                let due = Duration::from_micros(remaining.unwrap_or(self.timeout_versions()).max(1) as u64);
+                if let Either::Left((Err(e), _)) = future::select(Box::pin(watch), Box::pin(crate::runtime::sleep(due))).await {
+                    Err(e)?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: i64 = 1_000;
+
+    fn candidate(id: &str, registered: u8, heartbeat: i64) -> (Vec<u8>, CandidateRecord) {
+        (id.as_bytes().to_vec(), CandidateRecord { registered: [registered; VERSIONSTAMP_LEN], heartbeat })
+    }
+
+    fn leader(id: &str, term: u64, expires_at: i64) -> LeaderRecord {
+        LeaderRecord { term, expires_at, id: id.as_bytes().to_vec() }
+    }
+
+    #[test]
+    fn earliest_registered_candidate_is_elected() {
+        let candidates = [candidate("b", 2, 100), candidate("a", 1, 100), candidate("c", 3, 100)];
+        let decision = decide(None, &candidates, b"c", 100, TIMEOUT);
+        assert_eq!(decision.leader, Some(leader("a", 1, 100 + TIMEOUT)));
+        assert!(decision.stale.is_empty());
+    }
+
+    #[test]
+    fn current_leader_is_kept_and_extended_by_its_heartbeat() {
+        let candidates = [candidate("a", 1, 100), candidate("b", 2, 100)];
+        let current = leader("b", 4, 900);
+
+        let decision = decide(Some(&current), &candidates, b"a", 500, TIMEOUT);
+        assert_eq!(decision.leader, None);
+
+        let decision = decide(Some(&current), &candidates, b"b", 500, TIMEOUT);
+        assert_eq!(decision.leader, Some(leader("b", 4, 500 + TIMEOUT)));
+    }
+
+    #[test]
+    fn stale_leader_is_replaced_and_evicted() {
+        let candidates = [candidate("a", 1, 0), candidate("b", 2, 1_500)];
+        let current = leader("a", 4, 1_000);
+
+        let decision = decide(Some(&current), &candidates, b"b", 1_500, TIMEOUT);
+        assert_eq!(decision.leader, Some(leader("b", 5, 1_500 + TIMEOUT)));
+        assert_eq!(decision.stale, vec![b"a".to_vec()]);
+    }
+
+    #[test]
+    fn resigned_leader_keeps_term() {
+        let candidates = [candidate("a", 1, 100)];
+        let resigned = leader("", 7, 0);
+        let decision = decide(Some(&resigned), &candidates, b"a", 100, TIMEOUT);
+        assert_eq!(decision.leader, Some(leader("a", 8, 100 + TIMEOUT)));
+    }
+
+    #[test]
+    fn records_roundtrip() {
+        let record = leader("node-1", 3, 42);
+        assert_eq!(LeaderRecord::decode(&record.encode()), Some(record));
+        let (_, record) = candidate("a", 9, 77);
+        assert_eq!(CandidateRecord::decode(&record.encode()), Some(record));
+    }
+
+    fn elected(id: &str, term: u64) -> Option<Leader> {
+        Some(Leader { id: id.as_bytes().to_vec(), term })
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn campaigning_keeps_the_leader() {
+        let cluster = crate::testing::TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let election = LeaderElection::new(cluster.subspace(), Duration::from_millis(300));
+        election.register(&db, b"a").await.unwrap();
+
+        // Several timeouts long, in which only the heartbeats extend the lease of `a`
+        let campaigns = future::select(Box::pin(election.campaign(&db, b"a")), Box::pin(election.campaign(&db, b"b")));
+        let observed = async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            db.run(async |tx: &mut Transaction| election.leader(tx).await).await
+        };
+        let outcome = future::select(Box::pin(campaigns), Box::pin(observed)).await;
+        match outcome {
+            Either::Right((leader, _)) => assert_eq!(leader.unwrap(), elected("a", 1)),
+            Either::Left(_) => panic!("a campaign failed"),
+        };
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn missing_heartbeats_evict_the_leader() {
+        let cluster = crate::testing::TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let election = LeaderElection::new(cluster.subspace(), Duration::from_millis(300));
+
+        assert_eq!(election.heartbeat(&db, b"a").await.unwrap(), elected("a", 1));
+        election.register(&db, b"b").await.unwrap();
+        assert_eq!(election.heartbeat(&db, b"b").await.unwrap(), elected("a", 1));
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(election.heartbeat(&db, b"b").await.unwrap(), elected("b", 2));
+        let registered = db.run(async |tx: &mut Transaction| get_optional(tx, election.candidates().key(b"a")).await).await.unwrap();
+        assert_eq!(registered, None);
+
+        // Back after the eviction, behind `b`
+        assert_eq!(election.heartbeat(&db, b"a").await.unwrap(), elected("b", 2));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn leader_changes_follow_elections() {
+        let cluster = crate::testing::TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let election = LeaderElection::new(cluster.subspace(), Duration::from_secs(30));
+        let changes = election.leader_changes(&db);
+        pin_mut!(changes);
+
+        assert_eq!(changes.next().await.unwrap().unwrap(), None);
+        election.heartbeat(&db, b"a").await.unwrap();
+        assert_eq!(changes.next().await.unwrap().unwrap(), elected("a", 1));
+        election.resign(&db, b"a").await.unwrap();
+        assert_eq!(changes.next().await.unwrap().unwrap(), None);
+        election.heartbeat(&db, b"b").await.unwrap();
+        assert_eq!(changes.next().await.unwrap().unwrap(), elected("b", 2));
+    }
+}
@@ -0,0 +1,276 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::future::{self, Either};
+
+use crate::{Database, Error, Transaction};
+use crate::layers::{get_optional, VERSIONS_PER_SECOND};
+use crate::types::{Key, Subspace};
+
+/// Shortest wait before checking again whether a lease has expired.
+const MIN_EXPIRY_WAIT: Duration = Duration::from_millis(10);
+
+/// How long a lease lasts unless renewed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// Until the database version advanced by this many versions, about a million per second.
+    /// Does not depend on the clocks of the clients.
+    Versions(i64),
+    /// Until the duration passed on the wall clock. The clocks of all clients using the lock must
+    /// be synchronized.
+    WallClock(Duration),
+}
+
+/// The lease stored under the lock's key. A released lease keeps its token with an empty owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    token: u64,
+    /// Whether `expires_at` is a version rather than milliseconds since the Unix epoch
+    versions: bool,
+    expires_at: i64,
+    owner: Vec<u8>,
+}
+
+impl Record {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(17 + self.owner.len());
+        bytes.extend_from_slice(&self.token.to_be_bytes());
+        bytes.push(self.versions as u8);
+        bytes.extend_from_slice(&self.expires_at.to_be_bytes());
+        bytes.extend_from_slice(&self.owner);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Record> {
+        if bytes.len() < 17 {
+            return None;
+        }
+        Some(Record {
+            token: u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            versions: bytes[8] != 0,
+            expires_at: i64::from_be_bytes(bytes[9..17].try_into().unwrap()),
+            owner: bytes[17..].to_vec(),
+        })
+    }
+
+    /// Time until the lease expires, zero once it has, or if it was released.
+    fn remaining(&self, now: i64) -> Duration {
+        if self.owner.is_empty() || now >= self.expires_at {
+            return Duration::ZERO;
+        }
+        match self.versions {
+            true => Duration::from_micros(((self.expires_at - now) * 1_000_000 / VERSIONS_PER_SECOND) as u64),
+            false => Duration::from_millis((self.expires_at - now) as u64),
+        }
+    }
+}
+
+fn wall_clock_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+/// The current time in the unit the record expires in.
+async fn now(tx: &Transaction, versions: bool) -> Result<i64, Error> {
+    match versions {
+        true => tx.get_read_version().await,
+        false => Ok(wall_clock_millis()),
+    }
+}
+
+/// A lock held by at most one owner at a time, stored under a single key.
+///
+/// Acquiring the lock grants a `Lease`, which expires unless renewed so that a crashed owner does
+/// not hold the lock forever. Every acquisition increments the fencing token of the lease:
+/// services rejecting requests with a lower token than one they have seen are protected from an
+/// owner whose lease expired unnoticed. `Lease::check` does the same for FoundationDB transactions.
+#[derive(Debug, Clone)]
+pub struct Lock {
+    key: Key,
+    expiry: Expiry,
+}
+
+impl Lock {
+    pub fn new<S: Into<Subspace>, N: AsRef<[u8]>>(subspace: S, name: N, expiry: Expiry) -> Self {
+        Lock {
+            key: subspace.into().key(name),
+            expiry,
+        }
+    }
+
+    async fn record(&self, tx: &Transaction) -> Result<Option<Record>, Error> {
+        Ok(get_optional(tx, self.key.clone()).await?.and_then(|v| Record::decode(&v)))
+    }
+
+    /// Writes a lease for `owner` expiring after `self.expiry`.
+    async fn grant(&self, tx: &Transaction, owner: &[u8], token: u64) -> Result<Lease, Error> {
+        let record = match self.expiry {
+            Expiry::Versions(versions) => Record {
+                token,
+                versions: true,
+                expires_at: tx.get_read_version().await? + versions,
+                owner: owner.to_vec(),
+            },
+            Expiry::WallClock(duration) => Record {
+                token,
+                versions: false,
+                expires_at: wall_clock_millis() + duration.as_millis() as i64,
+                owner: owner.to_vec(),
+            },
+        };
+        tx.set(self.key.clone(), record.encode()).await;
+
+        Ok(Lease { lock: self.clone(), owner: owner.to_vec(), token })
+    }
+
+    /// Acquires the lock, or returns how long the current lease has left.
+    async fn attempt(&self, tx: &Transaction, owner: &[u8]) -> Result<Result<Lease, Duration>, Error> {
+        // Would match the empty owner of a released lease, keeping its token
+        if owner.is_empty() {
+            return Err(Error::EmptyLeaseOwner);
+        }
+        let Some(record) = self.record(tx).await? else {
+            return Ok(Ok(self.grant(tx, owner, 1).await?));
+        };
+
+        if record.owner == owner {
+            return Ok(Ok(self.grant(tx, owner, record.token).await?));
+        }
+        let remaining = record.remaining(now(tx, record.versions).await?);
+        if remaining.is_zero() {
+            return Ok(Ok(self.grant(tx, owner, record.token + 1).await?));
+        }
+        Ok(Err(remaining))
+    }
+
+    /// Acquires the lock for `owner`, unless another owner holds an unexpired lease. Acquiring a
+    /// lock `owner` already holds renews the lease and keeps its token.
+    ///
+    /// Fails with `Error::EmptyLeaseOwner` if `owner` is empty, as does `acquire`.
+    pub async fn try_acquire(&self, db: &Database, owner: &[u8]) -> Result<Option<Lease>, Error> {
+        let attempt = db.run(async |tx: &mut Transaction| self.attempt(tx, owner).await).await?;
+        Ok(attempt.ok())
+    }
+
+    /// Acquires the lock for `owner`, waiting for the current lease to be released or to expire.
+    ///
+    /// Waits on a watch of the lock's key, so a release is noticed right away, and otherwise sleeps
+    /// until the lease is due to expire.
+    pub async fn acquire(&self, db: &Database, owner: &[u8]) -> Result<Lease, Error> {
+        loop {
+            let attempt = db.run(async |tx: &mut Transaction| {
+                Ok(match self.attempt(tx, owner).await? {
+                    Ok(lease) => Ok(lease),
+                    Err(remaining) => Err((tx.watch(self.key.clone()), remaining)),
+                })
+            }).await?;
+
+            let (watch, remaining) = match attempt {
+                Ok(lease) => return Ok(lease),
+                Err(held) => held,
+            };
+
+            let expired = crate::runtime::sleep(remaining.max(MIN_EXPIRY_WAIT));
+            if let Either::Left((Err(e), _)) = future::select(Box::pin(watch), Box::pin(expired)).await {
+                return Err(e);
+            }
+        }
+    }
+
+    /// The owner of the current lease, unless it expired or was released.
+    pub async fn owner(&self, tx: &Transaction) -> Result<Option<Vec<u8>>, Error> {
+        let Some(record) = self.record(tx).await? else {
+            return Ok(None);
+        };
+        let remaining = record.remaining(now(tx, record.versions).await?);
+        Ok((!remaining.is_zero()).then_some(record.owner))
+    }
+}
+
+/// The right to hold a `Lock` until the lease expires.
+#[derive(Debug, Clone)]
+pub struct Lease {
+    lock: Lock,
+    owner: Vec<u8>,
+    token: u64,
+}
+
+impl Lease {
+    pub fn owner(&self) -> &[u8] {
+        &self.owner
+    }
+
+    /// Fencing token, greater than the token of any earlier lease of the lock.
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// Fails with `Error::LeaseLost` unless this lease is still the current one, reading the lock's
+    /// key so that the transaction conflicts with any change of ownership before it commits.
+    ///
+    /// An expired lease passes as long as nobody else acquired the lock, like for `renew`.
+    pub async fn check(&self, tx: &Transaction) -> Result<(), Error> {
+        match self.lock.record(tx).await? {
+            Some(record) if record.token == self.token && record.owner == self.owner => Ok(()),
+            _ => Err(Error::LeaseLost),
+        }
+    }
+
+    /// Extends the lease by the lock's expiry, counted from now.
+    pub async fn renew(&self, db: &Database) -> Result<(), Error> {
+        db.run(async |tx: &mut Transaction| {
+            self.check(tx).await?;
+            self.lock.grant(tx, &self.owner, self.token).await?;
+            Ok(())
+        }).await
+    }
+
+    /// Releases the lock, if this lease is still the current one.
+    pub async fn release(self, db: &Database) -> Result<(), Error> {
+        db.run(async |tx: &mut Transaction| {
+            if self.check(tx).await.is_ok() {
+                let released = Record { token: self.token, versions: false, expires_at: 0, owner: Vec::new() };
+                tx.set(self.lock.key.clone(), released.encode()).await;
+            }
+            Ok(())
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_roundtrip() {
+        let record = Record { token: 7, versions: true, expires_at: 123_456, owner: b"worker-1".to_vec() };
+        assert_eq!(Record::decode(&record.encode()), Some(record));
+        assert_eq!(Record::decode(b"short"), None);
+    }
+
+    #[test]
+    fn remaining() {
+        let record = Record { token: 1, versions: true, expires_at: 3_000_000, owner: b"a".to_vec() };
+        assert_eq!(record.remaining(1_000_000), Duration::from_secs(2));
+        assert_eq!(record.remaining(3_000_000), Duration::ZERO);
+
+        let record = Record { token: 1, versions: false, expires_at: 1_500, owner: b"a".to_vec() };
+        assert_eq!(record.remaining(1_000), Duration::from_millis(500));
+
+        let released = Record { owner: Vec::new(), ..record };
+        assert_eq!(released.remaining(0), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn released_leases_are_not_reacquired() {
+        let cluster = crate::testing::TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let lock = Lock::new(cluster.subspace(), "lock", Expiry::Versions(10_000_000));
+
+        assert_eq!(lock.try_acquire(&db, b"").await.unwrap_err(), Error::EmptyLeaseOwner);
+
+        let lease = lock.acquire(&db, b"a").await.unwrap();
+        assert_eq!(lease.token(), 1);
+        lease.release(&db).await.unwrap();
+        assert_eq!(lock.try_acquire(&db, b"a").await.unwrap().map(|l| l.token()), Some(2));
+    }
+}
@@ -0,0 +1,52 @@
+//! Higher level data structures built from transactions, each keeping its keys in a `Subspace`.
+
+use crate::{Error, Transaction};
+use crate::types::{Key, Value};
+
+mod change_feed;
+mod counter;
+mod directory;
+mod election;
+mod lock;
+mod queue;
+mod record;
+
+pub use change_feed::{Change, ChangeFeed, ChangeOp, ChangeTransaction, Cursor};
+pub use counter::{Counter, CounterValue};
+pub use directory::DirectoryLayer;
+pub use election::{Leader, LeaderElection};
+pub use lock::{Expiry, Lease, Lock};
+pub use queue::{Message, Queue};
+pub use record::{Index, IndexKind, Record, RecordStore};
+
+/// Rate at which the cluster advances its version, used to convert between versions and time.
+pub(crate) const VERSIONS_PER_SECOND: i64 = 1_000_000;
+
+/// Decodes a value written by atomic adds, a little-endian integer of up to 8 bytes.
+pub(crate) fn decode_i64(value: &[u8]) -> i64 {
+    let mut bytes = [0u8; 8];
+    let len = value.len().min(8);
+    bytes[..len].copy_from_slice(&value[..len]);
+    i64::from_le_bytes(bytes)
+}
+
+/// Reads the key, mapping `Error::KeyNotFound` to `None`.
+pub(crate) async fn get_optional<K: Into<Key>>(tx: &Transaction, key: K) -> Result<Option<Value>, Error> {
+    match tx.get(key).await {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::KeyNotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_shards() {
+        assert_eq!(decode_i64(&(-7i64).to_le_bytes()), -7);
+        assert_eq!(decode_i64(&5i32.to_le_bytes()), 5);
+        assert_eq!(decode_i64(&[]), 0);
+    }
+}
@@ -0,0 +1,365 @@
+use std::time::Duration;
+
+use futures::{pin_mut, StreamExt};
+use futures::future::{self, Either};
+use rand::Rng;
+
+use crate::{Database, Error, Transaction};
+use crate::layers::{get_optional, VERSIONS_PER_SECOND};
+use crate::transaction::ConflictType;
+use crate::types::{Key, KeyRange, KeySelector, Subspace};
+
+/// Length of an item id: the 10 byte versionstamp of the enqueuing transaction, followed by a
+/// 2 byte sequence number telling apart the items it enqueued.
+const ID_LEN: usize = 12;
+
+/// Shortest wait before checking again whether a claimed item became visible.
+const MIN_VISIBILITY_WAIT: Duration = Duration::from_millis(10);
+
+/// Most items a claim chooses from, after repeated conflicts.
+const MAX_CLAIM_WINDOW: i32 = 64;
+
+/// The value of an item, wherever it is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    deliveries: u32,
+    payload: Vec<u8>,
+}
+
+impl Entry {
+    fn encode(&self) -> Vec<u8> {
+        [&self.deliveries.to_be_bytes()[..], &self.payload].concat()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Entry> {
+        if bytes.len() < 4 {
+            return None;
+        }
+        Some(Entry {
+            deliveries: u32::from_be_bytes(bytes[..4].try_into().unwrap()),
+            payload: bytes[4..].to_vec(),
+        })
+    }
+}
+
+/// Splits the key of a claimed item within `in_flight/` into its visibility deadline and id.
+fn decode_in_flight(key: &[u8]) -> Option<(i64, [u8; ID_LEN])> {
+    if key.len() != 8 + ID_LEN {
+        return None;
+    }
+    Some((i64::from_be_bytes(key[..8].try_into().unwrap()), key[8..].try_into().unwrap()))
+}
+
+/// Number of items a claim chooses from on its `attempt`-th try. Starts out at one, which keeps the
+/// order strict while there is no contention, and doubles with every conflict.
+fn claim_window(attempt: u32) -> i32 {
+    1i32.checked_shl(attempt.saturating_sub(1)).unwrap_or(i32::MAX).min(MAX_CLAIM_WINDOW)
+}
+
+/// An item claimed by `pop`, invisible to other consumers until acknowledged or until its
+/// visibility timeout passes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    key: Key,
+    id: [u8; ID_LEN],
+    deliveries: u32,
+    payload: Vec<u8>,
+}
+
+impl Message {
+    /// Identifies the item across deliveries, ordered like the items were enqueued.
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// How often the item was delivered, including this delivery.
+    pub fn deliveries(&self) -> u32 {
+        self.deliveries
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn into_payload(self) -> Vec<u8> {
+        self.payload
+    }
+}
+
+/// A durable FIFO queue stored in a subspace.
+///
+/// `push` writes items under the versionstamp of the committing transaction, so producers never
+/// conflict and items are ordered by commit. `pop` claims the oldest item, making it invisible
+/// for the visibility timeout. Unless it is acknowledged with `ack` by then, it is delivered again,
+/// and after `max_deliveries` moved to the dead letter subspace instead.
+///
+/// Consumers popping concurrently would all claim the same oldest item, so each retry after a
+/// conflict chooses randomly among more of the oldest items, up to 64. Under contention items are
+/// therefore delivered in roughly, but not strictly, FIFO order.
+///
+/// Time is measured in database versions, so the clocks of the consumers do not matter.
+#[derive(Debug, Clone)]
+pub struct Queue {
+    subspace: Subspace,
+    dead_letters: Subspace,
+    visibility_timeout: Duration,
+    max_deliveries: u32,
+}
+
+impl Queue {
+    /// A queue keeping its dead letters in the nested subspace `dead/`.
+    pub fn new<S: Into<Subspace>>(subspace: S, visibility_timeout: Duration, max_deliveries: u32) -> Self {
+        let subspace = subspace.into();
+        Queue {
+            dead_letters: subspace.subspace("dead/"),
+            subspace,
+            visibility_timeout,
+            max_deliveries: max_deliveries.max(1),
+        }
+    }
+
+    /// Moves items delivered `max_deliveries` times without acknowledgement to `subspace`, keyed
+    /// by their id.
+    pub fn with_dead_letters<S: Into<Subspace>>(mut self, subspace: S) -> Self {
+        self.dead_letters = subspace.into();
+        self
+    }
+
+    /// Subspace of the items delivered `max_deliveries` times without acknowledgement.
+    pub fn dead_letters(&self) -> &Subspace {
+        &self.dead_letters
+    }
+
+    fn items(&self) -> Subspace {
+        self.subspace.subspace("items/")
+    }
+
+    fn in_flight(&self) -> Subspace {
+        self.subspace.subspace("in_flight/")
+    }
+
+    /// Changed by every `push`, so that blocked consumers can watch it.
+    fn signal_key(&self) -> Key {
+        self.subspace.key("signal")
+    }
+
+    fn in_flight_key(&self, deadline: i64, id: &[u8; ID_LEN]) -> Key {
+        self.in_flight().key([&deadline.to_be_bytes()[..], id].concat())
+    }
+
+    fn visibility_versions(&self) -> i64 {
+        (self.visibility_timeout.as_micros() as i64).saturating_mul(VERSIONS_PER_SECOND) / 1_000_000
+    }
+
+    /// Enqueues `item` when the transaction commits. Fails with `Error::TooManyChanges` after 65536
+    /// items in one transaction.
+    pub async fn push(&self, tx: &Transaction, item: &[u8]) -> Result<(), Error> {
+        // Numbered per transaction, so that items pushed through several handles stay distinct
+        let sequence = tx.next_sequence()?;
+        // The versionstamp is written over the placeholder at the offset in the last 4 bytes
+        let offset = self.items().prefix().len() as u32;
+        let key = [
+            self.items().prefix().as_slice(),
+            &[0; 10],
+            &sequence.to_be_bytes(),
+            &offset.to_le_bytes(),
+        ].concat();
+
+        let entry = Entry { deliveries: 0, payload: item.to_vec() };
+        tx.atomic_set_versionstamped_key(key, entry.encode().into()).await;
+        tx.atomic_add_i64(self.signal_key(), 1).await;
+        Ok(())
+    }
+
+    /// Up to `limit` of the first keys in `range`, read without conflicting.
+    async fn first(&self, tx: &mut Transaction, range: KeyRange, limit: i32) -> Result<Vec<(Key, Vec<u8>)>, Error> {
+        let kvs = tx.snapshot().get_range(
+            KeySelector::first_greater_or_equal(range.start),
+            KeySelector::first_greater_or_equal(range.end),
+            Some(limit),
+            None,
+            false,
+        );
+        pin_mut!(kvs);
+
+        let mut first = Vec::new();
+        while let Some(kv) = kvs.next().await {
+            let (key, value) = kv?;
+            first.push((key, value.to_vec()));
+        }
+        Ok(first)
+    }
+
+    /// Claims one of the first `window` visible items. Returns how long until the next claimed
+    /// item becomes visible again if there is none, or `None` if no item is claimed either.
+    async fn claim(&self, tx: &mut Transaction, window: i32) -> Result<Result<Message, Option<Duration>>, Error> {
+        let now = tx.get_read_version().await?;
+        let expired = KeyRange::new(self.in_flight().range().start, self.in_flight().key(now.to_be_bytes()));
+
+        loop {
+            // Items whose visibility timeout passed are older than any waiting item
+            let mut candidates = self.first(tx, expired.clone(), window).await?;
+            if candidates.is_empty() {
+                candidates = self.first(tx, self.items().range(), window).await?;
+            }
+            if candidates.is_empty() {
+                let next = self.first(tx, self.in_flight().range(), 1).await?;
+                let visible = next.first()
+                    .and_then(|(key, _)| decode_in_flight(self.in_flight().strip(key)?))
+                    .map(|(deadline, _)| Duration::from_micros(((deadline - now).max(0) * 1_000_000 / VERSIONS_PER_SECOND) as u64));
+                return Ok(Err(visible));
+            }
+
+            let (key, value) = candidates.swap_remove(rand::thread_rng().gen_range(0..candidates.len()));
+            let id = match (self.items().strip(&key), self.in_flight().strip(&key)) {
+                (Some(id), _) => id.try_into().ok(),
+                (_, Some(in_flight)) => decode_in_flight(in_flight).map(|(_, id)| id),
+                _ => None,
+            };
+
+            // The snapshot read did not conflict, so that consumers claiming other items do not
+            // either, but one claiming the same item must
+            tx.add_conflict_range(KeyRange::new(key.clone(), crate::scan::key_after(&key)), ConflictType::Read).await?;
+            tx.clear(key).await;
+
+            let (Some(id), Some(entry)) = (id, Entry::decode(&value)) else {
+                continue;
+            };
+            if entry.deliveries >= self.max_deliveries {
+                tx.set(self.dead_letters.key(id), entry.encode()).await;
+                continue;
+            }
+
+            let entry = Entry { deliveries: entry.deliveries + 1, ..entry };
+            let key = self.in_flight_key(now + self.visibility_versions(), &id);
+            tx.set(key.clone(), entry.encode()).await;
+
+            return Ok(Ok(Message { key, id, deliveries: entry.deliveries, payload: entry.payload }));
+        }
+    }
+
+    /// Claims the oldest visible item, if any.
+    pub async fn try_pop(&self, db: &Database) -> Result<Option<Message>, Error> {
+        let mut attempt = 0;
+        db.run(async |tx: &mut Transaction| {
+            attempt += 1;
+            Ok(self.claim(tx, claim_window(attempt)).await?.ok())
+        }).await
+    }
+
+    /// Claims the oldest visible item, waiting for one to be pushed or for the visibility timeout
+    /// of a claimed one to pass.
+    pub async fn pop(&self, db: &Database) -> Result<Message, Error> {
+        loop {
+            let mut attempt = 0;
+            let claim = db.run(async |tx: &mut Transaction| {
+                attempt += 1;
+                Ok(match self.claim(tx, claim_window(attempt)).await? {
+                    Ok(message) => Ok(message),
+                    Err(visible) => Err((tx.watch(self.signal_key()), visible)),
+                })
+            }).await?;
+
+            let (watch, visible) = match claim {
+                Ok(message) => return Ok(message),
+                Err(empty) => empty,
+            };
+
+            let result = match visible {
+                Some(visible) => {
+                    let visible = crate::runtime::sleep(visible.max(MIN_VISIBILITY_WAIT));
+                    match future::select(Box::pin(watch), Box::pin(visible)).await {
+                        Either::Left((result, _)) => result,
+                        Either::Right(_) => Ok(()),
+                    }
+                }
+                None => watch.await,
+            };
+            result?;
+        }
+    }
+
+    /// Removes the item for good, when the transaction commits.
+    ///
+    /// Fails with `Error::ClaimLost` if the item was claimed again after its visibility timeout
+    /// passed, or was already acknowledged. Acknowledging in the transaction processing the item
+    /// makes both take effect together.
+    pub async fn ack(&self, tx: &Transaction, message: &Message) -> Result<(), Error> {
+        if get_optional(tx, message.key.clone()).await?.is_none() {
+            return Err(Error::ClaimLost);
+        }
+        tx.clear(message.key.clone()).await;
+        Ok(())
+    }
+
+    /// Makes the item visible again right away, ahead of the items enqueued after it.
+    pub async fn release(&self, tx: &Transaction, message: &Message) -> Result<(), Error> {
+        let Some(entry) = get_optional(tx, message.key.clone()).await? else {
+            return Err(Error::ClaimLost);
+        };
+        tx.clear(message.key.clone()).await;
+        tx.set(self.items().key(message.id), entry).await;
+        tx.atomic_add_i64(self.signal_key(), 1).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_roundtrip() {
+        let entry = Entry { deliveries: 3, payload: b"job".to_vec() };
+        assert_eq!(Entry::decode(&entry.encode()), Some(entry));
+        assert_eq!(Entry::decode(b"abc"), None);
+    }
+
+    #[test]
+    fn in_flight_keys() {
+        let queue = Queue::new("q/", Duration::from_secs(30), 5);
+        let id = [7; ID_LEN];
+        let key = queue.in_flight_key(1_000, &id);
+        assert_eq!(decode_in_flight(queue.in_flight().strip(&key).unwrap()), Some((1_000, id)));
+
+        // Claimed items are ordered by their visibility deadline
+        assert!(queue.in_flight_key(999, &[0xff; ID_LEN]) < key);
+        assert!(queue.in_flight().range().contains(&key));
+    }
+
+    #[test]
+    fn claim_window_grows_with_conflicts() {
+        assert_eq!(claim_window(1), 1);
+        assert_eq!(claim_window(2), 2);
+        assert_eq!(claim_window(4), 8);
+        assert_eq!(claim_window(100), MAX_CLAIM_WINDOW);
+    }
+
+    #[test]
+    fn dead_letters() {
+        let queue = Queue::new("q/", Duration::from_secs(30), 0);
+        assert_eq!(queue.max_deliveries, 1);
+        assert_eq!(queue.dead_letters().prefix(), &Key::from("q/dead/"));
+        assert_eq!(queue.with_dead_letters("dlq/").dead_letters().prefix(), &Key::from("dlq/"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn handles_pushing_in_one_transaction() {
+        let cluster = crate::testing::TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let (first, second) = (Queue::new(cluster.subspace(), Duration::from_secs(30), 5), Queue::new(cluster.subspace(), Duration::from_secs(30), 5));
+
+        db.run(async |tx: &mut Transaction| {
+            first.push(tx, b"a").await?;
+            second.push(tx, b"b").await?;
+            first.push(tx, b"c").await
+        }).await.unwrap();
+
+        let mut popped = Vec::new();
+        while let Some(message) = first.try_pop(&db).await.unwrap() {
+            popped.push(message.into_payload());
+        }
+        assert_eq!(popped, [b"a", b"b", b"c"]);
+    }
+}
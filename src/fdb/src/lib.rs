@@ -4,18 +4,50 @@ use std::fmt::{Debug, Display, Formatter};
 use log::error;
 use thiserror::Error;
 
-pub use client::Client;
+pub use bulk::{BulkLoad, LoadProgress};
+pub use client::{Client, ClientBuilder};
+pub use delete::{DeleteProgress, DeleteRange};
 pub use database::Database;
 use fdb_c::fdb_error_t;
-pub use transaction::{CreateTransaction, Transaction};
+pub use locality::ScanOrder;
+pub use options::{NetworkOption, TransactionOption};
+pub use scan::{Scan, ScanConsistency};
+#[cfg(any(feature = "730", feature = "dlopen"))]
+pub use status::{ClientStatus, ConnectionStatus};
+#[cfg(any(feature = "730", feature = "710", feature = "dlopen"))]
+pub use tenant::Tenant;
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "630", feature = "dlopen"))]
+pub use throttle::{TagPriority, ThrottledTag, ThrottleReason};
+pub use transaction::{CreateTransaction, ReadOnlyTransaction, ReadTransaction, Snapshot, Transaction};
+pub use types::{Key, KeyRange, KeySelector, printable, StorageAddress, Subspace, Value};
 
 mod client;
 mod transaction;
 mod database;
-#[cfg(any(feature = "730", feature = "710"))]
+mod locality;
+mod options;
+mod scan;
+#[cfg(any(feature = "730", feature = "dlopen"))]
+mod status;
+mod bulk;
+mod delete;
+mod telemetry;
+#[cfg(any(feature = "730", feature = "710", feature = "dlopen"))]
 mod tenant;
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "630", feature = "dlopen"))]
+mod throttle;
 mod future;
 mod types;
+pub mod runtime;
+pub mod layers;
+pub mod chaos;
+pub mod cache;
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+pub mod limiter;
+#[cfg(feature = "backup")]
+pub mod backup;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum Error {
@@ -31,6 +63,50 @@ pub enum Error {
     ActionInvalidBeforeNetworkConfig,
     #[error("Key not found")]
     KeyNotFound,
+    #[error("Transaction is too old to perform reads or be committed")]
+    TransactionTooOld,
+    #[error("A tag of the transaction is throttled")]
+    TagThrottled,
+    #[error("Transaction tag {0:?} is longer than 16 bytes")]
+    TagTooLong(String),
+    #[error("At most 40 tags can be throttled manually")]
+    TooManyThrottles,
+    #[error("The rate of a throttle must be a positive number of transactions per second")]
+    InvalidThrottleRate,
+    #[error("Invalid network address {0:?}")]
+    InvalidAddress(String),
+    #[error("Invalid cluster file path {0:?}")]
+    InvalidClusterFile(std::path::PathBuf),
+    #[error("No key range covers all keys starting with {0:?}")]
+    InvalidPrefix(Key),
+    #[error("{0} is not supported by the selected API version or the loaded client library")]
+    Unsupported(&'static str),
+    #[error("Could not load the client library: {0}")]
+    LibraryLoad(String),
+    #[error("The lease has expired and was acquired by another owner, or released")]
+    LeaseLost,
+    #[error("The owner of a lease must not be empty, which marks a released lock")]
+    EmptyLeaseOwner,
+    #[error("The visibility timeout of the message passed and it was claimed again, or it was already acknowledged")]
+    ClaimLost,
+    #[error("The record type declares no index {0:?} supporting the query")]
+    UnknownIndex(String),
+    #[error("The index {0:?} is still being built")]
+    IndexNotReady(String),
+    #[error("Another record has the same value in the unique index {0:?}")]
+    UniqueViolation(String),
+    #[error("The record stored under {0:?} could not be decoded")]
+    InvalidRecord(Key),
+    #[error("Directory layer: {0}")]
+    Directory(String),
+    #[error("Too many changes recorded in a single transaction")]
+    TooManyChanges,
+    #[error("Backup failed: {0}")]
+    Backup(String),
+    #[error("Could not start the test cluster: {0}")]
+    TestCluster(String),
+    #[error("Invalid client status: {0}")]
+    ClientStatus(String),
 }
 
 #[derive(Eq, PartialEq)]
@@ -60,11 +136,24 @@ impl From<FdbErrorCode> for Error {
             2201 => Error::APIVersionSingletonViolated,
             2009 => Error::NetworkSingletonViolated,
             2008 => Error::ActionInvalidBeforeNetworkConfig,
+            1007 => Error::TransactionTooOld,
+            1213 => Error::TagThrottled,
             _ => Error::Generic(FdbErrorCode(value.0)),
         }
     }
 }
 
+impl Error {
+    /// The code of errors reported by the client library, `None` for the errors of these bindings,
+    /// which `Transaction::on_error` does not retry.
+    pub(crate) fn fdb_code(&self) -> Option<i32> {
+        match FdbErrorCode::from(self).0 {
+            -1 => None,
+            code => Some(code),
+        }
+    }
+}
+
 impl From<&Error> for FdbErrorCode {
     fn from(value: &Error) -> Self {
         FdbErrorCode(match value {
@@ -72,6 +161,8 @@ impl From<&Error> for FdbErrorCode {
             Error::APIVersionSingletonViolated => 2201,
             Error::NetworkSingletonViolated => 2009,
             Error::ActionInvalidBeforeNetworkConfig => 2008,
+            Error::TransactionTooOld => 1007,
+            Error::TagThrottled => 1213,
             Error::Generic(i) => i.0,
             _ => -1,
         })
@@ -82,21 +173,33 @@ impl From<&Error> for FdbErrorCode {
 
 #[cfg(test)]
 mod tests {
+    use crate::{CreateTransaction, Error, FdbErrorCode};
+    use crate::testing::TestCluster;
+
+    #[test]
+    fn only_client_errors_have_codes() {
+        assert_eq!(Error::Generic(FdbErrorCode(1020)).fdb_code(), Some(1020));
+        assert_eq!(Error::TransactionTooOld.fdb_code(), Some(1007));
+        assert_eq!(Error::KeyNotFound.fdb_code(), None);
+        assert_eq!(Error::LeaseLost.fdb_code(), None);
+        assert_eq!(Error::TooManyThrottles.fdb_code(), None);
+    }
+
     #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
     async fn test_simple_transaction() {
-        // let client = Client::new().await.unwrap();
-        // let db = client.database().unwrap();
-        // let tx = db.create_transaction().unwrap();
-        //
-        // let empty_get = tx.get("hello").await;
-        // assert_eq!(empty_get, Err(Error::KeyNotFound));
-        //
-        //
-        // tx.set("hello", "world").await;
-        // let existing_get = tx.get("hello").await;
-        // assert_eq!(existing_get, Ok("world".into()));
-        //
-        // tx.clear("hello").await;
-        // tx.commit().await.unwrap();
+        let cluster = TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let tx = db.create_transaction().unwrap();
+
+        let empty_get = tx.get("hello").await;
+        assert_eq!(empty_get, Err(Error::KeyNotFound));
+
+        tx.set("hello", "world").await;
+        let existing_get = tx.get("hello").await;
+        assert_eq!(existing_get, Ok("world".into()));
+
+        tx.clear("hello").await;
+        tx.commit().await.unwrap();
     }
 }
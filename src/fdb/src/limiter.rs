@@ -0,0 +1,198 @@
+//! Backpressure on the network thread.
+//!
+//! libfdb_c drives every transaction of a process from a single network thread. Once it is
+//! saturated, more concurrent transactions only add latency to all of them, until they time out.
+//! `LimitedDatabase` bounds the transactions in flight and adapts the bound to
+//! `Database::get_main_thread_busyness`: it shrinks the limit in proportion to how far the busyness
+//! exceeds `Limiter::target_busyness`, and grows it again while below and transactions are waiting.
+//!
+//! The busyness is updated about once a second, so the limit is adjusted at most that often.
+//! Transactions over the limit wait for one in flight to finish, on any executor.
+
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::sync::Mutex;
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::{Database, Error};
+use crate::transaction::{ReadOnlyTransaction, Transaction};
+
+/// Bounds and target of the limit of a `LimitedDatabase`.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    min: usize,
+    max: usize,
+    target_busyness: f64,
+    interval: Duration,
+}
+
+impl Default for Limiter {
+    /// Between 1 and 1000 transactions in flight, keeping the busyness below 0.8.
+    fn default() -> Self {
+        Limiter { min: 1, max: 1_000, target_busyness: 0.8, interval: Duration::from_secs(1) }
+    }
+}
+
+impl Limiter {
+    /// Transactions in flight the limit never drops below, and never exceeds. Starts at `max`.
+    pub fn concurrency(mut self, min: usize, max: usize) -> Self {
+        self.min = min.max(1);
+        self.max = max.max(self.min);
+        self
+    }
+
+    /// Busyness the limit is adjusted to stay below, 1 meaning saturated.
+    pub fn target_busyness(mut self, busyness: f64) -> Self {
+        self.target_busyness = busyness;
+        self
+    }
+
+    /// Time between adjustments. Shorter than the busyness update interval, the same busyness
+    /// is acted upon several times.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The limit after `limit` with the network thread at `busyness`. Grows only if `waiting`,
+    /// as a limit nobody hits tells nothing about the busyness it would cause.
+    fn adjust(&self, limit: usize, busyness: f64, waiting: bool) -> usize {
+        let next = if busyness > self.target_busyness {
+            // Proportional decrease, at most halving per step
+            let factor = (self.target_busyness / busyness).max(0.5);
+            (limit as f64 * factor) as usize
+        } else if waiting {
+            // Grows by a tenth per step, slower the closer it gets to the target
+            let headroom = 1.0 - busyness / self.target_busyness;
+            limit + ((limit as f64 * 0.1 * headroom).ceil() as usize).max(1)
+        } else {
+            limit
+        };
+        next.clamp(self.min, self.max)
+    }
+}
+
+struct State {
+    limit: usize,
+    in_flight: usize,
+    waiters: VecDeque<Waker>,
+    adjusted_at: Instant,
+}
+
+/// A `Database` whose transactions wait while the network thread is busy, see the module docs.
+pub struct LimitedDatabase<'a> {
+    db: &'a Database,
+    options: Limiter,
+    state: Mutex<State>,
+}
+
+/// Held by a transaction in flight, letting the next one in when dropped.
+struct Permit<'l, 'a>(&'l LimitedDatabase<'a>);
+
+impl Drop for Permit<'_, '_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.in_flight -= 1;
+        // Wakes all, as a woken waiter may have been dropped in the meantime
+        for waker in state.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl<'a> LimitedDatabase<'a> {
+    /// Fails with `Error::Unsupported` if the busyness is not reported by the client library.
+    pub fn new(db: &'a Database, options: Limiter) -> Result<Self, Error> {
+        db.get_main_thread_busyness()?;
+        let state = State { limit: options.max, in_flight: 0, waiters: VecDeque::new(), adjusted_at: Instant::now() };
+        Ok(LimitedDatabase { db, options, state: Mutex::new(state) })
+    }
+
+    /// The database transactions are created on, e.g. to bypass the limit.
+    pub fn database(&self) -> &'a Database {
+        self.db
+    }
+
+    /// Transactions currently allowed in flight.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+
+    /// `Database::run`, once fewer than `limit` transactions are in flight. Retries keep their
+    /// place.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: AsyncFnMut(&mut Transaction) -> Result<T, Error>,
+    {
+        let _permit = self.acquire().await;
+        self.db.run(f).await
+    }
+
+    /// `Database::read`, once fewer than `limit` transactions are in flight.
+    pub async fn read<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: AsyncFnMut(&ReadOnlyTransaction) -> Result<T, Error>,
+    {
+        let _permit = self.acquire().await;
+        self.db.read(f).await
+    }
+
+    async fn acquire(&self) -> Permit<'_, 'a> {
+        poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+            self.adjust(&mut state);
+            if state.in_flight < state.limit {
+                state.in_flight += 1;
+                return Poll::Ready(());
+            }
+            state.waiters.push_back(cx.waker().clone());
+            Poll::Pending
+        }).await;
+        Permit(self)
+    }
+
+    fn adjust(&self, state: &mut State) {
+        if state.adjusted_at.elapsed() < self.options.interval {
+            return;
+        }
+        // Checked to be supported in `new`
+        let busyness = self.db.get_main_thread_busyness().unwrap_or_default();
+        let waiting = !state.waiters.is_empty() || state.in_flight >= state.limit;
+        state.limit = self.options.adjust(state.limit, busyness, waiting);
+        state.adjusted_at = Instant::now();
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::gauge!("fdb_network_thread_busyness").set(busyness);
+            metrics::gauge!("fdb_limiter_limit").set(state.limit as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shrinks_when_busy() {
+        let limiter = Limiter::default().concurrency(10, 100);
+        assert_eq!(limiter.adjust(100, 1.0, false), 80);
+        assert_eq!(limiter.adjust(100, 4.0, true), 50);
+        assert_eq!(limiter.adjust(12, 4.0, true), 10);
+    }
+
+    #[test]
+    fn grows_only_when_waiting() {
+        let limiter = Limiter::default().concurrency(10, 100);
+        assert_eq!(limiter.adjust(50, 0.0, false), 50);
+        assert_eq!(limiter.adjust(50, 0.0, true), 55);
+        assert_eq!(limiter.adjust(50, 0.7, true), 51);
+        assert_eq!(limiter.adjust(99, 0.0, true), 100);
+        assert_eq!(limiter.adjust(50, 0.8, true), 51);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+
+use async_stream::try_stream;
+use futures::{future, pin_mut, SinkExt, Stream, StreamExt};
+use futures::channel::mpsc;
+use futures::future::Either;
+use futures::stream::{FuturesUnordered, SelectAll};
+
+use crate::{Error, TransactionOption};
+use crate::database::Database;
+use crate::scan::{key_after, Scan, ScanConsistency};
+use crate::transaction::{CreateTransaction, Transaction};
+use crate::types::{Key, KeyRange, KeySelector, Value};
+
+/// Prefix of the system keys mapping each shard's begin key to the storage servers holding it.
+const KEY_SERVERS_PREFIX: &[u8] = b"\xff/keyServers/";
+
+/// Lower bound for the size of a single `parallel_scan` shard, so that small ranges are not split
+/// into many tiny transactions.
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+const MIN_SHARD_BYTES: i64 = 1 << 20;
+
+/// Pairs read ahead per shard of a `parallel_scan`, beyond the batch of the `get_range` in flight.
+const SHARD_BUFFER: usize = 1_000;
+
+/// Order in which `Database::parallel_scan` yields the key-value pairs of its shards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOrder {
+    /// Key order, as a single `get_range` would return them. Shards after the one being yielded
+    /// are read ahead until `SHARD_BUFFER` of their pairs are waiting, and count towards the
+    /// concurrency until they are yielded completely.
+    Ordered,
+    /// Pairs are yielded as soon as they are read, interleaving the shards. Pairs within a shard
+    /// stay in key order.
+    Unordered,
+}
+
+fn prefixed(prefix: &[u8], key: &[u8]) -> Key {
+    [prefix, key].concat().into()
+}
+
+impl Database {
+    fn create_system_transaction(&self) -> Result<Transaction, Error> {
+        let tx = self.create_transaction()?;
+        tx.set_option(TransactionOption::ReadSystemKeys)?;
+        tx.set_option(TransactionOption::LockAware)?;
+        Ok(tx)
+    }
+
+    /// Returns the keys at which the shards (contiguous key ranges assigned to the same storage
+    /// servers) inside the range begin.
+    ///
+    /// The boundaries are read from the `\xff/keyServers` system keys. The returned keys only reflect
+    /// the shard layout at the time of reading; data distribution may move boundaries at any time.
+    pub async fn boundary_keys<R: Into<KeyRange>>(&self, range: R) -> Result<Vec<Key>, Error> {
+        let KeyRange { start: mut begin, end } = range.into();
+        let mut boundaries = Vec::new();
+        let mut tx = self.create_system_transaction()?;
+
+        loop {
+            let attempt: Result<(), Error> = async {
+                let range = tx.snapshot().get_range(
+                    KeySelector::first_greater_or_equal(prefixed(KEY_SERVERS_PREFIX, &begin)),
+                    KeySelector::first_greater_or_equal(prefixed(KEY_SERVERS_PREFIX, &end)),
+                    None,
+                    None,
+                    false,
+                );
+                pin_mut!(range);
+
+                while let Some(kv) = range.next().await {
+                    let (key, _) = kv?;
+                    let boundary: Key = key[KEY_SERVERS_PREFIX.len()..].to_vec().into();
+                    begin = key_after(&boundary);
+                    boundaries.push(boundary);
+                }
+                Ok(())
+            }.await;
+
+            match attempt {
+                Ok(()) => return Ok(boundaries),
+                // Continue from the last boundary read in a fresh transaction
+                Err(e) => {
+                    tx.on_error(e).await?;
+                    tx.set_option(TransactionOption::ReadSystemKeys)?;
+                    tx.set_option(TransactionOption::LockAware)?;
+                }
+            }
+        }
+    }
+
+    /// Keys splitting the range into chunks, including its start and end. Chunks are of roughly
+    /// equal size where `get_range_split_points` is available, and shards otherwise.
+    async fn split_points(&self, range: KeyRange, concurrency: usize) -> Result<Vec<Key>, Error> {
+        #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+        if crate::client::supports(700, "fdb_transaction_get_range_split_points") {
+            return self.sized_split_points(range, concurrency).await;
+        }
+        #[cfg(not(any(feature = "730", feature = "710", feature = "700", feature = "dlopen")))]
+        let _ = concurrency;
+
+        self.shard_split_points(range).await
+    }
+
+    /// Keys splitting the range into chunks of roughly equal size, including its start and end.
+    #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+    async fn sized_split_points(&self, range: KeyRange, concurrency: usize) -> Result<Vec<Key>, Error> {
+        let mut tx = self.create_transaction()?;
+        loop {
+            let attempt = async {
+                let size = tx.get_estimated_range_size(range.clone()).await?;
+                // A few shards per worker so that uneven shards even out
+                let chunk_size = (size / (concurrency as i64 * 4)).max(MIN_SHARD_BYTES);
+                tx.get_range_split_points(range.clone(), chunk_size).await
+            }.await;
+
+            match attempt {
+                Ok(points) => return Ok(points.into_iter().collect()),
+                Err(e) => tx.on_error(e).await?,
+            }
+        }
+    }
+
+    /// Keys splitting the range at the shard boundaries, including its start and end.
+    async fn shard_split_points(&self, range: KeyRange) -> Result<Vec<Key>, Error> {
+        let mut points = vec![range.start.clone()];
+        points.extend(self.boundary_keys(range.clone()).await?.into_iter().filter(|k| *k > range.start));
+        points.push(range.end);
+        Ok(points)
+    }
+
+    /// Scans the range with up to `concurrency` transactions in flight.
+    ///
+    /// The range is split into shards using `get_estimated_range_size` and `get_range_split_points`
+    /// (or the shard boundary keys before API version 700, or if the loaded library lacks them). Each shard is read in its own transaction,
+    /// which is transparently continued from the last key read when it exceeds the 5 second
+    /// transaction lifetime. The result is therefore not a consistent snapshot of the whole range.
+    ///
+    /// Shards are streamed rather than read completely first, and the next shard only starts once
+    /// the pairs of one are all yielded, so memory stays bounded by `concurrency` times
+    /// `SHARD_BUFFER` pairs however large the range.
+    pub fn parallel_scan<R: Into<KeyRange>>(
+        &self,
+        range: R,
+        concurrency: usize,
+        order: ScanOrder,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> + '_ {
+        let range = range.into();
+        let concurrency = concurrency.max(1);
+
+        try_stream! {
+            let points = self.split_points(range, concurrency).await?;
+            let mut shards = points.windows(2).map(|w| KeyRange::new(w[0].clone(), w[1].clone()));
+            let mut readers = FuturesUnordered::new();
+            let mut outputs = match order {
+                ScanOrder::Ordered => Outputs::Ordered(VecDeque::new()),
+                ScanOrder::Unordered => Outputs::Unordered(SelectAll::new()),
+            };
+
+            loop {
+                // Shards read completely but not yielded yet hold on to their slot, limiting the
+                // read-ahead of ordered scans
+                while outputs.len() < concurrency {
+                    let Some(shard) = shards.next() else { break };
+                    let (sender, receiver) = mpsc::channel(SHARD_BUFFER);
+                    outputs.push(receiver);
+                    readers.push(read_shard(self.scan(shard, ScanConsistency::Latest), sender));
+                }
+                if readers.is_empty() && outputs.is_empty() {
+                    break;
+                }
+
+                // Drives the readers while waiting for the next pair, and starts the next shard
+                // as soon as one is yielded completely
+                let next = outputs.next();
+                pin_mut!(next);
+                let kv = if readers.is_empty() {
+                    next.await
+                } else {
+                    match future::select(readers.next(), next).await {
+                        Either::Left(_) => continue,
+                        Either::Right((kv, _)) => kv,
+                    }
+                };
+                match kv {
+                    Some(kv) => yield kv?,
+                    // The started shards are yielded, the next ones start above
+                    None => continue,
+                }
+            }
+        }
+    }
+}
+
+type Pairs = mpsc::Receiver<Result<(Key, Value), Error>>;
+
+/// Pairs of the shards being read, in the order `parallel_scan` yields them.
+enum Outputs {
+    Ordered(VecDeque<Pairs>),
+    Unordered(SelectAll<Pairs>),
+}
+
+impl Outputs {
+    fn push(&mut self, pairs: Pairs) {
+        match self {
+            Outputs::Ordered(shards) => shards.push_back(pairs),
+            Outputs::Unordered(shards) => shards.push(pairs),
+        }
+    }
+
+    /// Shards with pairs left to yield, or still being read.
+    fn len(&self) -> usize {
+        match self {
+            Outputs::Ordered(shards) => shards.len(),
+            Outputs::Unordered(shards) => shards.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The next pair, `None` once all shards pushed so far are exhausted.
+    async fn next(&mut self) -> Option<Result<(Key, Value), Error>> {
+        match self {
+            Outputs::Ordered(shards) => {
+                while let Some(shard) = shards.front_mut() {
+                    match shard.next().await {
+                        Some(kv) => return Some(kv),
+                        None => shards.pop_front(),
+                    };
+                }
+                None
+            }
+            Outputs::Unordered(shards) => shards.next().await,
+        }
+    }
+}
+
+/// Reads the shard into `sender`, waiting while `SHARD_BUFFER` pairs are not consumed yet.
+async fn read_shard(mut scan: Scan<'_>, mut sender: mpsc::Sender<Result<(Key, Value), Error>>) {
+    while let Some(kv) = scan.next().await {
+        let failed = kv.is_err();
+        if sender.send(kv).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(key: &str) -> Result<(Key, Value), Error> {
+        Ok((Key::from(key), Value::from(key)))
+    }
+
+    async fn drain(mut outputs: Outputs) -> Vec<Key> {
+        let mut keys = Vec::new();
+        while let Some(kv) = outputs.next().await {
+            keys.push(kv.unwrap().0);
+        }
+        keys
+    }
+
+    #[tokio::test]
+    async fn outputs_follow_the_scan_order() {
+        for ordered in [true, false] {
+            let mut outputs = match ordered {
+                true => Outputs::Ordered(VecDeque::new()),
+                false => Outputs::Unordered(SelectAll::new()),
+            };
+            let (mut first, receiver) = mpsc::channel(SHARD_BUFFER);
+            outputs.push(receiver);
+            let (mut second, receiver) = mpsc::channel(SHARD_BUFFER);
+            outputs.push(receiver);
+            assert_eq!(outputs.len(), 2);
+
+            second.send(pair("c")).await.unwrap();
+            first.send(pair("a")).await.unwrap();
+            second.send(pair("d")).await.unwrap();
+            first.send(pair("b")).await.unwrap();
+            drop((first, second));
+
+            let mut keys = drain(outputs).await;
+            if !ordered {
+                keys.sort();
+            }
+            assert_eq!(keys, ["a", "b", "c", "d"].map(Key::from));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use fdb_c::{FDBNetworkOption, FDBTransactionOption};
+
+/// Options that can be set on a transaction with `Transaction::set_option`.
+///
+/// Options are reset when the transaction is reset after a retryable error, except for
+/// `Timeout`, `RetryLimit` and `MaxRetryDelay`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionOption {
+    /// Allows this transaction to read system keys (those that start with the byte 0xFF).
+    ReadSystemKeys,
+    /// Allows this transaction to read and modify system keys (those that start with the byte 0xFF).
+    AccessSystemKeys,
+    /// The transaction can read and write to locked databases.
+    LockAware,
+    /// The transaction can read from locked databases.
+    ReadLockAware,
+    /// Reads performed by the transaction will not see any prior mutations that occurred in that transaction.
+    ReadYourWritesDisable,
+    /// Snapshot reads will not see the effects of prior writes in the same transaction.
+    SnapshotRywDisable,
+    /// Specifies that this transaction should be treated as low priority and that default priority
+    /// transactions will be processed first.
+    PriorityBatch,
+    /// Set a timeout in milliseconds which, when elapsed, will cause the transaction automatically
+    /// to be cancelled. 0 disables the timeout.
+    Timeout(i64),
+    /// Set a maximum number of retries after which additional calls to `on_error` will throw the
+    /// most recently seen error code. -1 disables the limit.
+    RetryLimit(i64),
+    /// Set the maximum amount of backoff delay incurred in the call to `on_error` in milliseconds.
+    MaxRetryDelay(i64),
+    /// Adds a tag to the transaction, by which it can be throttled with `Database::throttle_tag`
+    /// and is reported in the cluster status. At most 5 tags of up to 16 bytes each, longer tags
+    /// fail with `Error::TagTooLong`.
+    #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "630", feature = "dlopen"))]
+    Tag(String),
+    /// Like `Tag`, and also lets the cluster throttle the tag on its own when its transactions
+    /// overload storage servers.
+    #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "630", feature = "dlopen"))]
+    AutoThrottleTag(String),
+    /// Allows writes to the special key space, the keys starting with `\xff\xff` through which
+    /// tenants and the cluster configuration are managed.
+    #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+    SpecialKeySpaceEnableWrites,
+}
+
+impl TransactionOption {
+    pub(crate) fn code(&self) -> FDBTransactionOption {
+        match self {
+            TransactionOption::ReadSystemKeys => fdb_c::FDBTransactionOption_FDB_TR_OPTION_READ_SYSTEM_KEYS,
+            TransactionOption::AccessSystemKeys => fdb_c::FDBTransactionOption_FDB_TR_OPTION_ACCESS_SYSTEM_KEYS,
+            TransactionOption::LockAware => fdb_c::FDBTransactionOption_FDB_TR_OPTION_LOCK_AWARE,
+            TransactionOption::ReadLockAware => fdb_c::FDBTransactionOption_FDB_TR_OPTION_READ_LOCK_AWARE,
+            TransactionOption::ReadYourWritesDisable => fdb_c::FDBTransactionOption_FDB_TR_OPTION_READ_YOUR_WRITES_DISABLE,
+            TransactionOption::SnapshotRywDisable => fdb_c::FDBTransactionOption_FDB_TR_OPTION_SNAPSHOT_RYW_DISABLE,
+            TransactionOption::PriorityBatch => fdb_c::FDBTransactionOption_FDB_TR_OPTION_PRIORITY_BATCH,
+            TransactionOption::Timeout(_) => fdb_c::FDBTransactionOption_FDB_TR_OPTION_TIMEOUT,
+            TransactionOption::RetryLimit(_) => fdb_c::FDBTransactionOption_FDB_TR_OPTION_RETRY_LIMIT,
+            TransactionOption::MaxRetryDelay(_) => fdb_c::FDBTransactionOption_FDB_TR_OPTION_MAX_RETRY_DELAY,
+            #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "630", feature = "dlopen"))]
+            TransactionOption::Tag(_) => fdb_c::FDBTransactionOption_FDB_TR_OPTION_TAG,
+            #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "630", feature = "dlopen"))]
+            TransactionOption::AutoThrottleTag(_) => fdb_c::FDBTransactionOption_FDB_TR_OPTION_AUTO_THROTTLE_TAG,
+            #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+            TransactionOption::SpecialKeySpaceEnableWrites => fdb_c::FDBTransactionOption_FDB_TR_OPTION_SPECIAL_KEY_SPACE_ENABLE_WRITES,
+        }
+    }
+
+    /// Fails for values the client would reject, before they are passed to it.
+    pub(crate) fn check(&self) -> Result<(), crate::Error> {
+        match self {
+            #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "630", feature = "dlopen"))]
+            TransactionOption::Tag(tag) | TransactionOption::AutoThrottleTag(tag) => crate::throttle::check_tag(tag),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parameter of the option as expected by `fdb_transaction_set_option`.
+    /// Integer parameters are passed as 64-bit little-endian values.
+    pub(crate) fn value(&self) -> Option<Vec<u8>> {
+        match self {
+            TransactionOption::Timeout(v)
+            | TransactionOption::RetryLimit(v)
+            | TransactionOption::MaxRetryDelay(v) => Some(v.to_le_bytes().to_vec()),
+            #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "630", feature = "dlopen"))]
+            TransactionOption::Tag(tag) | TransactionOption::AutoThrottleTag(tag) => Some(tag.as_bytes().to_vec()),
+            _ => None,
+        }
+    }
+}
+
+
+/// Options of the network, set through `ClientBuilder` before the network is set up.
+///
+/// The external client options enable the multi-version client: every external library is loaded
+/// alongside the primary one, and each connection uses whichever library speaks the cluster's
+/// protocol. This keeps a client working while the cluster is upgraded between versions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkOption {
+    /// Adds an external client library for use by the multi-version client API.
+    ExternalClientLibrary(PathBuf),
+    /// Searches the directory for libraries to be used by the multi-version client API.
+    ExternalClientDirectory(PathBuf),
+    /// Prevents connections through the local client, allowing only connections through externally
+    /// loaded client libraries.
+    DisableLocalClient,
+}
+
+impl NetworkOption {
+    pub(crate) fn code(&self) -> FDBNetworkOption {
+        match self {
+            NetworkOption::ExternalClientLibrary(_) => fdb_c::FDBNetworkOption_FDB_NET_OPTION_EXTERNAL_CLIENT_LIBRARY,
+            NetworkOption::ExternalClientDirectory(_) => fdb_c::FDBNetworkOption_FDB_NET_OPTION_EXTERNAL_CLIENT_DIRECTORY,
+            NetworkOption::DisableLocalClient => fdb_c::FDBNetworkOption_FDB_NET_OPTION_DISABLE_LOCAL_CLIENT,
+        }
+    }
+
+    /// Parameter of the option as expected by `fdb_network_set_option`.
+    pub(crate) fn value(&self) -> Option<Vec<u8>> {
+        match self {
+            NetworkOption::ExternalClientLibrary(path)
+            | NetworkOption::ExternalClientDirectory(path) => Some(path.to_string_lossy().into_owned().into_bytes()),
+            NetworkOption::DisableLocalClient => None,
+        }
+    }
+}
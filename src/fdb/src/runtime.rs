@@ -0,0 +1,19 @@
+//! Runtime-agnostic helpers.
+//!
+//! The bindings never spawn tasks: `FDBFuture`s are woken from the network thread, so they can be
+//! awaited on any executor. The few places that need a timer go through this module, which uses
+//! tokio's timer when the `tokio` feature is enabled and `futures-timer` otherwise.
+
+use std::time::Duration;
+
+/// Waits until `duration` has elapsed.
+#[cfg(feature = "tokio")]
+pub async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+/// Waits until `duration` has elapsed.
+#[cfg(not(feature = "tokio"))]
+pub async fn sleep(duration: Duration) {
+    futures_timer::Delay::new(duration).await
+}
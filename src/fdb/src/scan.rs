@@ -0,0 +1,146 @@
+use std::cell::Cell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use async_stream::try_stream;
+use futures::{pin_mut, Stream, StreamExt};
+use futures::stream::LocalBoxStream;
+
+use crate::Error;
+use crate::database::Database;
+use crate::transaction::CreateTransaction;
+use crate::types::{Key, KeyRange, KeySelector, Value};
+
+/// Read version used when `Database::scan` continues in a new transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanConsistency {
+    /// Every transaction uses the latest read version. Any continuation loses consistency.
+    Latest,
+    /// Pin the read version of the first transaction for as long as the storage servers still
+    /// serve it, then fall back to the latest read version.
+    Pinned,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ScanState {
+    read_version: Option<i64>,
+    consistent: bool,
+    transactions: usize,
+}
+
+/// Smallest key strictly greater than `key`.
+pub(crate) fn key_after(key: &[u8]) -> Key {
+    let mut next = key.to_vec();
+    next.push(0);
+    next.into()
+}
+
+/// Stream of the key-value pairs of a range, read across as many transactions as needed.
+///
+/// Created with `Database::scan`.
+pub struct Scan<'a> {
+    inner: LocalBoxStream<'a, Result<(Key, Value), Error>>,
+    state: Rc<Cell<ScanState>>,
+}
+
+impl Scan<'_> {
+    /// Whether every pair returned so far was read at the same read version.
+    pub fn is_consistent(&self) -> bool {
+        self.state.get().consistent
+    }
+
+    /// The pinned read version, while it is still in use.
+    pub fn read_version(&self) -> Option<i64> {
+        self.state.get().read_version
+    }
+
+    /// Number of transactions used so far.
+    pub fn transactions(&self) -> usize {
+        self.state.get().transactions
+    }
+}
+
+impl Stream for Scan<'_> {
+    type Item = Result<(Key, Value), Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl Database {
+    /// Reads the range in key order without being limited by the 5 second transaction lifetime.
+    ///
+    /// Whenever a read fails with a retryable error, most notably `Error::TransactionTooOld`, the
+    /// transaction is reset and the scan continues right after the last key returned.
+    /// With `ScanConsistency::Pinned` the continuation keeps the original read version until it
+    /// is no longer available; `Scan::is_consistent` reports whether that was the case for the whole
+    /// scan so far.
+    pub fn scan<R: Into<KeyRange>>(&self, range: R, consistency: ScanConsistency) -> Scan<'_> {
+        let KeyRange { start: mut begin, end } = range.into();
+        let state = Rc::new(Cell::new(ScanState { read_version: None, consistent: true, transactions: 0 }));
+        let shared = state.clone();
+
+        let inner = try_stream! {
+            let mut tx = self.create_transaction()?;
+            let mut returned_any = false;
+
+            loop {
+                let mut s = shared.get();
+                s.transactions += 1;
+                match s.read_version {
+                    Some(version) => tx.set_read_version(version),
+                    None if consistency == ScanConsistency::Pinned && s.consistent => {
+                        s.read_version = Some(tx.get_read_version().await?);
+                    }
+                    None => {}
+                }
+                shared.set(s);
+
+                let mut failure = None;
+                {
+                    let range = tx.snapshot().get_range(
+                        KeySelector::first_greater_or_equal(begin.clone()),
+                        KeySelector::first_greater_or_equal(end.clone()),
+                        None,
+                        None,
+                        false,
+                    );
+                    pin_mut!(range);
+
+                    while let Some(kv) = range.next().await {
+                        match kv {
+                            Ok((key, value)) => {
+                                begin = key_after(&key);
+                                returned_any = true;
+                                yield (key, value);
+                            }
+                            Err(e) => {
+                                failure = Some(e);
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                match failure {
+                    None => break,
+                    Some(e) => {
+                        let mut s = shared.get();
+                        if s.read_version.is_none() || e == Error::TransactionTooOld {
+                            s.read_version = None;
+                            // Nothing read yet means nothing to be inconsistent with
+                            s.consistent &= !returned_any;
+                        }
+                        shared.set(s);
+
+                        tx.on_error(e).await?;
+                    }
+                }
+            }
+        };
+
+        Scan { inner: inner.boxed_local(), state }
+    }
+}
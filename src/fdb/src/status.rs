@@ -0,0 +1,149 @@
+use serde_json::Value;
+
+use crate::Error;
+
+/// The client's view of the cluster, see `Database::get_client_status`.
+///
+/// Fields missing from the report, e.g. because the client is still connecting, are left empty.
+/// The full report is kept in `json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientStatus {
+    /// Whether the client is initialized and connected to all the servers it needs.
+    pub healthy: bool,
+    /// State of the multi-version client, e.g. `created` or `initialized`.
+    pub initialization_state: String,
+    pub initialization_error: Option<i64>,
+    /// Connection string of the cluster file the database was opened with.
+    pub connection_record: String,
+    pub coordinators: Vec<String>,
+    pub current_coordinator: Option<String>,
+    pub grv_proxies: Vec<String>,
+    pub commit_proxies: Vec<String>,
+    pub storage_servers: Vec<String>,
+    pub connections: Vec<ConnectionStatus>,
+    pub json: String,
+}
+
+/// A connection of the client to a server of the cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStatus {
+    pub address: String,
+    /// `connected`, `connecting`, `disconnected` or `failed`.
+    pub status: String,
+    /// Whether the server runs a protocol version the client speaks.
+    pub compatible: bool,
+    pub connect_failed_count: u64,
+    pub ping_count: u64,
+    pub ping_timeout_count: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+
+fn string(value: &Value, field: &str) -> Option<String> {
+    value.get(field)?.as_str().map(str::to_owned)
+}
+
+fn strings(value: &Value, field: &str) -> Vec<String> {
+    let values = value.get(field).and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+    values.iter().filter_map(Value::as_str).map(str::to_owned).collect()
+}
+
+fn number(value: &Value, field: &str) -> u64 {
+    value.get(field).and_then(Value::as_u64).unwrap_or_default()
+}
+
+impl ConnectionStatus {
+    fn parse(value: &Value) -> Self {
+        ConnectionStatus {
+            address: string(value, "Address").unwrap_or_default(),
+            status: string(value, "Status").unwrap_or_default(),
+            compatible: value.get("Compatible").and_then(Value::as_bool).unwrap_or_default(),
+            connect_failed_count: number(value, "ConnectFailedCount"),
+            ping_count: number(value, "PingCount"),
+            ping_timeout_count: number(value, "PingTimeoutCount"),
+            bytes_received: number(value, "BytesReceived"),
+            bytes_sent: number(value, "BytesSent"),
+        }
+    }
+}
+
+impl ClientStatus {
+    /// Reads the report of the multi-version client, which embeds the one of the database it
+    /// currently uses under `DatabaseStatus`.
+    pub(crate) fn parse(json: &[u8]) -> Result<Self, Error> {
+        let report: Value = serde_json::from_slice(json).map_err(|e| Error::ClientStatus(e.to_string()))?;
+        let database = report.get("DatabaseStatus").unwrap_or(&Value::Null);
+        let connections = database.get("Connections").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+
+        Ok(ClientStatus {
+            healthy: report.get("Healthy").and_then(Value::as_bool).unwrap_or_default()
+                && database.get("Healthy").and_then(Value::as_bool).unwrap_or(true),
+            initialization_state: string(&report, "InitializationState").unwrap_or_default(),
+            initialization_error: report.get("InitializationError").and_then(Value::as_i64),
+            connection_record: string(&report, "ConnectionRecord").unwrap_or_default(),
+            coordinators: strings(database, "Coordinators"),
+            current_coordinator: string(database, "CurrentCoordinator"),
+            grv_proxies: strings(database, "GrvProxies"),
+            commit_proxies: strings(database, "CommitProxies"),
+            storage_servers: strings(database, "StorageServers"),
+            connections: connections.iter().map(ConnectionStatus::parse).collect(),
+            json: String::from_utf8_lossy(json).into_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_reports() {
+        let json = br#"{
+            "Healthy": true,
+            "InitializationState": "initialized",
+            "ConnectionRecord": "test:abc@127.0.0.1:4500",
+            "ProtocolVersion": "fdb00b073000000",
+            "DatabaseStatus": {
+                "Healthy": true,
+                "Coordinators": ["127.0.0.1:4500"],
+                "CurrentCoordinator": "127.0.0.1:4500",
+                "GrvProxies": ["127.0.0.1:4500"],
+                "CommitProxies": ["127.0.0.1:4500"],
+                "StorageServers": [],
+                "Connections": [{
+                    "Address": "127.0.0.1:4500",
+                    "Status": "connected",
+                    "Compatible": true,
+                    "ConnectFailedCount": 0,
+                    "PingCount": 12,
+                    "PingTimeoutCount": 1,
+                    "BytesReceived": 2048,
+                    "BytesSent": 1024
+                }]
+            }
+        }"#;
+        let status = ClientStatus::parse(json).unwrap();
+        assert!(status.healthy);
+        assert_eq!(status.initialization_state, "initialized");
+        assert_eq!(status.coordinators, vec!["127.0.0.1:4500"]);
+        assert_eq!(status.current_coordinator.as_deref(), Some("127.0.0.1:4500"));
+        assert_eq!(status.connections, vec![ConnectionStatus {
+            address: "127.0.0.1:4500".into(),
+            status: "connected".into(),
+            compatible: true,
+            connect_failed_count: 0,
+            ping_count: 12,
+            ping_timeout_count: 1,
+            bytes_received: 2048,
+            bytes_sent: 1024,
+        }]);
+    }
+
+    #[test]
+    fn missing_database_status_is_unhealthy() {
+        let status = ClientStatus::parse(br#"{"Healthy": false, "InitializationState": "created"}"#).unwrap();
+        assert!(!status.healthy);
+        assert!(status.connections.is_empty());
+        assert!(ClientStatus::parse(b"not json").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Opt-in instrumentation of transactions.
+//!
+//! With the `tracing` feature every transaction gets an `fdb.transaction` span recording its read
+//! version, retries, conflicts, commit latency and approximate size, and `get`, `get_range` and
+//! `commit` emit debug events inside it.
+//!
+//! With the `metrics` feature the following are recorded through the `metrics` facade, so any
+//! installed recorder (e.g. a Prometheus exporter) picks them up:
+//!
+//! | Name                                       | Type      | Labels |
+//! |--------------------------------------------|-----------|--------|
+//! | `fdb_transaction_retries_total`            | counter   | `code` |
+//! | `fdb_transaction_conflicts_total`          | counter   |        |
+//! | `fdb_transaction_commits_total`            | counter   |        |
+//! | `fdb_transaction_commit_latency_seconds`   | histogram |        |
+//! | `fdb_transaction_read_latency_seconds`     | histogram | `op`   |
+//! | `fdb_transaction_bytes_read_total`         | counter   |        |
+//! | `fdb_transaction_bytes_written_total`      | counter   |        |
+//! | `fdb_transaction_size_bytes`               | histogram |        |
+//! | `fdb_cache_hits_total`                     | counter   |        |
+//! | `fdb_cache_misses_total`                   | counter   |        |
+//! | `fdb_cache_invalidations_total`            | counter   |        |
+//! | `fdb_cache_evictions_total`                | counter   |        |
+//! | `fdb_network_thread_busyness`              | gauge     |        |
+//! | `fdb_limiter_limit`                        | gauge     |        |
+//!
+//! Bytes written are the approximate sizes of committed transactions, as reported by
+//! `get_approximate_size` (mutations plus conflict ranges). Fetching them costs a call into the
+//! client per commit, so they are only recorded after opting in with
+//! `ClientBuilder::record_commit_sizes`. The cache counters are recorded by
+//! `cache::CachedDatabase`, the gauges by `limiter::LimitedDatabase` when it adjusts its limit.
+//!
+//! Without either feature all of this compiles to nothing.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::Error;
+
+/// Error code of `not_committed`, returned when a transaction conflicts with another one.
+const NOT_COMMITTED: i32 = 1020;
+
+/// Set by `ClientBuilder::record_commit_sizes`.
+static COMMIT_SIZES: AtomicBool = AtomicBool::new(false);
+
+/// Fetches the approximate size before every commit from now on, for the span and metrics.
+pub(crate) fn record_commit_sizes() {
+    COMMIT_SIZES.store(true, Ordering::Relaxed);
+}
+
+/// Code reported for a failed read. A missing key is an ordinary outcome of `get`, not an error.
+#[cfg(any(test, feature = "tracing"))]
+fn read_error(error: &Error) -> Option<i32> {
+    match error {
+        Error::KeyNotFound => None,
+        e => Some(crate::FdbErrorCode::from(e).0),
+    }
+}
+
+pub(crate) struct Telemetry {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    retries: u64,
+    conflicts: u64,
+}
+
+impl Telemetry {
+    pub(crate) fn new() -> Self {
+        Telemetry {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "fdb.transaction",
+                read_version = tracing::field::Empty,
+                retries = 0u64,
+                conflicts = 0u64,
+                commit_latency_ms = tracing::field::Empty,
+                approximate_size = tracing::field::Empty,
+            ),
+            retries: 0,
+            conflicts: 0,
+        }
+    }
+
+    pub(crate) fn read_version(&self, _version: i64) {
+        #[cfg(feature = "tracing")]
+        self.span.record("read_version", _version);
+    }
+
+    /// Whether commit sizes were opted into and something records them: the transaction span of
+    /// an enabled subscriber, or the metrics. Fetching the size is skipped otherwise.
+    pub(crate) fn wants_approximate_size(&self) -> bool {
+        #[cfg(feature = "tracing")]
+        let traced = !self.span.is_disabled();
+        #[cfg(not(feature = "tracing"))]
+        let traced = false;
+        COMMIT_SIZES.load(Ordering::Relaxed) && (traced || cfg!(feature = "metrics"))
+    }
+
+    /// A read of `op` finished after `started`, returning `bytes` bytes of keys and values.
+    pub(crate) fn read(&self, _op: &'static str, _started: Instant, _bytes: usize, _error: Option<&Error>) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            parent: &self.span,
+            op = _op,
+            bytes = _bytes,
+            latency_us = _started.elapsed().as_micros() as u64,
+            error = _error.and_then(read_error),
+        );
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::histogram!("fdb_transaction_read_latency_seconds", "op" => _op).record(_started.elapsed());
+            metrics::counter!("fdb_transaction_bytes_read_total").increment(_bytes as u64);
+        }
+    }
+
+    /// `on_error` was called with `code`; `retry` tells whether the transaction is going to be retried.
+    pub(crate) fn on_error(&mut self, code: i32, retry: bool) {
+        if !retry {
+            return;
+        }
+        self.retries += 1;
+        if code == NOT_COMMITTED {
+            self.conflicts += 1;
+        }
+
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("retries", self.retries);
+            self.span.record("conflicts", self.conflicts);
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("fdb_transaction_retries_total", "code" => code.to_string()).increment(1);
+            if code == NOT_COMMITTED {
+                metrics::counter!("fdb_transaction_conflicts_total").increment(1);
+            }
+        }
+    }
+
+    pub(crate) fn commit(&self, _latency: Duration, _approximate_size: Option<i64>, _error: Option<i32>) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("commit_latency_ms", _latency.as_secs_f64() * 1000.0);
+            if let Some(size) = _approximate_size {
+                self.span.record("approximate_size", size);
+            }
+            tracing::debug!(parent: &self.span, op = "commit", latency_us = _latency.as_micros() as u64, error = _error);
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::histogram!("fdb_transaction_commit_latency_seconds").record(_latency);
+            if _error.is_none() {
+                metrics::counter!("fdb_transaction_commits_total").increment(1);
+                if let Some(size) = _approximate_size {
+                    metrics::counter!("fdb_transaction_bytes_written_total").increment(size as u64);
+                    metrics::histogram!("fdb_transaction_size_bytes").record(size as f64);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_keys_are_no_errors() {
+        assert_eq!(read_error(&Error::KeyNotFound), None);
+        assert_eq!(read_error(&Error::TransactionTooOld), Some(1007));
+    }
+
+    #[test]
+    fn counts_retries_and_conflicts() {
+        let mut telemetry = Telemetry::new();
+        telemetry.on_error(NOT_COMMITTED, true);
+        telemetry.on_error(1007, true);
+        telemetry.on_error(NOT_COMMITTED, false);
+        assert_eq!((telemetry.retries, telemetry.conflicts), (2, 1));
+    }
+
+    #[test]
+    fn fetches_the_size_only_when_enabled() {
+        assert!(!Telemetry::new().wants_approximate_size());
+        record_commit_sizes();
+        assert_eq!(Telemetry::new().wants_approximate_size(), cfg!(feature = "metrics"));
+    }
+}
@@ -1,21 +1,63 @@
 use std::ptr;
 
+use futures::Stream;
 use log::error;
 
 use fdb_c::FDBTenant;
 
-use crate::{Error, FdbErrorCode};
-use crate::transaction::{CreateTransaction, Transaction};
+use crate::{BulkLoad, Error, FdbErrorCode, LoadProgress};
+use crate::transaction::{CreateTransaction, ReadOnlyTransaction, Transaction};
+use crate::types::{Key, Value};
 
 
-pub struct Tenant(*mut FDBTenant);
+/// Handle to a tenant, opened with `Database::tenant`.
+pub struct Tenant(*mut FDBTenant, String);
 
-impl From<*mut FDBTenant> for Tenant {
-    fn from(value: *mut FDBTenant) -> Self {
-        Tenant(value)
+impl Tenant {
+    pub(crate) fn new(tenant: *mut FDBTenant, name: &str) -> Self {
+        Tenant(tenant, name.to_string())
+    }
+
+    /// Prefix of the special keys mapping each tenant to its metadata, through which tenants are
+    /// listed, created and deleted. Moved with API version 720.
+    pub fn map_prefix() -> &'static [u8] {
+        match crate::client::api_version() {
+            ..720 => b"\xff\xff/management/tenant_map/",
+            _ => b"\xff\xff/management/tenant/map/",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.1
+    }
+
+    /// `Database::run` in the tenant.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: AsyncFnMut(&mut Transaction) -> Result<T, Error>,
+    {
+        crate::database::run(self, f).await
     }
-}
 
+    /// `Database::read` in the tenant.
+    pub async fn read<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: AsyncFnMut(&ReadOnlyTransaction) -> Result<T, Error>,
+    {
+        crate::database::read(self, f).await
+    }
+
+    /// `Database::bulk_load` in the tenant.
+    pub async fn bulk_load<S, K, V, P>(&self, kvs: S, options: BulkLoad, on_progress: P) -> Result<LoadProgress, Error>
+    where
+        S: Stream<Item=(K, V)>,
+        K: Into<Key>,
+        V: Into<Value>,
+        P: FnMut(&LoadProgress),
+    {
+        crate::bulk::bulk_load(self, kvs, options, on_progress).await
+    }
+}
 
 impl CreateTransaction for Tenant {
     fn create_transaction(&self) -> Result<Transaction, Error> {
@@ -29,6 +71,10 @@ impl CreateTransaction for Tenant {
 
         Ok(trx.into())
     }
+
+    fn tenant_name(&self) -> Option<&str> {
+        Some(&self.1)
+    }
 }
 
 
@@ -0,0 +1,203 @@
+//! Throwaway FoundationDB clusters for integration tests.
+//!
+//! `TestCluster::start` runs an `fdbserver` process on a free local port, with its data, logs and
+//! cluster file in a temporary directory, and configures it as a `single memory` database. The
+//! process is killed and the directory removed when the cluster is dropped.
+//!
+//! Requires FoundationDB to be installed: `fdbserver` and `fdbcli` are looked up in the
+//! `FDBSERVER` and `FDBCLI` environment variables, the `PATH` and the default install locations.
+//! The tests of this crate using it are `#[ignore]`d, run them with `cargo test -- --ignored`.
+//! Other tests can check `installed` first.
+
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::{Client, Database, Error, TransactionOption};
+use crate::transaction::Transaction;
+use crate::types::Subspace;
+#[cfg(any(feature = "730", feature = "710", feature = "dlopen"))]
+use crate::Tenant;
+
+/// Directories FoundationDB's packages install their binaries to.
+const INSTALL_DIRS: [&str; 4] = ["/usr/sbin", "/usr/bin", "/usr/local/bin", "/usr/local/libexec"];
+
+/// Seconds `fdbcli` waits for the cluster, and transactions checking that it is available.
+const TIMEOUT_SECS: i64 = 30;
+
+static CLUSTERS: AtomicUsize = AtomicUsize::new(0);
+
+fn failed<T>(reason: impl Into<String>) -> Result<T, Error> {
+    Err(Error::TestCluster(reason.into()))
+}
+
+/// Finds the binary `name`, at the path in the environment variable `var` if it is set.
+fn find(name: &str, var: &str) -> Option<PathBuf> {
+    if let Some(path) = env::var_os(var) {
+        return Some(PathBuf::from(path));
+    }
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path)
+        .chain(INSTALL_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Whether `fdbserver` and `fdbcli` can be found.
+pub fn installed() -> bool {
+    find("fdbserver", "FDBSERVER").is_some() && find("fdbcli", "FDBCLI").is_some()
+}
+
+/// The client shared by all test clusters of the process. It is never dropped, as dropping a
+/// client stops the network, which cannot be started again.
+fn client() -> Result<&'static Client, Error> {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    static INIT: Mutex<()> = Mutex::new(());
+
+    let _guard = INIT.lock().unwrap();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let client = Client::new()?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+/// A local port nothing listens on at the moment. Another process may take it before the server
+/// binds it, which is unlikely enough for tests.
+fn free_port() -> Result<u16, Error> {
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| Error::TestCluster(e.to_string()))?;
+    listener.local_addr().map(|a| a.port()).map_err(|e| Error::TestCluster(e.to_string()))
+}
+
+/// A single process FoundationDB cluster, torn down on drop.
+pub struct TestCluster {
+    server: Child,
+    dir: PathBuf,
+    cluster_file: PathBuf,
+    fdbcli: PathBuf,
+    /// Held while enabling tenants, so that concurrent `tenant` calls wait for the first one
+    #[cfg(any(feature = "730", feature = "710", feature = "dlopen"))]
+    tenants_enabled: Mutex<bool>,
+}
+
+impl TestCluster {
+    /// Starts `fdbserver`, creates a `single memory` database and waits until it is available.
+    ///
+    /// `fdbcli` is run synchronously, blocking the calling thread for the few seconds the
+    /// database takes to be created.
+    pub async fn start() -> Result<Self, Error> {
+        let Some(fdbserver) = find("fdbserver", "FDBSERVER") else {
+            return failed("fdbserver is not installed, or set FDBSERVER to its path");
+        };
+        let Some(fdbcli) = find("fdbcli", "FDBCLI") else {
+            return failed("fdbcli is not installed, or set FDBCLI to its path");
+        };
+
+        let id = format!("{}-{}", std::process::id(), CLUSTERS.fetch_add(1, Ordering::Relaxed));
+        let dir = env::temp_dir().join(format!("fdb-test-{id}"));
+        let (data, logs) = (dir.join("data"), dir.join("logs"));
+        for d in [&data, &logs] {
+            fs::create_dir_all(d).map_err(|e| Error::TestCluster(format!("{}: {e}", d.display())))?;
+        }
+
+        let address = format!("127.0.0.1:{}", free_port()?);
+        let cluster_file = dir.join("fdb.cluster");
+        let secret = format!("{:016x}", rand::random::<u64>());
+        fs::write(&cluster_file, format!("test:{secret}@{address}\n")).map_err(|e| Error::TestCluster(e.to_string()))?;
+
+        let server = Command::new(&fdbserver)
+            .arg("--public-address").arg(&address)
+            .arg("--listen-address").arg(&address)
+            .arg("--cluster-file").arg(&cluster_file)
+            .arg("--datadir").arg(&data)
+            .arg("--logdir").arg(&logs)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| Error::TestCluster(format!("{}: {e}", fdbserver.display())))?;
+
+        // Constructed right away, so that the server is stopped if configuring fails
+        let cluster = TestCluster {
+            server,
+            dir,
+            cluster_file,
+            fdbcli,
+            #[cfg(any(feature = "730", feature = "710", feature = "dlopen"))]
+            tenants_enabled: Mutex::new(false),
+        };
+        cluster.fdbcli("configure new single memory")?;
+
+        let db = cluster.database()?;
+        db.run(async |tx: &mut Transaction| {
+            tx.set_option(TransactionOption::Timeout(TIMEOUT_SECS * 1000))?;
+            tx.get_read_version().await
+        }).await?;
+        Ok(cluster)
+    }
+
+    /// Runs an `fdbcli` command against the cluster.
+    fn fdbcli(&self, command: &str) -> Result<(), Error> {
+        let output = Command::new(&self.fdbcli)
+            .arg("--cluster-file").arg(&self.cluster_file)
+            .arg("--timeout").arg(TIMEOUT_SECS.to_string())
+            .arg("--exec").arg(command)
+            .output()
+            .map_err(|e| Error::TestCluster(format!("{}: {e}", self.fdbcli.display())))?;
+
+        if !output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            return failed(format!("fdbcli --exec {command:?} failed: {}", stdout.trim()));
+        }
+        Ok(())
+    }
+
+    pub fn cluster_file(&self) -> &Path {
+        &self.cluster_file
+    }
+
+    /// Opens the database of the cluster.
+    pub fn database(&self) -> Result<Database, Error> {
+        client()?.database_from(&self.cluster_file)
+    }
+
+    /// A subspace no other call returns, to keep the keys of a test apart from other tests
+    /// sharing the cluster.
+    pub fn subspace(&self) -> Subspace {
+        static SUBSPACES: AtomicUsize = AtomicUsize::new(0);
+        Subspace::new(format!("test-{}/", SUBSPACES.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    /// Creates the tenant `name` and opens it. Tenants are enabled on the cluster the first time.
+    #[cfg(any(feature = "730", feature = "710", feature = "dlopen"))]
+    pub async fn tenant(&self, name: &str) -> Result<Tenant, Error> {
+        {
+            // Only marked as enabled once `fdbcli` succeeded, so that a failure is retried
+            let mut enabled = self.tenants_enabled.lock().unwrap();
+            if !*enabled {
+                self.fdbcli("configure tenant_mode=optional_experimental")?;
+                *enabled = true;
+            }
+        }
+
+        let db = self.database()?;
+        let key = [Tenant::map_prefix(), name.as_bytes()].concat();
+        db.run(async |tx: &mut Transaction| {
+            tx.set_option(TransactionOption::SpecialKeySpaceEnableWrites)?;
+            tx.set(key.clone(), "").await;
+            Ok(())
+        }).await?;
+        db.tenant(name)
+    }
+}
+
+impl Drop for TestCluster {
+    fn drop(&mut self) {
+        let _ = self.server.kill();
+        let _ = self.server.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,254 @@
+use std::time::Duration;
+
+use futures::TryStreamExt;
+
+use crate::{Database, Error, TransactionOption};
+use crate::layers::{decode_i64, get_optional};
+use crate::transaction::{ReadOnlyTransaction, Transaction};
+use crate::types::{Key, KeySelector, Value};
+
+/// Throttles of transaction tags, one key per tag, read by the ratekeeper.
+const THROTTLES: &[u8] = b"\xff\x02/throttledTags/tag/";
+const THROTTLES_END: &[u8] = b"\xff\x02/throttledTags/tag0";
+
+/// Number of manual throttles, maintained by `fdbcli` alongside the throttles.
+const MANUAL_COUNT: &[u8] = b"\xff\x02/throttledTags/manualThrottleCount";
+
+/// Most manual throttles `fdbcli` allows at the same time.
+const MAX_MANUAL_THROTTLES: i64 = 40;
+
+/// Longest transaction tag the client accepts.
+const MAX_TAG_LENGTH: usize = 16;
+
+/// Versionstamped on every change of a manual throttle, for the ratekeeper to pick it up.
+const SIGNAL: &[u8] = b"\xff\x02/throttledTags/signal";
+
+/// Protocol version prefixing throttle values, the one introducing `ThrottleReason`.
+const VALUE_VERSION: u64 = 0x0FDB00B063010000;
+
+/// Priority of the transactions a throttle applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagPriority {
+    Batch,
+    Default,
+    Immediate,
+}
+
+/// Why a tag is throttled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleReason {
+    Unset,
+    /// Throttled by `Database::throttle_tag` or `fdbcli`.
+    Manual,
+    /// Throttled by the cluster, because of reads overloading a storage server.
+    BusyRead,
+    /// Throttled by the cluster, because of writes overloading a storage server.
+    BusyWrite,
+}
+
+/// A throttle of a transaction tag, see `Database::throttled_tags`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThrottledTag {
+    pub tag: String,
+    /// Whether the cluster throttled the tag on its own, see `TransactionOption::AutoThrottleTag`.
+    pub automatic: bool,
+    pub priority: TagPriority,
+    pub reason: ThrottleReason,
+    /// Transactions per second the tag is limited to.
+    pub rate: f64,
+    /// Time the throttle expires at, in seconds on the cluster's clock. 0 until the ratekeeper
+    /// picked up a new throttle.
+    pub expiration: f64,
+    /// How long the throttle lasts.
+    pub duration: Duration,
+}
+
+/// Fails for tags the client would reject, as `TransactionOption::Tag` or in a throttle.
+pub(crate) fn check_tag(tag: &str) -> Result<(), Error> {
+    if tag.len() > MAX_TAG_LENGTH {
+        return Err(Error::TagTooLong(tag.to_string()));
+    }
+    Ok(())
+}
+
+fn key(tag: &str, automatic: bool, priority: TagPriority) -> Result<Key, Error> {
+    check_tag(tag)?;
+    let priority = match priority {
+        TagPriority::Batch => 0,
+        TagPriority::Default => 1,
+        TagPriority::Immediate => 2,
+    };
+    // The tag is encoded as a set of tags holding just it
+    Ok([THROTTLES, &[automatic as u8, priority, 1, tag.len() as u8], tag.as_bytes()].concat().into())
+}
+
+fn value(rate: f64, duration: Duration, reason: ThrottleReason) -> Result<Value, Error> {
+    if !(rate.is_finite() && rate > 0.0) {
+        return Err(Error::InvalidThrottleRate);
+    }
+    let reason = match reason {
+        ThrottleReason::Unset => 0u8,
+        ThrottleReason::Manual => 1,
+        ThrottleReason::BusyRead => 2,
+        ThrottleReason::BusyWrite => 3,
+    };
+    let mut value = VALUE_VERSION.to_le_bytes().to_vec();
+    for x in [rate, 0.0, duration.as_secs_f64()] {
+        value.extend(x.to_le_bytes());
+    }
+    value.push(reason);
+    Ok(value.into())
+}
+
+fn f64_at(bytes: &[u8], at: usize) -> Option<f64> {
+    Some(f64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Decodes a throttle as written by the ratekeeper and `fdbcli`, `None` if the format is unknown.
+fn decode(key: &[u8], value: &[u8]) -> Option<ThrottledTag> {
+    let rest = key.strip_prefix(THROTTLES)?;
+    let (automatic, priority, count, len) = (*rest.first()?, *rest.get(1)?, *rest.get(2)?, *rest.get(3)? as usize);
+    // Throttles of several tags at once are not written by the cluster or `fdbcli`
+    if count != 1 {
+        return None;
+    }
+    let tag = String::from_utf8_lossy(rest.get(4..4 + len)?).into_owned();
+
+    // Values older than the reason end after the three doubles
+    let reason = match value.get(32) {
+        None | Some(0) => ThrottleReason::Unset,
+        Some(1) => ThrottleReason::Manual,
+        Some(2) => ThrottleReason::BusyRead,
+        Some(3) => ThrottleReason::BusyWrite,
+        Some(_) => return None,
+    };
+    Some(ThrottledTag {
+        tag,
+        automatic: automatic != 0,
+        priority: match priority {
+            0 => TagPriority::Batch,
+            1 => TagPriority::Default,
+            2 => TagPriority::Immediate,
+            _ => return None,
+        },
+        reason,
+        rate: f64_at(value, 8)?,
+        expiration: f64_at(value, 16)?,
+        duration: Duration::try_from_secs_f64(f64_at(value, 24)?).ok()?,
+    })
+}
+
+impl Database {
+    /// Tags currently throttled, manually or by the cluster.
+    ///
+    /// Read from the system keys the ratekeeper keeps them in, like `fdbcli`'s `throttle list`.
+    pub async fn throttled_tags(&self) -> Result<Vec<ThrottledTag>, Error> {
+        let kvs: Vec<_> = self.read(async |tx: &ReadOnlyTransaction| {
+            tx.set_option(TransactionOption::ReadSystemKeys)?;
+            tx.get_range(
+                KeySelector::first_greater_or_equal(THROTTLES),
+                KeySelector::first_greater_or_equal(THROTTLES_END),
+                None,
+                None,
+                false,
+            ).try_collect().await
+        }).await?;
+        Ok(kvs.iter().filter_map(|(k, v)| decode(k, v)).collect())
+    }
+
+    /// Limits the transactions tagged `tag` with the default priority to `rate` per second, for
+    /// `duration`. Transactions over the limit are delayed, or fail with `Error::TagThrottled`
+    /// once they run out of retries.
+    ///
+    /// Writes the throttle like `fdbcli`'s `throttle on tag`, replacing an earlier one of the tag.
+    /// Fails with `Error::TooManyThrottles` if 40 other tags are throttled manually already, and with
+    /// `Error::InvalidThrottleRate` unless `rate` is positive and finite.
+    pub async fn throttle_tag(&self, tag: &str, rate: f64, duration: Duration) -> Result<(), Error> {
+        let (key, value) = (key(tag, false, TagPriority::Default)?, value(rate, duration, ThrottleReason::Manual)?);
+        self.run(async |tx: &mut Transaction| {
+            tx.set_option(TransactionOption::AccessSystemKeys)?;
+            if get_optional(tx, key.clone()).await?.is_none() {
+                let count = get_optional(tx, MANUAL_COUNT).await?;
+                if count.is_some_and(|count| decode_i64(&count) >= MAX_MANUAL_THROTTLES) {
+                    return Err(Error::TooManyThrottles);
+                }
+                tx.atomic_add_i64(MANUAL_COUNT, 1).await;
+            }
+            tx.set(key.clone(), value.clone()).await;
+            signal(tx).await;
+            Ok(())
+        }).await
+    }
+
+    /// Removes the manual throttle of `tag`, returning whether there was one.
+    pub async fn unthrottle_tag(&self, tag: &str) -> Result<bool, Error> {
+        let key = key(tag, false, TagPriority::Default)?;
+        self.run(async |tx: &mut Transaction| {
+            tx.set_option(TransactionOption::AccessSystemKeys)?;
+            if get_optional(tx, key.clone()).await?.is_none() {
+                return Ok(false);
+            }
+            tx.atomic_add_i64(MANUAL_COUNT, -1).await;
+            tx.clear(key.clone()).await;
+            signal(tx).await;
+            Ok(true)
+        }).await
+    }
+}
+
+/// Tells the ratekeeper that the manual throttles changed.
+async fn signal(tx: &Transaction) {
+    // Ten bytes replaced by the versionstamp, followed by their offset
+    let placeholder = [[0u8; 10].as_slice(), &0u32.to_le_bytes()].concat();
+    tx.atomic_set_versionstamped_value(SIGNAL, placeholder.into()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttles_roundtrip() {
+        let key = key("reports", false, TagPriority::Default).unwrap();
+        assert_eq!(key.as_slice(), b"\xff\x02/throttledTags/tag/\x00\x01\x01\x07reports");
+
+        let value = value(10.0, Duration::from_secs(60), ThrottleReason::Manual).unwrap();
+        assert_eq!(value.len(), 33);
+        assert_eq!(decode(&key, &value), Some(ThrottledTag {
+            tag: "reports".into(),
+            automatic: false,
+            priority: TagPriority::Default,
+            reason: ThrottleReason::Manual,
+            rate: 10.0,
+            expiration: 0.0,
+            duration: Duration::from_secs(60),
+        }));
+    }
+
+    #[test]
+    fn older_values_have_no_reason() {
+        let key = key("scan", true, TagPriority::Batch).unwrap();
+        let value = value(1.5, Duration::from_secs(2), ThrottleReason::BusyRead).unwrap();
+        let throttle = decode(&key, &value[..32]).unwrap();
+        assert_eq!((throttle.automatic, throttle.priority, throttle.reason), (true, TagPriority::Batch, ThrottleReason::Unset));
+
+        assert_eq!(decode(&key, &value[..20]), None);
+        assert_eq!(decode(b"\xff\x02/throttledTags/tag/\x00\x01\x01\x09short", &value), None);
+        assert_eq!(decode(b"\xff\x02/throttledTags/tag/\x00\x01\x02\x01a\x01b", &value), None);
+    }
+
+    #[test]
+    fn long_tags_are_rejected() {
+        assert!(key("sixteen-bytes-ok", false, TagPriority::Default).is_ok());
+        assert_eq!(key("seventeen-bytes-x", false, TagPriority::Default), Err(Error::TagTooLong("seventeen-bytes-x".into())));
+        assert_eq!(TransactionOption::Tag("seventeen-bytes-x".into()).check(), Err(Error::TagTooLong("seventeen-bytes-x".into())));
+        assert_eq!(TransactionOption::AutoThrottleTag("short".into()).check(), Ok(()));
+    }
+
+    #[test]
+    fn rates_must_be_positive() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(value(rate, Duration::from_secs(1), ThrottleReason::Manual), Err(Error::InvalidThrottleRate), "{rate}");
+        }
+    }
+}
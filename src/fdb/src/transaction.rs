@@ -1,4 +1,6 @@
+use std::cell::Cell;
 use std::future::Future;
+use std::time::Instant;
 
 use async_stream::try_stream;
 use futures::Stream;
@@ -8,9 +10,13 @@ use fdb_c::{FDBConflictRangeType_FDB_CONFLICT_RANGE_TYPE_READ, FDBConflictRangeT
 
 use crate::{Error, FdbErrorCode};
 use crate::future::FDBFuture;
+use crate::options::TransactionOption;
+use crate::telemetry::Telemetry;
 use crate::types::*;
 
-pub struct Transaction(*mut FDBTransaction);
+/// The C transaction, its instrumentation, and the sequence numbers handed out by
+/// `next_sequence`.
+pub struct Transaction(*mut FDBTransaction, Telemetry, Cell<u32>);
 
 impl Drop for Transaction {
     fn drop(&mut self) {
@@ -20,12 +26,17 @@ impl Drop for Transaction {
 
 impl From<*mut FDBTransaction> for Transaction {
     fn from(value: *mut FDBTransaction) -> Self {
-        Transaction(value)
+        Transaction(value, Telemetry::new(), Cell::new(0))
     }
 }
 
 pub trait CreateTransaction {
     fn create_transaction(&self) -> Result<Transaction, Error>;
+
+    /// The tenant the transactions are created in, `None` for the whole database.
+    fn tenant_name(&self) -> Option<&str> {
+        None
+    }
 }
 
 
@@ -40,48 +51,109 @@ async fn exec(
     match result {
         Ok(r) => Ok(r),
         Err(e) => {
-            let error_code = FdbErrorCode::from(&e);
-            let error_handling_fut: FDBFuture<Empty> =
-                unsafe { fdb_c::fdb_transaction_on_error(tx.0, error_code.0) }.into();
+            let should_be_retried = tx.on_error(e).await;
 
-            let should_be_retried = error_handling_fut.await.is_ok();
-
-            if should_be_retried {
+            match should_be_retried {
                 // Recursion in async functions requires boxing
-                Box::pin(exec(tx, f)).await
-            } else {
-                Err(e)
+                Ok(()) => Box::pin(exec(tx, f)).await,
+                Err(e) => Err(e),
             }
         }
     }
 }
 
 impl Transaction {
-    fn set_option() -> Result<(), Error> {
-        todo!()
+    pub fn set_option(&self, option: TransactionOption) -> Result<(), Error> {
+        option.check()?;
+        let value = option.value();
+        let (value_ptr, value_length) = match &value {
+            Some(v) => (v.as_ptr(), v.len() as i32),
+            None => (std::ptr::null(), 0),
+        };
+
+        let result = unsafe { fdb_c::fdb_transaction_set_option(self.0, option.code(), value_ptr, value_length) };
+
+        if result != 0 {
+            error!("{result}");
+            return Err(FdbErrorCode(result).into());
+        }
+
+        Ok(())
+    }
+
+    /// Implements the recommended retry and backoff behavior for a transaction.
+    ///
+    /// If the error is retryable, the transaction is reset (to the same state as a newly created
+    /// transaction) after an appropriate delay and `Ok(())` is returned. Otherwise the error passed
+    /// in is returned, as is for errors of these bindings rather than the client library, such as
+    /// `Error::KeyNotFound` or `Error::LeaseLost`.
+    pub async fn on_error(&mut self, error: Error) -> Result<(), Error> {
+        let Some(error_code) = error.fdb_code() else {
+            return Err(error);
+        };
+        let error_handling_fut: FDBFuture<Empty> =
+            unsafe { fdb_c::fdb_transaction_on_error(self.0, error_code) }.into();
+
+        let result = error_handling_fut.await.map(|_| ());
+        self.1.on_error(error_code, result.is_ok());
+        // The retry starts from scratch, without the keys numbered before
+        self.2.set(0);
+
+        result.map_err(|_| error)
+    }
+
+    /// Numbers the keys a layer writes under the versionstamp of this transaction, so that they
+    /// stay distinct. Fails with `Error::TooManyChanges` after 65536 of them.
+    pub(crate) fn next_sequence(&self) -> Result<u16, Error> {
+        let sequence = self.2.get();
+        let sequence = u16::try_from(sequence).map_err(|_| Error::TooManyChanges)?;
+        self.2.set(u32::from(sequence) + 1);
+        Ok(sequence)
     }
 
-    fn set_read_version() -> Result<(), Error> {
-        todo!()
+    /// Sets the snapshot read version used by the transaction. The version must have been obtained
+    /// from `get_read_version` and still be within the MVCC window (about 5 seconds), otherwise reads
+    /// fail with `Error::TransactionTooOld`.
+    pub fn set_read_version(&self, version: i64) {
+        unsafe { fdb_c::fdb_transaction_set_read_version(self.0, version) }
+        self.1.read_version(version);
+    }
+
+    /// Returns the snapshot read version of the transaction, fetching one from the cluster if it
+    /// has not been set or fetched before.
+    pub async fn get_read_version(&self) -> Result<i64, Error> {
+        let future: FDBFuture<Int64> = unsafe { fdb_c::fdb_transaction_get_read_version(self.0) }.into();
+
+        let version = future.await?;
+        self.1.read_version(version.0);
+
+        Ok(version.0)
     }
 
     /// Reads a value from the database
     async fn _get(&self, key: Key, snapshot: bool) -> Result<Value, Error> {
-
+        let started = Instant::now();
         let future: FDBFuture<Value> = unsafe {
             fdb_c::fdb_transaction_get(self.0, key.as_ptr(), key.len() as i32, snapshot as i32)
         }.into();
 
-        let handle = tokio::spawn(future);
-        
-        handle.await.unwrap()
+        let value = future.await;
+        match &value {
+            Ok(v) => self.1.read("get", started, v.len(), None),
+            Err(e) => self.1.read("get", started, 0, Some(e)),
+        }
+
+        value
     }
 
     pub async fn get<K: Into<Key>>(&self, key: K) -> Result<Value, Error> {
         self._get(key.into(), false).await
     }
-    pub async fn snapshot_get<K: Into<Key>>(&self, key: K) -> Result<Value, Error> {
-        self._get(key.into(), true).await
+
+    /// A view of the transaction whose reads are snapshot reads: they do not add read conflict
+    /// ranges, so the transaction does not conflict with writes of what they read.
+    pub fn snapshot(&self) -> Snapshot<'_> {
+        Snapshot(self)
     }
 
     /// Returns an estimated byte size of the key range.
@@ -94,20 +166,15 @@ impl Transaction {
     /// For a rough reference, if the returned size is larger than 3MB, one can consider the size to be accurate.
     ///
     /// TODO: Does this include the size of the keys as well?
-    pub async fn get_estimated_range_size<K: Into<Key>>(
-        &mut self,
-        start: K,
-        end: K,
-    ) -> Result<i64, Error> {
-        let start = start.into();
-        let end = end.into();
+    pub async fn get_estimated_range_size<R: Into<KeyRange>>(&mut self, range: R) -> Result<i64, Error> {
+        let range = range.into();
         let future: FDBFuture<Int64> = unsafe {
             fdb_c::fdb_transaction_get_estimated_range_size_bytes(
                 self.0,
-                start.as_ptr(),
-                start.len() as i32,
-                end.as_ptr(),
-                end.len() as i32,
+                range.start.as_ptr(),
+                range.start.len() as i32,
+                range.end.as_ptr(),
+                range.end.len() as i32,
             )
         }.into();
 
@@ -117,22 +184,21 @@ impl Transaction {
         Ok(size.0)
     }
     /// Returns a list of keys that can split the given range into (roughly) equally sized chunks based on chunk_size.
-    #[cfg(any(feature = "730", feature = "710", feature = "700"))]
-    pub async fn get_range_split_points<K: Into<Key>>(
+    #[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
+    pub async fn get_range_split_points<R: Into<KeyRange>>(
         &mut self,
-        start: K,
-        end: K,
+        range: R,
         chunk_size: i64,
     ) -> Result<KeyArray, Error> {
-        let start = start.into();
-        let end = end.into();
+        crate::client::require(700, "fdb_transaction_get_range_split_points")?;
+        let range = range.into();
         let future: FDBFuture<KeyArray> = unsafe {
             fdb_c::fdb_transaction_get_range_split_points(
                 self.0,
-                start.as_ptr(),
-                start.len() as i32,
-                end.as_ptr(),
-                end.len() as i32,
+                range.start.as_ptr(),
+                range.start.len() as i32,
+                range.end.as_ptr(),
+                range.end.len() as i32,
                 chunk_size,
             )
         }.into();
@@ -142,20 +208,14 @@ impl Transaction {
     }
 
     /// Resolves a key selector against the keys in the database snapshot represented by transaction.
-    async fn _get_key(
-        &mut self,
-        key: Key,
-        offset: i32,
-        inclusive: bool,
-        snapshot: bool,
-    ) -> Result<Key, Error> {
+    async fn _get_key(&self, selector: KeySelector, snapshot: bool) -> Result<Key, Error> {
         let future: FDBFuture<Key> = unsafe {
             fdb_c::fdb_transaction_get_key(
                 self.0,
-                key.as_ptr(),
-                key.len() as i32,
-                inclusive as i32,
-                offset,
+                selector.key.as_ptr(),
+                selector.key.len() as i32,
+                selector.inclusive as i32,
+                selector.offset,
                 snapshot as i32,
             )
         }.into();
@@ -164,130 +224,15 @@ impl Transaction {
         future.await
     }
 
-    pub async fn get_key<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-        inclusive: bool,
-    ) -> Result<Key, Error> {
-        self._get_key(key.into(), offset, inclusive, false).await
-    }
-
-    pub async fn snapshot_get_key<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-        inclusive: bool,
-    ) -> Result<Key, Error> {
-        self._get_key(key.into(), offset, inclusive, true).await
-    }
-
-    async fn _get_first_key_greater_or_equal_than(
-        &mut self,
-        key: Key,
-        offset: i32,
-        snapshot: bool,
-    ) -> Result<Key, Error> {
-        self._get_key(key, 1 + offset, true, snapshot).await
-    }
-
-    pub async fn get_first_key_greater_or_equal_than<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-    ) -> Result<Key, Error> {
-        self._get_first_key_greater_or_equal_than(key.into(), offset, false)
-            .await
-    }
-    pub async fn snapshot_get_first_key_greater_or_equal_than<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-    ) -> Result<Key, Error> {
-        self._get_first_key_greater_or_equal_than(key.into(), offset, true)
-            .await
+    /// Returns the key the selector resolves to, e.g. `KeySelector::last_less_than(key)` for the key
+    /// right before `key`.
+    pub async fn get_key<S: Into<KeySelector>>(&self, selector: S) -> Result<Key, Error> {
+        self._get_key(selector.into(), false).await
     }
 
-    async fn _get_first_key_greater_than(
-        &mut self,
-        key: Key,
-        offset: i32,
-        snapshot: bool,
-    ) -> Result<Key, Error> {
-        self._get_key(key, 1 + offset, false, snapshot).await
-    }
-
-    pub async fn get_first_key_greater_than<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-    ) -> Result<Key, Error> {
-        self._get_first_key_greater_than(key.into(), offset, false)
-            .await
-    }
-    pub async fn snapshot_get_first_key_greater_than<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-    ) -> Result<Key, Error> {
-        self._get_first_key_greater_than(key.into(), offset, true)
-            .await
-    }
-
-    async fn _get_last_key_less_or_equal_than(
-        &mut self,
-        key: Key,
-        offset: i32,
-        snapshot: bool,
-    ) -> Result<Key, Error> {
-        self._get_key(key, -1 + offset, true, snapshot).await
-    }
-
-    pub async fn get_last_key_less_or_equal_than<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-    ) -> Result<Key, Error> {
-        self._get_last_key_less_or_equal_than(key.into(), offset, false)
-            .await
-    }
-    pub async fn snapshot_get_last_key_less_or_equal_than<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-    ) -> Result<Key, Error> {
-        self._get_last_key_less_or_equal_than(key.into(), offset, true)
-            .await
-    }
-
-    async fn _get_last_key_less_than(
-        &mut self,
-        key: Key,
-        offset: i32,
-        snapshot: bool,
-    ) -> Result<Key, Error> {
-        self._get_key(key, -1 + offset, false, snapshot).await
-    }
-
-    pub async fn get_last_key_less_than<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-    ) -> Result<Key, Error> {
-        self._get_last_key_less_than(key.into(), offset, false)
-            .await
-    }
-    pub async fn snapshot_get_last_key_less_than<K: Into<Key>>(
-        &mut self,
-        key: K,
-        offset: i32,
-    ) -> Result<Key, Error> {
-        self._get_last_key_less_than(key.into(), offset, true).await
-    }
-
-    /// Returns a list of public network addresses as strings, one for each of the storage servers
-    /// responsible for storing the key and its associated value.
-    pub async fn get_key_addresses<K: Into<Key>>(&self, key: K) -> Result<StringArray, Error> {
+    /// Returns the public network addresses of the storage servers responsible for storing
+    /// the key and its associated value.
+    pub async fn get_key_addresses<K: Into<Key>>(&self, key: K) -> Result<Vec<StorageAddress>, Error> {
         let key = key.into();
         let future: FDBFuture<StringArray> = unsafe {
             fdb_c::fdb_transaction_get_addresses_for_key(
@@ -297,39 +242,77 @@ impl Transaction {
             )
         }.into();
 
-        future.await
+        let addresses = future.await?;
+
+        addresses.iter().map(|a| a.parse()).collect()
     }
 
     /// Return Keys and Values within a given range as a stream of `(Key, Value)` tuples.
     ///
     /// TODO: Check Lifetime of returned tuples corresponds to lifetime of transaction
-    pub async fn get_range(
-        &mut self,
-        start: KeySelector,
-        end: KeySelector,
+    pub fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> + '_ {
+        self._get_range(start.into(), end.into(), limit, target_bytes, false, reverse)
+    }
+
+    fn _get_range(
+        &self,
+        mut start: KeySelector,
+        mut end: KeySelector,
         limit: Option<i32>,
         target_bytes: Option<i32>,
         snapshot: bool,
         reverse: bool,
     ) -> impl Stream<Item=Result<(Key, Value), Error>> + '_ {
         try_stream! {
-                let mut iteration = 0;
+                let limited = limit.is_some();
+                let mut limit = limit.unwrap_or(0);
+                let mut iteration = 1;
                 let mode = fdb_c::FDBStreamingMode_FDB_STREAMING_MODE_ITERATOR;
+                let started = Instant::now();
+                let mut bytes = 0;
 
                  loop {
-                    let future: FDBFuture<KeyValueArray> = unsafe { fdb_c::fdb_transaction_get_range(self.0, start.key.as_ptr(), start.key.len() as i32, start.inclusive as i32, start.offset, end.key.as_ptr(), end.key.len() as i32, end.inclusive as i32, end.offset, limit.unwrap_or(0), target_bytes.unwrap_or(0), mode, iteration, snapshot as i32, reverse as i32) }.into();
+                    let future: FDBFuture<KeyValueArray> = unsafe { fdb_c::fdb_transaction_get_range(self.0, start.key.as_ptr(), start.key.len() as i32, start.inclusive as i32, start.offset, end.key.as_ptr(), end.key.len() as i32, end.inclusive as i32, end.offset, limit, target_bytes.unwrap_or(0), mode, iteration, snapshot as i32, reverse as i32) }.into();
 
-                    let result = future.await?;
+                    let result = future.await;
+                    if let Err(e) = &result {
+                        self.1.read("get_range", started, bytes, Some(e));
+                    }
+                    let result = result?;
+                    let more = result.1;
+                    bytes += result.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>();
+
+                    // Continue the next batch right after the last returned key
+                    if let Some((last, _)) = result.last() {
+                        let last = last.clone();
+                        if reverse {
+                            end = KeySelector::first_greater_or_equal(last);
+                        } else {
+                            start = KeySelector::first_greater_than(last);
+                        }
+                    }
 
-                    if result.is_empty() {
-                        // All range items have been returned
-                        break;
+                    if limit > 0 {
+                        limit -= result.len() as i32;
                     }
 
                     iteration += 1;
                         for r in result.0.into_iter() {
                             yield r;
                         }
+
+                    if !more || (limited && limit <= 0) {
+                        // All range items have been returned
+                        self.1.read("get_range", started, bytes, None);
+                        break;
+                    }
                 }
         }
     }
@@ -355,16 +338,15 @@ impl Transaction {
         unsafe { fdb_c::fdb_transaction_clear(self.0, key.as_ptr(), key.len() as i32) }
     }
 
-    pub async fn clear_range<K: Into<Key>>(&self, start: K, end: K) {
-        let start = start.into();
-        let end = end.into();
+    pub async fn clear_range<R: Into<KeyRange>>(&self, range: R) {
+        let range = range.into();
         unsafe {
             fdb_c::fdb_transaction_clear_range(
                 self.0,
-                start.as_ptr(),
-                start.len() as i32,
-                end.as_ptr(),
-                end.len() as i32,
+                range.start.as_ptr(),
+                range.start.len() as i32,
+                range.end.as_ptr(),
+                range.end.len() as i32,
             )
         }
     }
@@ -386,6 +368,25 @@ impl Transaction {
         }
     }
 
+    /// Like `atomic_add`, adding a 64-bit integer. The stored value is extended to 8 bytes if it is
+    /// shorter, and the sum wraps around on overflow.
+    pub async fn atomic_add_i64<K: Into<Key>>(&self, key: K, other: i64) {
+        let key = key.into();
+        let operation_type = FDBMutationType_FDB_MUTATION_TYPE_ADD;
+        let addend = other.to_le_bytes();
+
+        unsafe {
+            fdb_c::fdb_transaction_atomic_op(
+                self.0,
+                key.as_ptr(),
+                key.len() as i32,
+                addend.as_ptr(),
+                64 / 8,
+                operation_type,
+            )
+        }
+    }
+
     /// Performs a bitwise “and” operation
     ///
     /// TODO: better datatype for other (Maybe something like impl BitAnd?)
@@ -610,27 +611,30 @@ impl Transaction {
         future.await
     }
 
-    /// TODO: perhaps make this a method of Key? How to implement cancelling watches?
-    pub async fn watch<K: Into<Key>>(&self, key: K) -> Result<(), Error> {
+    /// Resolves once the value of the key differs from the one this transaction read or wrote.
+    ///
+    /// The watch is created right away, but only becomes active when the transaction commits. The
+    /// returned future does not borrow the transaction, so it can be awaited after `commit`.
+    /// Dropping it cancels the watch.
+    pub fn watch<K: Into<Key>>(&self, key: K) -> impl Future<Output=Result<(), Error>> + 'static {
         let key = key.into();
         let future: FDBFuture<Empty> =
             unsafe { fdb_c::fdb_transaction_watch(self.0, key.as_ptr(), key.len() as i32) }.into();
 
+        async move {
+            let _changed = future.await?;
 
-        let _changed = future.await?;
-
-        Ok(())
+            Ok(())
+        }
     }
 
     /// Adds a conflict range to a transaction without performing the associated read or write.
-    pub async fn add_conflict_range<K: Into<Key>>(
+    pub async fn add_conflict_range<R: Into<KeyRange>>(
         &mut self,
-        start: K,
-        end: K,
+        range: R,
         conflict_type: ConflictType,
     ) -> Result<(), Error> {
-        let start = start.into();
-        let end = end.into();
+        let range = range.into();
         let t = match conflict_type {
             ConflictType::Read => FDBConflictRangeType_FDB_CONFLICT_RANGE_TYPE_READ,
             ConflictType::Write => FDBConflictRangeType_FDB_CONFLICT_RANGE_TYPE_WRITE,
@@ -638,10 +642,10 @@ impl Transaction {
         let result = unsafe {
             fdb_c::fdb_transaction_add_conflict_range(
                 self.0,
-                start.as_ptr(),
-                start.len() as i32,
-                end.as_ptr(),
-                end.len() as i32,
+                range.start.as_ptr(),
+                range.start.len() as i32,
+                range.end.as_ptr(),
+                range.end.len() as i32,
                 t,
             )
         };
@@ -659,15 +663,25 @@ impl Transaction {
         unsafe { fdb_c::fdb_transaction_cancel(self.0) }
     }
 
-    /// Consume a readonly transaction, thereby destroying it (readonly transactions don't need to be committed)
-    pub fn commit_readonly(self) {
-        drop(self)
+    pub async fn commit(mut self) -> Result<(), Error> {
+        self._commit().await
     }
 
-    pub async fn commit(mut self) -> Result<(), Error> {
+    /// Commits without consuming the transaction, so that it can be retried with `on_error`.
+    pub(crate) async fn _commit(&mut self) -> Result<(), Error> {
+        // Only needed for reporting, skipped when nothing records it
+        let approximate_size = match self.1.wants_approximate_size() {
+            true => self.get_approximate_size().await.ok().map(|s| s.0),
+            false => None,
+        };
+
+        let started = Instant::now();
         let future: FDBFuture<Empty> = unsafe { fdb_c::fdb_transaction_commit(self.0) }.into();
 
-        let _commited = future.await?;
+        let commited = future.await;
+        self.1.commit(started.elapsed(), approximate_size, commited.as_ref().err().map(|e| FdbErrorCode::from(e).0));
+
+        commited?;
 
         Ok(())
     }
@@ -682,3 +696,296 @@ pub enum ConflictType {
     Read,
     Write,
 }
+
+/// The reads of a transaction, shared by `Transaction`, its `Snapshot` view and
+/// `ReadOnlyTransaction`, so that code which only reads can accept any of them.
+pub trait ReadTransaction {
+    /// See `Transaction::get_read_version`.
+    fn get_read_version(&self) -> impl Future<Output=Result<i64, Error>>;
+
+    /// Reads the value of `key`, failing with `Error::KeyNotFound` if it has none.
+    fn get<K: Into<Key>>(&self, key: K) -> impl Future<Output=Result<Value, Error>>;
+
+    /// See `Transaction::get_key`.
+    fn get_key<S: Into<KeySelector>>(&self, selector: S) -> impl Future<Output=Result<Key, Error>>;
+
+    /// See `Transaction::get_range`.
+    fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>>;
+}
+
+impl ReadTransaction for Transaction {
+    fn get_read_version(&self) -> impl Future<Output=Result<i64, Error>> {
+        Transaction::get_read_version(self)
+    }
+
+    fn get<K: Into<Key>>(&self, key: K) -> impl Future<Output=Result<Value, Error>> {
+        Transaction::get(self, key)
+    }
+
+    fn get_key<S: Into<KeySelector>>(&self, selector: S) -> impl Future<Output=Result<Key, Error>> {
+        Transaction::get_key(self, selector)
+    }
+
+    fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> {
+        Transaction::get_range(self, start, end, limit, target_bytes, reverse)
+    }
+}
+
+/// Snapshot reads of a transaction, created with `Transaction::snapshot`.
+///
+/// Snapshot reads see the writes of the transaction, but not those committed by others since its
+/// read version, and do not cause conflicts with them. They suit reads whose result may be
+/// slightly stale without affecting correctness, such as heuristics or values only displayed.
+#[derive(Clone, Copy)]
+pub struct Snapshot<'a>(&'a Transaction);
+
+impl<'a> Snapshot<'a> {
+    pub fn get_read_version(&self) -> impl Future<Output=Result<i64, Error>> + 'a {
+        self.0.get_read_version()
+    }
+
+    pub fn get<K: Into<Key>>(&self, key: K) -> impl Future<Output=Result<Value, Error>> + 'a {
+        self.0._get(key.into(), true)
+    }
+
+    pub fn get_key<S: Into<KeySelector>>(&self, selector: S) -> impl Future<Output=Result<Key, Error>> + 'a {
+        self.0._get_key(selector.into(), true)
+    }
+
+    pub fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> + 'a {
+        self.0._get_range(start.into(), end.into(), limit, target_bytes, true, reverse)
+    }
+}
+
+/// A transaction that can only read, opened by `Database::read`. It is never committed.
+///
+/// None of the writes of `Transaction` are available:
+///
+/// ```compile_fail
+/// async fn write(tx: &fdb::ReadOnlyTransaction) {
+///     tx.set("key", "value").await;
+/// }
+/// ```
+///
+/// ```compile_fail
+/// async fn commit(tx: fdb::ReadOnlyTransaction) {
+///     tx.commit().await.unwrap();
+/// }
+/// ```
+pub struct ReadOnlyTransaction(Transaction);
+
+impl ReadOnlyTransaction {
+    pub(crate) fn new(tx: Transaction) -> Self {
+        ReadOnlyTransaction(tx)
+    }
+
+    pub fn set_option(&self, option: TransactionOption) -> Result<(), Error> {
+        self.0.set_option(option)
+    }
+
+    /// See `Transaction::set_read_version`.
+    pub fn set_read_version(&self, version: i64) {
+        self.0.set_read_version(version)
+    }
+
+    pub async fn get_read_version(&self) -> Result<i64, Error> {
+        self.0.get_read_version().await
+    }
+
+    pub async fn get<K: Into<Key>>(&self, key: K) -> Result<Value, Error> {
+        self.0.get(key).await
+    }
+
+    pub async fn get_key<S: Into<KeySelector>>(&self, selector: S) -> Result<Key, Error> {
+        self.0.get_key(selector).await
+    }
+
+    pub fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> + '_ {
+        self.0.get_range(start, end, limit, target_bytes, reverse)
+    }
+
+    /// See `Transaction::snapshot`.
+    pub fn snapshot(&self) -> Snapshot<'_> {
+        self.0.snapshot()
+    }
+
+    /// See `Transaction::on_error`.
+    pub async fn on_error(&mut self, error: Error) -> Result<(), Error> {
+        self.0.on_error(error).await
+    }
+}
+
+impl ReadTransaction for Snapshot<'_> {
+    fn get_read_version(&self) -> impl Future<Output=Result<i64, Error>> {
+        Snapshot::get_read_version(self)
+    }
+
+    fn get<K: Into<Key>>(&self, key: K) -> impl Future<Output=Result<Value, Error>> {
+        Snapshot::get(self, key)
+    }
+
+    fn get_key<S: Into<KeySelector>>(&self, selector: S) -> impl Future<Output=Result<Key, Error>> {
+        Snapshot::get_key(self, selector)
+    }
+
+    fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> {
+        Snapshot::get_range(self, start, end, limit, target_bytes, reverse)
+    }
+}
+
+impl ReadTransaction for ReadOnlyTransaction {
+    fn get_read_version(&self) -> impl Future<Output=Result<i64, Error>> {
+        ReadOnlyTransaction::get_read_version(self)
+    }
+
+    fn get<K: Into<Key>>(&self, key: K) -> impl Future<Output=Result<Value, Error>> {
+        ReadOnlyTransaction::get(self, key)
+    }
+
+    fn get_key<S: Into<KeySelector>>(&self, selector: S) -> impl Future<Output=Result<Key, Error>> {
+        ReadOnlyTransaction::get_key(self, selector)
+    }
+
+    fn get_range<B: Into<KeySelector>, E: Into<KeySelector>>(
+        &self,
+        start: B,
+        end: E,
+        limit: Option<i32>,
+        target_bytes: Option<i32>,
+        reverse: bool,
+    ) -> impl Stream<Item=Result<(Key, Value), Error>> {
+        ReadOnlyTransaction::get_range(self, start, end, limit, target_bytes, reverse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use crate::testing::TestCluster;
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn snapshot_reads_do_not_conflict() {
+        let cluster = TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let subspace = cluster.subspace();
+        let (read, written) = (subspace.subspace("read"), subspace.key("written"));
+
+        for (snapshot, range) in [(true, false), (true, true), (false, false), (false, true)] {
+            let tx = db.create_transaction().unwrap();
+            let (start, end) = (KeySelector::first_greater_or_equal(read.range().start), KeySelector::first_greater_or_equal(read.range().end));
+            let _ = match (snapshot, range) {
+                (true, true) => tx.snapshot().get_range(start, end, None, None, false).try_collect::<Vec<_>>().await.map(drop),
+                (true, false) => tx.snapshot().get(read.key("k")).await.map(drop),
+                (false, true) => tx.get_range(start, end, None, None, false).try_collect::<Vec<_>>().await.map(drop),
+                (false, false) => tx.get(read.key("k")).await.map(drop),
+            };
+
+            // A concurrent write of what was read
+            db.run(async |other: &mut Transaction| {
+                other.set(read.key("k"), "changed").await;
+                Ok(())
+            }).await.unwrap();
+
+            tx.set(written.clone(), "").await;
+            let committed = tx.commit().await;
+            assert_eq!(committed.is_ok(), snapshot, "snapshot {snapshot}, range {range}: {committed:?}");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn errors_of_the_bindings_are_not_retried() {
+        let cluster = TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+
+        let mut attempts = 0;
+        let result: Result<(), Error> = db.run(async |_: &mut Transaction| {
+            attempts += 1;
+            Err(Error::LeaseLost)
+        }).await;
+        assert_eq!((result, attempts), (Err(Error::LeaseLost), 1));
+
+        let result = db.read(async |tx: &ReadOnlyTransaction| tx.get(cluster.subspace().key("missing")).await).await;
+        assert_eq!(result, Err(Error::KeyNotFound));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn read_only_transactions_read() {
+        let cluster = TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let key = cluster.subspace().key("k");
+
+        db.run(async |tx: &mut Transaction| {
+            tx.set(key.clone(), "v").await;
+            Ok(())
+        }).await.unwrap();
+
+        let values = db.read(async |tx: &ReadOnlyTransaction| {
+            Ok((tx.get(key.clone()).await?, tx.snapshot().get(key.clone()).await?))
+        }).await;
+        assert_eq!(values, Ok((Value::from("v"), Value::from("v"))));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fdbserver, run with --ignored"]
+    async fn dropped_watches_are_cancelled() {
+        let cluster = TestCluster::start().await.unwrap();
+        let db = cluster.database().unwrap();
+        let key = cluster.subspace().key("watched");
+
+        // More watches than the default limit of 10,000 per database, which only fits if dropped
+        // watches leave the cluster
+        for _ in 0..101 {
+            let watches = db.run(async |tx: &mut Transaction| {
+                Ok((0..100).map(|_| tx.watch(key.clone())).collect::<Vec<_>>())
+            }).await.unwrap();
+            drop(watches);
+        }
+
+        let watch = db.run(async |tx: &mut Transaction| Ok(tx.watch(key.clone()))).await.unwrap();
+        db.run(async |tx: &mut Transaction| {
+            tx.set(key.clone(), "changed").await;
+            Ok(())
+        }).await.unwrap();
+        assert_eq!(watch.await, Ok(()));
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// Network address of a storage server, as returned by `Transaction::get_key_addresses`.
+///
+/// FoundationDB formats addresses as `ip:port`, with IPv6 addresses in brackets and a `:tls`
+/// suffix for TLS-enabled processes. API versions before 630 omit the port unless the
+/// `include_port_in_address` option is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StorageAddress {
+    pub ip: IpAddr,
+    pub port: Option<u16>,
+    pub tls: bool,
+}
+
+impl FromStr for StorageAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidAddress(s.to_owned());
+
+        let (address, tls) = match s.strip_suffix(":tls") {
+            Some(address) => (address, true),
+            None => (s, false),
+        };
+
+        // IPv6: [::1]:4500 or [::1]
+        if let Some(rest) = address.strip_prefix('[') {
+            let (ip, port) = rest.split_once(']').ok_or_else(invalid)?;
+            let ip = ip.parse().map_err(|_| invalid())?;
+            let port = match port {
+                "" => None,
+                port => Some(port.strip_prefix(':').ok_or_else(invalid)?.parse().map_err(|_| invalid())?),
+            };
+            return Ok(StorageAddress { ip, port, tls });
+        }
+
+        // IPv4: 10.0.0.1:4500 or 10.0.0.1
+        let (ip, port) = match address.split_once(':') {
+            Some((ip, port)) => (ip, Some(port.parse().map_err(|_| invalid())?)),
+            None => (address, None),
+        };
+
+        Ok(StorageAddress { ip: ip.parse().map_err(|_| invalid())?, port, tls })
+    }
+}
+
+impl Display for StorageAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.ip {
+            IpAddr::V4(ip) => write!(f, "{ip}")?,
+            IpAddr::V6(ip) => write!(f, "[{ip}]")?,
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+        if self.tls {
+            write!(f, ":tls")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_addresses() {
+        let a: StorageAddress = "10.0.0.1:4500".parse().unwrap();
+        assert_eq!(a.ip, "10.0.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(a.port, Some(4500));
+        assert!(!a.tls);
+
+        let a: StorageAddress = "10.0.0.1:4500:tls".parse().unwrap();
+        assert!(a.tls);
+
+        let a: StorageAddress = "[::1]:4500:tls".parse().unwrap();
+        assert_eq!(a.ip, "::1".parse::<IpAddr>().unwrap());
+        assert_eq!(a.port, Some(4500));
+        assert!(a.tls);
+
+        let a: StorageAddress = "127.0.0.1".parse().unwrap();
+        assert_eq!(a.port, None);
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for s in ["10.0.0.1:4500", "10.0.0.1:4500:tls", "[::1]:4500", "127.0.0.1"] {
+            assert_eq!(s.parse::<StorageAddress>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_address() {
+        assert_eq!("localhost:4500".parse::<StorageAddress>(), Err(Error::InvalidAddress("localhost:4500".into())));
+        assert!("10.0.0.1:port".parse::<StorageAddress>().is_err());
+    }
+}
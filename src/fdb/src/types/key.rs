@@ -1,7 +1,30 @@
-use std::ops::{Deref, DerefMut};
+use std::fmt::{Debug, Formatter};
+use std::ops::{Add, Deref, DerefMut, Sub};
 
+use bytes::Bytes;
+
+use crate::types::printable;
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub struct Key(Vec<u8>);
 
+impl Key {
+    /// Returns the first key that does not start with this key, by stripping trailing 0xFF bytes
+    /// and incrementing the last remaining byte. Returns `None` for keys that consist only of
+    /// 0xFF bytes (including the empty key).
+    pub fn strinc(&self) -> Option<Key> {
+        let end = self.0.iter().rposition(|b| *b != 0xff)?;
+        let mut next = self.0[..=end].to_vec();
+        next[end] += 1;
+        Some(Key(next))
+    }
+
+    /// The key escaped for display, see `printable`.
+    pub fn printable(&self) -> String {
+        printable(&self.0)
+    }
+}
+
 impl Deref for Key {
     type Target = Vec<u8>;
     fn deref(&self) -> &Self::Target {
@@ -15,6 +38,18 @@ impl DerefMut for Key {
     }
 }
 
+impl AsRef<[u8]> for Key {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Debug for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", self.printable())
+    }
+}
+
 impl From<&str> for Key {
     fn from(value: &str) -> Self {
         let bytes = value.as_bytes();
@@ -22,39 +57,171 @@ impl From<&str> for Key {
     }
 }
 
+impl From<String> for Key {
+    fn from(value: String) -> Self {
+        Key(value.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Key {
+    fn from(value: &[u8]) -> Self {
+        Key(value.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Key {
+    fn from(value: &[u8; N]) -> Self {
+        Key(value.to_vec())
+    }
+}
+
 impl From<Vec<u8>> for Key {
     fn from(value: Vec<u8>) -> Self {
         Key(value)
     }
 }
 
+impl From<Bytes> for Key {
+    fn from(value: Bytes) -> Self {
+        Key(value.to_vec())
+    }
+}
+
+impl From<Key> for Vec<u8> {
+    fn from(value: Key) -> Self {
+        value.0
+    }
+}
+
 
+/// Identifies a key relative to another key, resolved against the database at read time.
+///
+/// A selector selects the last key less than `key` (or equal to it, if `inclusive` is set),
+/// then moves `offset` keys forward. Use the four canonical constructors rather than setting the
+/// fields directly, and `+`/`-` to move the selected key by a number of keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct KeySelector {
     // Key the selector starts from
     pub key: Key,
+    /// `or_equal` in the C API
     pub inclusive: bool,
     pub offset: i32,
 }
 
 impl KeySelector {
-    fn set_inclusive(mut self, to: bool) -> Self {
-        self.inclusive = to;
+    pub fn new<K: Into<Key>>(key: K, inclusive: bool, offset: i32) -> Self {
+        KeySelector {
+            key: key.into(),
+            inclusive,
+            offset,
+        }
+    }
+
+    /// Selects the last key less than `key`.
+    pub fn last_less_than<K: Into<Key>>(key: K) -> Self {
+        Self::new(key, false, 0)
+    }
+
+    /// Selects the last key less than or equal to `key`.
+    pub fn last_less_or_equal<K: Into<Key>>(key: K) -> Self {
+        Self::new(key, true, 0)
+    }
+
+    /// Selects the first key greater than `key`.
+    pub fn first_greater_than<K: Into<Key>>(key: K) -> Self {
+        Self::new(key, true, 1)
+    }
+
+    /// Selects the first key greater than or equal to `key`.
+    pub fn first_greater_or_equal<K: Into<Key>>(key: K) -> Self {
+        Self::new(key, false, 1)
+    }
+}
+
+impl Add<i32> for KeySelector {
+    type Output = KeySelector;
+    fn add(mut self, rhs: i32) -> Self::Output {
+        self.offset += rhs;
         self
     }
+}
 
-    fn set_offset(mut self, to: i32) -> Self {
-        self.offset = to;
+impl Sub<i32> for KeySelector {
+    type Output = KeySelector;
+    fn sub(mut self, rhs: i32) -> Self::Output {
+        self.offset -= rhs;
         self
     }
 }
 
-/// Default Conversion
+/// Default Conversion: a plain key selects itself, or the next key if it does not exist.
 impl From<Key> for KeySelector {
     fn from(value: Key) -> Self {
-        KeySelector {
-            key: value,
-            inclusive: true,
-            offset: 0,
-        }
+        KeySelector::first_greater_or_equal(value)
+    }
+}
+
+impl From<&str> for KeySelector {
+    fn from(value: &str) -> Self {
+        KeySelector::first_greater_or_equal(value)
+    }
+}
+
+impl From<Vec<u8>> for KeySelector {
+    fn from(value: Vec<u8>) -> Self {
+        KeySelector::first_greater_or_equal(value)
     }
-}
\ No newline at end of file
+}
+
+impl From<&[u8]> for KeySelector {
+    fn from(value: &[u8]) -> Self {
+        KeySelector::first_greater_or_equal(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_selectors() {
+        let s = KeySelector::last_less_than("a");
+        assert_eq!((s.inclusive, s.offset), (false, 0));
+        let s = KeySelector::last_less_or_equal("a");
+        assert_eq!((s.inclusive, s.offset), (true, 0));
+        let s = KeySelector::first_greater_than("a");
+        assert_eq!((s.inclusive, s.offset), (true, 1));
+        let s = KeySelector::first_greater_or_equal("a");
+        assert_eq!((s.inclusive, s.offset), (false, 1));
+    }
+
+    #[test]
+    fn selector_arithmetic() {
+        let s = KeySelector::first_greater_or_equal("a") + 5;
+        assert_eq!(s.offset, 6);
+        assert!(!s.inclusive);
+
+        let s = KeySelector::last_less_than("a") - 2;
+        assert_eq!(s.offset, -2);
+        assert_eq!(*s.key, b"a".to_vec());
+    }
+
+    #[test]
+    fn strinc() {
+        assert_eq!(Key::from("ab").strinc(), Some(Key::from("ac")));
+        assert_eq!(Key::from(b"a\xff\xff").strinc(), Some(Key::from("b")));
+        assert_eq!(Key::from(b"\xff").strinc(), None);
+        assert_eq!(Key::from("").strinc(), None);
+    }
+
+    #[test]
+    fn printable_debug() {
+        assert_eq!(format!("{:?}", Key::from(b"\x02hello\\\x00\xff")), r#""\x02hello\\\x00\xff""#);
+    }
+
+    #[test]
+    fn plain_key_selects_itself() {
+        let s: KeySelector = Key::from("a").into();
+        assert_eq!((s.inclusive, s.offset), (false, 1));
+    }
+}
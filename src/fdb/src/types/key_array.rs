@@ -1,7 +1,24 @@
+use std::ops::Deref;
+
 use crate::types::Key;
 
 pub struct KeyArray(Vec<Key>);
 
+impl Deref for KeyArray {
+    type Target = Vec<Key>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl IntoIterator for KeyArray {
+    type Item = Key;
+    type IntoIter = std::vec::IntoIter<Key>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
 impl From<Vec<Key>> for KeyArray {
     fn from(value: Vec<Key>) -> Self {
         KeyArray(value)
@@ -0,0 +1,129 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::Range;
+
+use crate::Error;
+use crate::types::Key;
+
+/// The half-open range of keys `[start, end)`.
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct KeyRange {
+    pub start: Key,
+    pub end: Key,
+}
+
+impl KeyRange {
+    pub fn new<S: Into<Key>, E: Into<Key>>(start: S, end: E) -> Self {
+        KeyRange {
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+
+    /// All keys starting with `prefix`, including `prefix` itself.
+    ///
+    /// Fails for prefixes consisting only of 0xFF bytes, which have no upper bound (see `Key::strinc`).
+    pub fn prefix_range<K: Into<Key>>(prefix: K) -> Result<Self, Error> {
+        let start = prefix.into();
+        let end = start.strinc().ok_or_else(|| Error::InvalidPrefix(start.clone()))?;
+        Ok(KeyRange { start, end })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    pub fn contains<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        let key = key.as_ref();
+        self.start.as_slice() <= key && key < self.end.as_slice()
+    }
+
+    /// Keys contained in both ranges, or `None` if they do not overlap.
+    pub fn intersect(&self, other: &KeyRange) -> Option<KeyRange> {
+        let range = KeyRange {
+            start: self.start.clone().max(other.start.clone()),
+            end: self.end.clone().min(other.end.clone()),
+        };
+
+        if range.is_empty() {
+            return None;
+        }
+        Some(range)
+    }
+
+    /// Splits the range into `[start, key)` and `[key, end)`. `key` is clamped to the range, so one of
+    /// the halves is empty if it lies outside.
+    pub fn split_at<K: Into<Key>>(&self, key: K) -> (KeyRange, KeyRange) {
+        let key = key.into().clamp(self.start.clone(), self.end.clone().max(self.start.clone()));
+        (
+            KeyRange { start: self.start.clone(), end: key.clone() },
+            KeyRange { start: key, end: self.end.clone() },
+        )
+    }
+}
+
+impl Debug for KeyRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:?}, {:?})", self.start, self.end)
+    }
+}
+
+impl<S: Into<Key>, E: Into<Key>> From<(S, E)> for KeyRange {
+    fn from((start, end): (S, E)) -> Self {
+        KeyRange::new(start, end)
+    }
+}
+
+impl<K: Into<Key>> From<Range<K>> for KeyRange {
+    fn from(value: Range<K>) -> Self {
+        KeyRange::new(value.start, value.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains() {
+        let range = KeyRange::new("b", "d");
+        assert!(!range.contains("a"));
+        assert!(range.contains("b"));
+        assert!(range.contains(b"c\xff".as_slice()));
+        assert!(!range.contains("d"));
+    }
+
+    #[test]
+    fn intersect() {
+        let range = KeyRange::new("b", "d");
+        assert_eq!(range.intersect(&("c", "e").into()), Some(KeyRange::new("c", "d")));
+        assert_eq!(range.intersect(&("a", "z").into()), Some(range.clone()));
+        assert_eq!(range.intersect(&("d", "e").into()), None);
+    }
+
+    #[test]
+    fn split_at() {
+        let range = KeyRange::new("b", "d");
+        assert_eq!(range.split_at("c"), (KeyRange::new("b", "c"), KeyRange::new("c", "d")));
+        let (left, right) = range.split_at("z");
+        assert_eq!(right, KeyRange::new("d", "d"));
+        assert!(right.is_empty());
+        assert_eq!(left, range);
+    }
+
+    #[test]
+    fn prefix_range() {
+        let range = KeyRange::prefix_range(b"app\xff".as_slice()).unwrap();
+        assert_eq!(range, KeyRange::new(b"app\xff".as_slice(), "apq"));
+        assert!(range.contains(b"app\xff\x00".as_slice()));
+        assert!(!range.contains("apq"));
+
+        assert!(KeyRange::prefix_range(b"\xff\xff".as_slice()).is_err());
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(KeyRange::from("a".."b"), KeyRange::new("a", "b"));
+        assert_eq!(KeyRange::from((String::from("a"), b"b".to_vec())), KeyRange::new("a", "b"));
+        assert_eq!(format!("{:?}", KeyRange::new("a", b"\xff".as_slice())), r#"["a", "\xff")"#);
+    }
+}
@@ -1,20 +1,40 @@
 use std::ops::{Deref};
 
 mod key;
+mod key_range;
 mod value;
+mod address;
+mod subspace;
 
 
 pub use key::{Key, KeySelector};
+pub use key_range::KeyRange;
 pub use value::Value;
+pub use address::StorageAddress;
+pub use subspace::Subspace;
 
-#[cfg(any(feature = "730", feature = "710", feature = "700"))]
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
 mod key_array;
 
-#[cfg(any(feature = "730", feature = "710", feature = "700"))]
+#[cfg(any(feature = "730", feature = "710", feature = "700", feature = "dlopen"))]
 pub use key_array::KeyArray;
 
 
 
+/// Escapes bytes like FoundationDB's `printable()`: printable ASCII is kept, backslashes are
+/// doubled and every other byte is written as `\xNN`.
+pub fn printable(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len());
+    for b in bytes {
+        match b {
+            b'\\' => s.push_str("\\\\"),
+            32..=126 => s.push(*b as char),
+            _ => s.push_str(&format!("\\x{b:02x}")),
+        }
+    }
+    s
+}
+
 pub struct Empty(());
 
 impl Default for Empty {
@@ -40,11 +60,19 @@ impl From<Vec<String>> for StringArray {
     }
 }
 
-pub struct KeyValueArray(pub Vec<(Key, Value)>);
+impl Deref for StringArray {
+    type Target = Vec<String>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Key-value pairs of one batch of a range read, and whether more pairs are available in the range.
+pub struct KeyValueArray(pub Vec<(Key, Value)>, pub bool);
 
 impl From<Vec<(Key, Value)>> for KeyValueArray {
     fn from(value: Vec<(Key, Value)>) -> Self {
-        KeyValueArray(value)
+        KeyValueArray(value, false)
     }
 }
 
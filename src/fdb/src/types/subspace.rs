@@ -0,0 +1,73 @@
+use crate::types::{Key, KeyRange};
+
+/// A key prefix keeping the keys of one layer or application apart from the rest of the database.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Subspace {
+    prefix: Key,
+}
+
+impl Subspace {
+    pub fn new<K: Into<Key>>(prefix: K) -> Self {
+        Subspace { prefix: prefix.into() }
+    }
+
+    pub fn prefix(&self) -> &Key {
+        &self.prefix
+    }
+
+    /// The key `suffix` within the subspace.
+    pub fn key<S: AsRef<[u8]>>(&self, suffix: S) -> Key {
+        [self.prefix.as_slice(), suffix.as_ref()].concat().into()
+    }
+
+    /// The subspace nested under `suffix`.
+    pub fn subspace<S: AsRef<[u8]>>(&self, suffix: S) -> Subspace {
+        Subspace { prefix: self.key(suffix) }
+    }
+
+    /// All keys of the subspace, except the prefix itself. For prefixes consisting only of 0xFF
+    /// bytes, which have no upper bound, keys starting with another 0xFF byte are left out.
+    pub fn range(&self) -> KeyRange {
+        let end = self.prefix.strinc().unwrap_or_else(|| self.key([0xff]));
+        KeyRange::new(self.key([0x00]), end)
+    }
+
+    pub fn contains<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        key.as_ref().starts_with(&self.prefix)
+    }
+
+    /// The part of `key` after the prefix, or `None` if the key is not in the subspace.
+    pub fn strip<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        key.strip_prefix(self.prefix.as_slice())
+    }
+}
+
+impl<K: Into<Key>> From<K> for Subspace {
+    fn from(prefix: K) -> Self {
+        Subspace::new(prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let subspace = Subspace::new("app/");
+        assert_eq!(subspace.key("a"), Key::from("app/a"));
+        assert_eq!(subspace.subspace("users/").key("1"), Key::from("app/users/1"));
+        assert_eq!(subspace.strip(b"app/a"), Some(b"a".as_slice()));
+        assert_eq!(subspace.strip(b"other"), None);
+    }
+
+    #[test]
+    fn range() {
+        let range = Subspace::new("app/").range();
+        assert!(range.contains("app/a"));
+        assert!(range.contains(b"app/\x00".as_slice()));
+        assert!(range.contains(b"app/\xff\xff".as_slice()));
+        assert!(!range.contains("app/"));
+        assert!(!range.contains("app0"));
+    }
+}
@@ -1,6 +1,11 @@
+use std::fmt::{Debug, Formatter};
 use std::ops::Deref;
 
-#[derive(Eq, PartialEq, Debug)]
+use bytes::Bytes;
+
+use crate::types::printable;
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct Value(Vec<u8>);
 
 impl Deref for Value {
@@ -10,14 +15,50 @@ impl Deref for Value {
     }
 }
 
+impl AsRef<[u8]> for Value {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Debug for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", printable(&self.0))
+    }
+}
+
 impl From<&str> for Value {
     fn from(value: &str) -> Self {
         Value(value.as_bytes().to_vec())
     }
 }
 
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value(value.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Value(value.to_vec())
+    }
+}
+
 impl From<Vec<u8>> for Value {
     fn from(value: Vec<u8>) -> Self {
         Self(value)
     }
-}
\ No newline at end of file
+}
+
+impl From<Bytes> for Value {
+    fn from(value: Bytes) -> Self {
+        Value(value.to_vec())
+    }
+}
+
+impl From<Value> for Vec<u8> {
+    fn from(value: Value) -> Self {
+        value.0
+    }
+}
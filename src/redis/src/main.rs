@@ -17,7 +17,7 @@ mod test;
 async fn main() -> Result<(), io::Error> {
     let listener = TcpListener::bind("127.0.0.1:1234").await.expect("Could not bind to port");
     listener.set_ttl(100).expect("Could not set TTL");
-    let fdb_client = fdb::Client::new().await.expect("Could not initialize foundation db client");
+    let fdb_client = fdb::Client::new().expect("Could not initialize foundation db client");
     let db = fdb_client.database().unwrap();
     
     loop {